@@ -0,0 +1,63 @@
+// Compare the SIMD math against the scalar fallback
+// Run with `cargo bench --bench math`, add `--features f64` for double precision
+// (and `RUSTFLAGS="-C target-cpu=native"` so that the AVX2 path is used)
+use ray_tracer::simd::{self, scalar};
+use ray_tracer::{point, vector, Matrix, Tuple};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10_000_000;
+
+// Time `ITERATIONS` calls of `f`
+fn time<T>(mut f: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, simd_time: Duration, scalar_time: Duration) {
+    println!(
+        "{:<16} simd: {:>8.2} ns  scalar: {:>8.2} ns  speedup: {:.2}x",
+        name,
+        simd_time.as_nanos() as f64 / ITERATIONS as f64,
+        scalar_time.as_nanos() as f64 / ITERATIONS as f64,
+        scalar_time.as_secs_f64() / simd_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let a = Tuple::new(1.5, -2.0, 3.25, 0.0);
+    let b = Tuple::new(-4.0, 0.5, 2.0, 1.0);
+    let m = Matrix::get_translation_matrix(1.0, 2.0, 3.0)
+        * Matrix::get_rotation_y_matrix(0.5)
+        * Matrix::get_scaling_matrix(2.0, 0.5, 1.5);
+    let n = Matrix::get_view_transform(
+        point(-4.0, 0.5, 2.0),
+        point(1.5, -2.0, 3.25),
+        vector(0.0, 1.0, 0.0),
+    );
+
+    println!("{} iterations of each operation", ITERATIONS);
+    report(
+        "dot",
+        time(|| simd::dot(black_box(&a), black_box(&b))),
+        time(|| scalar::dot(black_box(&a), black_box(&b))),
+    );
+    report(
+        "cross",
+        time(|| simd::cross(black_box(&a), black_box(&b))),
+        time(|| scalar::cross(black_box(&a), black_box(&b))),
+    );
+    report(
+        "matrix * tuple",
+        time(|| simd::mul_matrix_tuple(black_box(&m), black_box(&b))),
+        time(|| scalar::mul_matrix_tuple(black_box(&m), black_box(&b))),
+    );
+    report(
+        "matrix * matrix",
+        time(|| simd::mul_matrix_matrix(black_box(&m), black_box(&n))),
+        time(|| scalar::mul_matrix_matrix(black_box(&m), black_box(&n))),
+    );
+}
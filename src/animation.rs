@@ -0,0 +1,494 @@
+use crate::Float;
+use crate::Keyframe;
+use crate::Matrix;
+use crate::Pbr;
+use crate::Quaternion;
+use crate::Scene;
+use crate::{vector, Color, Point3, Vector3};
+use std::fs;
+
+// Steps of the bisection solving the x of a Bézier easing curve, plenty for both precisions
+const BEZIER_STEPS: u32 = 40;
+
+// How the value goes from one key to the next
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    // Constant speed
+    Linear,
+    // Starts and stops smoothly (smoothstep)
+    Ease,
+    // Cubic Bézier timing curve from (0, 0) to (1, 1) with the control points (x1, y1) and (x2, y2),
+    // like `cubic-bezier` in CSS. x1 and x2 should be between 0 and 1
+    Bezier {
+        x1: Float,
+        y1: Float,
+        x2: Float,
+        y2: Float,
+    },
+}
+
+// Values which can be animated, `t` = 0 gives `self` and `t` = 1 gives `other`
+pub trait Interpolate: Copy {
+    fn interpolate(&self, other: &Self, t: Float) -> Self;
+}
+
+// Value of a track at one instant, `easing` is used on the way to the next key
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Key<T> {
+    pub time: Float,
+    pub value: T,
+    pub easing: Easing,
+}
+
+// Keys of one animated value, sorted by time
+// Before the first key and after the last one the value stays the same
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>,
+}
+
+// Transformation made of animated translation, rotation and scale, applied on top of the
+// transformation of the object. Scaled, then rotated, then translated like a `Keyframe`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformTrack {
+    pub translation: Track<Vector3>,
+    pub rotation: Track<Quaternion>,
+    pub scale: Track<Vector3>,
+}
+
+// Animated value of the material of an object
+#[derive(Clone, Debug, PartialEq)]
+pub enum MaterialTrack {
+    Color(Track<Color>),
+    Emission(Track<Color>),
+    Ambient(Track<Float>),
+    Diffuse(Track<Float>),
+    Specular(Track<Float>),
+    Shininess(Track<Float>),
+    Metallic(Track<Float>),
+    Roughness(Track<Float>),
+}
+
+// Part of a scene driven by a track, objects are given by their id (see `World::add_object`), so
+// adding or removing other objects does not move the animation to another one
+#[derive(Clone, Debug, PartialEq)]
+pub enum Channel {
+    Transform(u64, TransformTrack),
+    Material(u64, MaterialTrack),
+    LightPosition(Track<Point3>),
+    LightIntensity(Track<Color>),
+    // Camera looking from `from` to `to`, replaces the view of the scene
+    CameraView {
+        from: Track<Point3>,
+        to: Track<Point3>,
+        up: Vector3,
+    },
+    FieldOfView(Track<Float>),
+}
+
+// Scene changing over time, times are in seconds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Animation {
+    pub channels: Vec<Channel>,
+}
+
+impl Easing {
+    // Eased progress for the linear progress `t` between 0 and 1
+    pub fn apply(&self, t: Float) -> Float {
+        match *self {
+            Easing::Linear => t,
+            Easing::Ease => t * t * (3.0 - 2.0 * t),
+            Easing::Bezier { x1, y1, x2, y2 } => {
+                // x grows with the curve parameter, find the parameter where it reaches `t`
+                let (mut low, mut high): (Float, Float) = (0.0, 1.0);
+                for _ in 0..BEZIER_STEPS {
+                    let middle = (low + high) / 2.0;
+                    if bezier(middle, x1, x2) < t {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                bezier((low + high) / 2.0, y1, y2)
+            }
+        }
+    }
+}
+
+// Coordinate of the cubic Bézier curve from 0 to 1 with the control points `p1` and `p2`
+fn bezier(s: Float, p1: Float, p2: Float) -> Float {
+    let r = 1.0 - s;
+    3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+}
+
+impl Interpolate for Float {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Point3 {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Interpolate for Vector3 {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+// Rotations are slerped, so they turn at a constant speed
+impl Interpolate for Quaternion {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        self.slerp(other, t)
+    }
+}
+
+impl<T: Interpolate> Track<T> {
+    // Track with a single key, the value stays the same until more keys are added
+    pub fn new(time: Float, value: T) -> Self {
+        Self {
+            keys: vec![Key {
+                time,
+                value,
+                easing: Easing::Linear,
+            }],
+        }
+    }
+
+    // Add a key, `easing` is used from it to the next key
+    // A key at the same time as an existing one comes after it, so the value jumps there
+    pub fn key(mut self, time: Float, value: T, easing: Easing) -> Self {
+        let ind = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(
+            ind,
+            Key {
+                time,
+                value,
+                easing,
+            },
+        );
+        self
+    }
+
+    pub fn keys(&self) -> &[Key<T>] {
+        &self.keys
+    }
+
+    pub fn value_at(&self, time: Float) -> T {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keys[0].value;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].value;
+        }
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        a.value.interpolate(&b.value, a.easing.apply(t))
+    }
+}
+
+impl TransformTrack {
+    // No transformation at any time, set the tracks which move
+    pub fn new() -> Self {
+        Self {
+            translation: Track::new(0.0, vector(0.0, 0.0, 0.0)),
+            rotation: Track::new(0.0, Quaternion::identity()),
+            scale: Track::new(0.0, vector(1.0, 1.0, 1.0)),
+        }
+    }
+
+    pub fn keyframe_at(&self, time: Float) -> Keyframe {
+        Keyframe::new(
+            time,
+            self.translation.value_at(time),
+            self.rotation.value_at(time),
+            self.scale.value_at(time),
+        )
+    }
+
+    pub fn matrix_at(&self, time: Float) -> Matrix {
+        self.keyframe_at(time).to_matrix()
+    }
+}
+
+impl Default for TransformTrack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, channel: Channel) {
+        self.channels.push(channel);
+    }
+
+    // Copy of `scene` with every channel set to its value at `time`, and the shutter of the
+    // camera opening `time` later, so objects with a `motion` blur where they are at `time`
+    // Fails when a channel drives an object which is not in the scene
+    pub fn scene_at(&self, scene: &Scene, time: Float) -> Result<Scene, String> {
+        let mut scene = scene.clone();
+        scene.camera.shutter_open += time;
+        scene.camera.shutter_close += time;
+        let missing = |id: u64| format!("Channel for object {}, which is not in the scene", id);
+        for channel in &self.channels {
+            match channel {
+                Channel::Transform(id, track) => {
+                    let object = scene.world.object_mut(*id).ok_or(missing(*id))?;
+                    object.set_transform(track.matrix_at(time) * object.transform());
+                }
+                Channel::Material(id, track) => {
+                    let object = scene.world.object_mut(*id).ok_or(missing(*id))?;
+                    let material = &mut object.material;
+                    match track {
+                        MaterialTrack::Color(track) => material.color = track.value_at(time),
+                        MaterialTrack::Emission(track) => material.emission = track.value_at(time),
+                        MaterialTrack::Ambient(track) => material.ambient = track.value_at(time),
+                        MaterialTrack::Diffuse(track) => material.diffuse = track.value_at(time),
+                        MaterialTrack::Specular(track) => material.specular = track.value_at(time),
+                        MaterialTrack::Shininess(track) => {
+                            material.shininess = track.value_at(time)
+                        }
+                        MaterialTrack::Metallic(track) => {
+                            material.pbr.get_or_insert_with(Pbr::default).metallic =
+                                track.value_at(time)
+                        }
+                        MaterialTrack::Roughness(track) => {
+                            material.pbr.get_or_insert_with(Pbr::default).roughness =
+                                track.value_at(time)
+                        }
+                    }
+                }
+                Channel::LightPosition(track) => scene.world.light.position = track.value_at(time),
+                Channel::LightIntensity(track) => {
+                    scene.world.light.intensity = track.value_at(time)
+                }
+                Channel::CameraView { from, to, up } => {
+                    scene.camera.set_transform(Matrix::get_view_transform(
+                        from.value_at(time),
+                        to.value_at(time),
+                        *up,
+                    ));
+                }
+                Channel::FieldOfView(track) => scene.camera.set_field_of_view(track.value_at(time)),
+            }
+        }
+        Ok(scene)
+    }
+
+    // Render `frames` frames, `fps` per second starting at time 0, to `directory` as
+    // frame_0001.png, frame_0002.png... The directory is created if needed
+    // Returns the paths of the images
+    pub fn render_frames(
+        &self,
+        scene: &Scene,
+        frames: u32,
+        fps: Float,
+        directory: &str,
+    ) -> Result<Vec<String>, String> {
+        if fps <= 0.0 || !fps.is_finite() {
+            return Err(format!("Frames per second must be positive, found {}", fps));
+        }
+        fs::create_dir_all(directory)
+            .map_err(|err| format!("Unable to create directory {}: {}", directory, err))?;
+        let mut paths = Vec::new();
+        for frame in 0..frames {
+            let time = frame as Float / fps;
+            let frame_scene = self.scene_at(scene, time)?;
+            let canvas = frame_scene.camera.render(&frame_scene.world);
+            let path = format!("{}/frame_{:04}.png", directory, frame + 1);
+            fs::write(&path, canvas.get_png())
+                .map_err(|err| format!("Unable to write {}: {}", path, err))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod animation_tests {
+    use super::*;
+    use crate::consts::{FRAC_PI_2, FRAC_PI_3};
+    use crate::{point, Camera, Compare, World};
+
+    fn test_scene() -> Scene {
+        let mut camera = Camera::new(11, 11, FRAC_PI_2);
+        camera.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        Scene {
+            camera,
+            world: World::default(),
+        }
+    }
+
+    #[test]
+    fn easing_curves() {
+        for easing in [
+            Easing::Linear,
+            Easing::Ease,
+            Easing::Bezier {
+                x1: 0.42,
+                y1: 0.0,
+                x2: 0.58,
+                y2: 1.0,
+            },
+        ] {
+            assert!(easing.apply(0.0).eq(0.0));
+            assert!(easing.apply(0.5).eq(0.5));
+            assert!(easing.apply(1.0).eq(1.0));
+        }
+        assert!(Easing::Ease.apply(0.25) < 0.25);
+        // Control points on the diagonal make a straight line
+        let straight = Easing::Bezier {
+            x1: 0.25,
+            y1: 0.25,
+            x2: 0.75,
+            y2: 0.75,
+        };
+        assert!(straight.apply(0.3).eq(0.3));
+        let ease_out = Easing::Bezier {
+            x1: 0.0,
+            y1: 0.0,
+            x2: 0.58,
+            y2: 1.0,
+        };
+        assert!(ease_out.apply(0.25) > 0.25);
+    }
+
+    #[test]
+    fn track_interpolates_between_keys() {
+        let track = Track::new(1.0, 0.0)
+            .key(3.0, 10.0, Easing::Linear)
+            .key(2.0, 2.0, Easing::Ease);
+        assert_eq!(track.keys().len(), 3);
+        assert!(track.value_at(0.0).eq(0.0));
+        assert!(track.value_at(1.5).eq(1.0));
+        assert!(track.value_at(2.0).eq(2.0));
+        // Eased from 2 to 10, halfway is still halfway
+        assert!(track.value_at(2.5).eq(6.0));
+        assert!(track.value_at(2.25) < 4.0);
+        assert!(track.value_at(5.0).eq(10.0));
+    }
+
+    #[test]
+    fn transform_track_turns_and_moves() {
+        let mut track = TransformTrack::new();
+        track.rotation = Track::new(0.0, Quaternion::identity()).key(
+            1.0,
+            Quaternion::from_axis_angle(vector(0.0, 0.0, 1.0), FRAC_PI_2),
+            Easing::Linear,
+        );
+        track.translation =
+            Track::new(0.0, vector(0.0, 0.0, 0.0)).key(1.0, vector(0.0, 0.0, 4.0), Easing::Linear);
+        assert_eq!(track.matrix_at(-1.0), Matrix::I());
+        let p = track.matrix_at(1.0) * point(1.0, 0.0, 0.0);
+        assert_eq!(p, point(0.0, 1.0, 4.0));
+    }
+
+    #[test]
+    fn scene_at_sets_the_channels() {
+        let base = test_scene();
+        let mut animation = Animation::new();
+        let mut transform = TransformTrack::new();
+        transform.translation =
+            Track::new(0.0, vector(0.0, 0.0, 0.0)).key(2.0, vector(2.0, 0.0, 0.0), Easing::Linear);
+        animation.add(Channel::Transform(2, transform));
+        animation.add(Channel::Material(
+            1,
+            MaterialTrack::Roughness(Track::new(0.0, 0.0).key(2.0, 1.0, Easing::Linear)),
+        ));
+        animation.add(Channel::LightPosition(
+            Track::new(0.0, point(0.0, 0.0, 0.0)).key(2.0, point(0.0, 4.0, 0.0), Easing::Linear),
+        ));
+        animation.add(Channel::FieldOfView(Track::new(0.0, FRAC_PI_3)));
+
+        let scene = animation.scene_at(&base, 1.0).unwrap();
+        // Translation is applied after the scaling of the sphere
+        assert_eq!(
+            scene.world.objects()[1].transform(),
+            Matrix::get_translation_matrix(1.0, 0.0, 0.0) * base.world.objects()[1].transform()
+        );
+        assert!(scene.world.objects()[0]
+            .material
+            .pbr
+            .unwrap()
+            .roughness
+            .eq(0.5));
+        assert_eq!(scene.world.light.position, point(0.0, 2.0, 0.0));
+        assert!(scene.camera.field_of_view().eq(FRAC_PI_3));
+        // The shutter opens at the time of the frame
+        assert!(scene.camera.shutter_open.eq(1.0));
+        assert!(scene.camera.shutter_close.eq(1.0));
+        // The base scene is left as it was
+        assert_eq!(base.world.objects()[0].material.pbr, None);
+        assert!(base.camera.field_of_view().eq(FRAC_PI_2));
+    }
+
+    #[test]
+    fn channels_of_missing_objects_fail() {
+        let mut animation = Animation::new();
+        animation.add(Channel::Material(
+            5,
+            MaterialTrack::Diffuse(Track::new(0.0, 0.5)),
+        ));
+        let err = animation.scene_at(&test_scene(), 0.0).err().unwrap();
+        assert_eq!(err, "Channel for object 5, which is not in the scene");
+
+        // Channels follow their object when the others go away
+        let mut scene = test_scene();
+        scene.world.remove_object(1);
+        let mut animation = Animation::new();
+        animation.add(Channel::Material(
+            2,
+            MaterialTrack::Diffuse(Track::new(0.0, 0.5)),
+        ));
+        let animated = animation.scene_at(&scene, 0.0).unwrap();
+        assert!(animated.world.objects()[0].material.diffuse.eq(0.5));
+        animation.add(Channel::Material(
+            1,
+            MaterialTrack::Diffuse(Track::new(0.0, 0.5)),
+        ));
+        assert!(animation.scene_at(&scene, 0.0).is_err());
+        let err = Animation::new()
+            .render_frames(&test_scene(), 1, 0.0, "unused")
+            .err()
+            .unwrap();
+        assert_eq!(err, "Frames per second must be positive, found 0");
+    }
+
+    #[test]
+    fn rendering_numbered_frames() {
+        let scene = test_scene();
+        let mut animation = Animation::new();
+        animation.add(Channel::LightIntensity(
+            Track::new(0.0, Color::white()).key(1.0, Color::black(), Easing::Linear),
+        ));
+        let directory = std::env::temp_dir().join(format!("frames_{}", std::process::id()));
+        let directory = directory.to_str().unwrap();
+        let paths = animation.render_frames(&scene, 3, 2.0, directory).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[2].ends_with("frame_0003.png"));
+        // The light fades out, the last frame at time 1 is black
+        let first = crate::Canvas::read_image(&paths[0]).unwrap();
+        let last = crate::Canvas::read_image(&paths[2]).unwrap();
+        assert!(first.pixel_at(5, 5).red > 0.0);
+        assert_eq!(last.pixel_at(5, 5), Color::black());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
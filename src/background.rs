@@ -0,0 +1,240 @@
+use crate::consts::PI;
+use crate::utils::Rng;
+use crate::Float;
+use crate::{vector, Canvas, Color, Vector3};
+
+// What rays which miss every object see, and the light coming from infinitely far away
+#[derive(Clone)]
+pub enum Background {
+    Solid(Color),
+    // Blend from `bottom` (looking straight down) to `top` (looking straight up)
+    Gradient { bottom: Color, top: Color },
+    Environment(EnvironmentMap),
+}
+
+impl Background {
+    // Color seen when looking in `direction` (a unit vector)
+    pub fn color(&self, direction: Vector3) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = (direction.y + 1.0) / 2.0;
+                *bottom * (1.0 - t) + *top * t
+            }
+            Background::Environment(map) => map.color(direction),
+        }
+    }
+
+    // Whether the background gives off any light at all
+    pub fn is_black(&self) -> bool {
+        match self {
+            Background::Solid(color) => color.max_component() <= 0.0,
+            Background::Gradient { bottom, top } => {
+                bottom.max_component() <= 0.0 && top.max_component() <= 0.0
+            }
+            Background::Environment(map) => map.total <= 0.0,
+        }
+    }
+
+    // Random direction to sample the light of the background in, with its probability density
+    // (per solid angle). Environment maps are importance sampled, bright areas are picked more often
+    pub fn sample(&self, rng: &mut Rng) -> (Vector3, Float) {
+        match self {
+            Background::Environment(map) => map.sample(rng),
+            _ => {
+                let z = 1.0 - 2.0 * rng.next_float();
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * rng.next_float();
+                (vector(r * phi.cos(), r * phi.sin(), z), 1.0 / (4.0 * PI))
+            }
+        }
+    }
+
+    // Probability density of `sample` returning `direction`
+    pub fn pdf(&self, direction: Vector3) -> Float {
+        match self {
+            Background::Environment(map) => map.pdf(direction),
+            _ => 1.0 / (4.0 * PI),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Color::black())
+    }
+}
+
+// Equirectangular (latitude/longitude) image of everything around the scene
+// The center of the image is seen when looking towards -z, the top row when looking up (+y)
+// Each pixel is sampled with a probability proportional to its brightness and solid angle
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    image: Canvas,
+    intensity: Float, // Multiplies the colors of the image
+    // Cumulative distribution of the rows, then of the columns inside each row
+    row_cdf: Vec<Float>,
+    col_cdf: Vec<Vec<Float>>,
+    total: Float, // Sum of the weights of all the pixels
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Canvas, intensity: Float) -> Self {
+        let (width, height) = (image.width, image.height);
+        let mut row_cdf = Vec::with_capacity(height + 1);
+        let mut col_cdf = Vec::with_capacity(height);
+        row_cdf.push(0.0);
+        let mut total = 0.0;
+        for row in 0..height {
+            // Rows near the poles cover a smaller solid angle
+            let sin_theta = ((row as Float + 0.5) / height as Float * PI).sin();
+            let mut cdf = Vec::with_capacity(width + 1);
+            cdf.push(0.0);
+            let mut row_total = 0.0;
+            for col in 0..width {
+                row_total += image.pixel_at(col, row).luminance().max(0.0) * sin_theta;
+                cdf.push(row_total);
+            }
+            total += row_total;
+            row_cdf.push(total);
+            col_cdf.push(cdf);
+        }
+        Self {
+            image,
+            intensity,
+            row_cdf,
+            col_cdf,
+            total,
+        }
+    }
+
+    // Pixel seen when looking in `direction`
+    fn pixel(&self, direction: Vector3) -> (usize, usize) {
+        let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+        let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+        let col = ((u * self.image.width as Float) as usize).min(self.image.width - 1);
+        let row = ((v * self.image.height as Float) as usize).min(self.image.height - 1);
+        (col, row)
+    }
+
+    // Direction through the point (`u`, `v`) of the image, both between 0 and 1
+    fn direction(u: Float, v: Float) -> Vector3 {
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = v * PI;
+        vector(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    pub fn color(&self, direction: Vector3) -> Color {
+        let (col, row) = self.pixel(direction);
+        self.image.pixel_at(col, row) * self.intensity
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> (Vector3, Float) {
+        if self.total <= 0.0 {
+            let u = rng.next_float();
+            let v = rng.next_float();
+            let direction = Self::direction(u, v);
+            return (direction, self.pdf(direction));
+        }
+        let row = find_interval(&self.row_cdf, rng.next_float() * self.total);
+        let cdf = &self.col_cdf[row];
+        let col = find_interval(cdf, rng.next_float() * cdf[cdf.len() - 1]);
+        // Uniformly inside the pixel
+        let u = (col as Float + rng.next_float()) / self.image.width as Float;
+        let v = (row as Float + rng.next_float()) / self.image.height as Float;
+        let direction = Self::direction(u, v);
+        (direction, self.pdf(direction))
+    }
+
+    pub fn pdf(&self, direction: Vector3) -> Float {
+        let (width, height) = (self.image.width as Float, self.image.height as Float);
+        let sin_theta = (1.0 - direction.y * direction.y).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // Density over the image (u, v) is turned into density per solid angle, the image covers
+        // 2 pi * pi of (phi, theta) and a small patch of solid angle is sin(theta) dphi dtheta
+        let uv_pdf = if self.total <= 0.0 {
+            1.0
+        } else {
+            let (col, row) = self.pixel(direction);
+            let cdf = &self.col_cdf[row];
+            (cdf[col + 1] - cdf[col]) / self.total * width * height
+        };
+        uv_pdf / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// Index `i` for which cdf[i] <= value < cdf[i + 1], skipping the empty intervals
+fn find_interval(cdf: &[Float], value: Float) -> usize {
+    let ind = cdf.partition_point(|&c| c <= value);
+    ind.clamp(1, cdf.len() - 1) - 1
+}
+
+#[cfg(test)]
+mod background_tests {
+    use super::*;
+    use crate::Compare;
+
+    fn environment(bright: (usize, usize)) -> EnvironmentMap {
+        let mut image = Canvas::new(8, 4);
+        image.fill(Color::new(0.1, 0.1, 0.1));
+        image.write_pixel(bright.0, bright.1, Color::new(50.0, 40.0, 30.0));
+        EnvironmentMap::new(image, 2.0)
+    }
+
+    #[test]
+    fn gradient_background() {
+        let b = Background::Gradient {
+            bottom: Color::black(),
+            top: Color::new(0.2, 0.4, 1.0),
+        };
+        assert_eq!(b.color(vector(0.0, 1.0, 0.0)), Color::new(0.2, 0.4, 1.0));
+        assert_eq!(b.color(vector(0.0, -1.0, 0.0)), Color::black());
+        assert_eq!(b.color(vector(1.0, 0.0, 0.0)), Color::new(0.1, 0.2, 0.5));
+        assert!(Background::default().is_black() && !b.is_black());
+    }
+
+    #[test]
+    fn environment_map_lookup() {
+        let map = environment((4, 1));
+        // Center of the image is ahead (-z), slightly above the horizon
+        let (col, row) = map.pixel(vector(0.0, 0.2, -1.0).normalize());
+        assert_eq!((col, row), (4, 1));
+        assert_eq!(
+            map.color(vector(0.0, 0.2, -1.0).normalize()),
+            Color::new(100.0, 80.0, 60.0)
+        );
+        assert_eq!(map.color(vector(0.0, -1.0, 0.0)), Color::new(0.2, 0.2, 0.2));
+        for (u, v) in [(0.1, 0.2), (0.6, 0.9), (0.95, 0.5)] {
+            let d = EnvironmentMap::direction(u, v);
+            assert!(d.magnitude().eq(1.0));
+            assert_eq!(map.pixel(d), ((u * 8.0) as usize, (v * 4.0) as usize));
+        }
+    }
+
+    #[test]
+    fn environment_map_importance_sampling() {
+        let map = environment((2, 2));
+        let mut rng = Rng::new(2);
+        let mut bright = 0;
+        let mut estimate = 0.0;
+        let count = 20000;
+        for _ in 0..count {
+            let (d, pdf) = map.sample(&mut rng);
+            assert!(pdf.eq(map.pdf(d)));
+            if map.pixel(d) == (2, 2) {
+                bright += 1;
+            }
+            // Integral of the pdf over the sphere, estimated with uniform directions, should be 1
+            let (u, _) = Background::Solid(Color::black()).sample(&mut rng);
+            estimate += map.pdf(u) * 4.0 * PI / count as Float;
+        }
+        assert!(bright > count * 9 / 10);
+        assert!((estimate - 1.0).abs() < 0.1, "{}", estimate);
+    }
+}
@@ -42,5 +42,5 @@ fn main() {
     }
 
     let ppm_string = canvas.get_ppm();
-    canvas.write_ppm(&ppm_string, "chapter2_end.ppm");
+    canvas.write_ppm(&ppm_string, "chapter2_end.ppm").unwrap();
 }
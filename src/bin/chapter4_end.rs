@@ -1,5 +1,5 @@
-use ray_tracer::{point, Canvas, Color, Matrix};
-use std::f32::consts::PI;
+use ray_tracer::consts::PI;
+use ray_tracer::{point, Canvas, Color, Float, Matrix};
 
 // Draw pixels around a circle where hours are present in a clock, center of clock is center of canvas
 fn main() {
@@ -7,8 +7,8 @@ fn main() {
     let hour_color = Color::white();
 
     let translate_to_canvas_centre = Matrix::get_translation_matrix(
-        (canvas.width as f32) / 2.0,
-        (canvas.height as f32) / 2.0,
+        (canvas.width as Float) / 2.0,
+        (canvas.height as Float) / 2.0,
         0.0,
     );
 
@@ -29,5 +29,5 @@ fn main() {
 
     // Save the canvas to a file
     let ppm_string = canvas.get_ppm();
-    canvas.write_ppm(&ppm_string, "chapter4_end.ppm");
+    canvas.write_ppm(&ppm_string, "chapter4_end.ppm").unwrap();
 }
@@ -1,5 +1,5 @@
-use ray_tracer::{point, Canvas, Color, Ray, Sphere};
-// use std::f32::consts::PI;
+use ray_tracer::{point, Canvas, Color, Float, Ray, Sphere};
+// use ray_tracer::consts::PI;
 
 // Cast a shadow of a sphere on a canvas located at `canvas_z` parallel to the `xy` axis
 // Ray is cast from the +ve z coordinate towards the canvas
@@ -7,7 +7,7 @@ use ray_tracer::{point, Canvas, Color, Ray, Sphere};
 // If it hits the sphere, then, we draw that point on the canvas
 fn main() {
     let canvas_size: usize = 350; // in pixels
-    let canvas_z: f32 = -200.0;
+    let canvas_z: Float = -200.0;
     let ray_origin = point(0.0, 0.0, 2.0);
     let sphere_color = Color::new(1.0, 0.0, 0.0);
 
@@ -26,7 +26,7 @@ fn main() {
     let half_width: i32 = (canvas_size as i32) / 2;
     for x in -half_width..half_width {
         for y in (-half_width + 1)..=half_width {
-            let ray_direction = point(x as f32, y as f32, canvas_z) - ray_origin;
+            let ray_direction = point(x as Float, y as Float, canvas_z) - ray_origin;
             let r = Ray::new(ray_origin, ray_direction.normalize());
 
             let xs = s.intersect(r);
@@ -40,5 +40,5 @@ fn main() {
     }
 
     let ppm_string = canvas.get_ppm();
-    canvas.write_ppm(&ppm_string, "chapter5_end.ppm");
+    canvas.write_ppm(&ppm_string, "chapter5_end.ppm").unwrap();
 }
@@ -0,0 +1,337 @@
+use ray_tracer::{run_worker, AdaptiveSampling, Canvas, Coordinator, Integrator, RenderJob};
+use ray_tracer::{Scene, Tile, TileOrder};
+use std::fs;
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: render <scene file> [options]
+       render --worker <address> [--threads <n>]
+
+Options:
+  -o, --output <path>         output image (default: render.ppm)
+  -f, --format <ppm|png>      output format (default: guessed from the output extension)
+  -r, --resolution <WxH>      overrides the resolution of the scene camera
+  -s, --samples <n>           samples per pixel (default: 1)
+  -a, --adaptive <min,max,threshold>
+                              samples per pixel between min and max, until the standard error
+                              of the pixel brightness is below threshold (replaces --samples)
+  -t, --threads <n>           render threads (default: number of cpus)
+  -i, --integrator <whitted|path>
+                              direct lighting or path tracing (default: whitted)
+  -b, --bounces <n>           maximum bounces of a path traced ray (default: 8)
+  -c, --crop <x,y,width,height>
+                              only render this window of the image
+  -T, --tiles <size>          render in tiles of size x size pixels, printing the progress
+      --tile-order <scanline|spiral|hilbert>
+                              order the tiles are rendered in (default: scanline)
+  -p, --progressive           save the image after every pass of rising samples per pixel
+  -l, --listen <address>      hand out the tiles (32x32 pixels unless --tiles is given) to the
+                              workers connecting to this address, like 0.0.0.0:7878
+      --tile-timeout <seconds>
+                              seconds a worker gets for a tile before it is handed to
+                              another one, the render fails after 3 tries (default: 300)
+  -w, --worker <address>      render tiles for the coordinator at this address, like
+                              192.168.1.10:7878, with the scene it sends
+  -h, --help                  print this message";
+
+enum Format {
+    Ppm,
+    Png,
+}
+
+struct Options {
+    scene_path: String,
+    output: String,
+    format: Option<Format>,
+    resolution: Option<(u16, u16)>,
+    samples: u16,
+    adaptive: Option<AdaptiveSampling>,
+    threads: usize,
+    path_tracing: bool,
+    bounces: u16,
+    crop: Option<(u16, u16, u16, u16)>,
+    tile_size: Option<u16>,
+    tile_order: TileOrder,
+    progressive: bool,
+    listen: Option<String>,
+    tile_timeout: Option<Duration>,
+    worker: Option<String>,
+}
+
+// Parse the `,` or `x` separated list of `count` numbers of the option `name`
+fn parse_list(name: &str, value: &str, count: usize) -> Result<Vec<u16>, String> {
+    let numbers = value
+        .split([',', 'x'])
+        .map(|number| number.trim().parse::<u16>())
+        .collect::<Result<Vec<u16>, _>>()
+        .map_err(|_| format!("Invalid value `{}` for {}", value, name))?;
+    if numbers.len() != count {
+        return Err(format!(
+            "{} expects {} numbers, found `{}`",
+            name, count, value
+        ));
+    }
+    Ok(numbers)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut scene_path: Option<String> = None;
+    let mut options = Options {
+        scene_path: String::new(),
+        output: String::from("render.ppm"),
+        format: None,
+        resolution: None,
+        samples: 1,
+        adaptive: None,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        path_tracing: false,
+        bounces: 8,
+        crop: None,
+        tile_size: None,
+        tile_order: TileOrder::Scanline,
+        progressive: false,
+        listen: None,
+        tile_timeout: None,
+        worker: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        if arg == "-p" || arg == "--progressive" {
+            options.progressive = true;
+            continue;
+        }
+        if !arg.starts_with('-') {
+            if scene_path.is_some() {
+                return Err(format!("Unexpected argument `{}`", arg));
+            }
+            scene_path = Some(arg.clone());
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "-o" | "--output" => options.output = value.clone(),
+            "-f" | "--format" => {
+                options.format = match value.to_lowercase().as_str() {
+                    "ppm" => Some(Format::Ppm),
+                    "png" => Some(Format::Png),
+                    _ => return Err(format!("Unknown format `{}`", value)),
+                }
+            }
+            "-r" | "--resolution" => {
+                let size = parse_list(arg, value, 2)?;
+                options.resolution = Some((size[0], size[1]));
+            }
+            "-s" | "--samples" => {
+                options.samples = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value `{}` for {}", value, arg))?;
+            }
+            "-a" | "--adaptive" => {
+                let invalid = || format!("Invalid value `{}` for {}", value, arg);
+                let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+                if parts.len() != 3 {
+                    return Err(format!("{} expects 3 numbers, found `{}`", arg, value));
+                }
+                options.adaptive = Some(AdaptiveSampling::new(
+                    parts[0].parse().map_err(|_| invalid())?,
+                    parts[1].parse().map_err(|_| invalid())?,
+                    parts[2].parse().map_err(|_| invalid())?,
+                ));
+            }
+            "-t" | "--threads" => {
+                options.threads = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value `{}` for {}", value, arg))?;
+            }
+            "-i" | "--integrator" => {
+                options.path_tracing = match value.to_lowercase().as_str() {
+                    "whitted" => false,
+                    "path" => true,
+                    _ => return Err(format!("Unknown integrator `{}`", value)),
+                }
+            }
+            "-b" | "--bounces" => {
+                options.bounces = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value `{}` for {}", value, arg))?;
+            }
+            "-c" | "--crop" => {
+                let window = parse_list(arg, value, 4)?;
+                options.crop = Some((window[0], window[1], window[2], window[3]));
+            }
+            "-T" | "--tiles" => {
+                let size: u16 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value `{}` for {}", value, arg))?;
+                if size == 0 {
+                    return Err(String::from("Tile size must be positive"));
+                }
+                options.tile_size = Some(size);
+            }
+            "--tile-order" => {
+                options.tile_order = match value.to_lowercase().as_str() {
+                    "scanline" => TileOrder::Scanline,
+                    "spiral" => TileOrder::Spiral,
+                    "hilbert" => TileOrder::Hilbert,
+                    _ => return Err(format!("Unknown tile order `{}`", value)),
+                }
+            }
+            "-l" | "--listen" => options.listen = Some(value.clone()),
+            "--tile-timeout" => {
+                let seconds: u64 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value `{}` for {}", value, arg))?;
+                if seconds == 0 {
+                    return Err(String::from("Tile timeout must be positive"));
+                }
+                options.tile_timeout = Some(Duration::from_secs(seconds));
+            }
+            "-w" | "--worker" => options.worker = Some(value.clone()),
+            _ => return Err(format!("Unknown option `{}`", arg)),
+        }
+    }
+
+    if options.tile_timeout.is_some() && options.listen.is_none() {
+        return Err(String::from(
+            "--tile-timeout is for the coordinator, with --listen",
+        ));
+    }
+    if options.worker.is_some() {
+        if scene_path.is_some() {
+            return Err(String::from("Workers get the scene from the coordinator"));
+        }
+        return Ok(options);
+    }
+    options.scene_path = scene_path.ok_or("Missing scene file")?;
+    Ok(options)
+}
+
+// Render a scene file and save the image
+fn run(options: Options) -> Result<(), String> {
+    if let Some(address) = &options.worker {
+        let tiles = run_worker(address, options.threads.max(1))?;
+        eprintln!("Rendered {} tiles", tiles);
+        return Ok(());
+    }
+
+    let source = fs::read_to_string(&options.scene_path)
+        .map_err(|err| format!("Unable to read scene file {}: {}", options.scene_path, err))?;
+    let scene = Scene::parse(&source)?;
+
+    let mut camera = scene.camera;
+    if let Some((hsize, vsize)) = options.resolution {
+        if hsize == 0 || vsize == 0 {
+            return Err(String::from("Resolution must be positive"));
+        }
+        camera.set_resolution(hsize, vsize);
+    }
+    camera.samples_per_pixel = options.samples.max(1);
+    camera.adaptive = options.adaptive;
+    camera.threads = options.threads.max(1);
+    if options.path_tracing {
+        camera.integrator = Integrator::PathTracing {
+            max_bounces: options.bounces,
+        };
+    }
+
+    let format = options
+        .format
+        .unwrap_or(if options.output.to_lowercase().ends_with(".png") {
+            Format::Png
+        } else {
+            Format::Ppm
+        });
+    let save = |canvas: &Canvas| match format {
+        Format::Ppm => canvas.write_ppm(&canvas.get_ppm(), &options.output),
+        Format::Png => canvas.write_png(&canvas.get_png(), &options.output),
+    };
+
+    if options.progressive {
+        if options.crop.is_some() {
+            return Err(String::from(
+                "--progressive renders the whole image, without --crop",
+            ));
+        }
+        if options.listen.is_some() {
+            return Err(String::from(
+                "--progressive renders on this machine, without --listen",
+            ));
+        }
+        if options.adaptive.is_some() {
+            return Err(String::from(
+                "--progressive takes the same samples in every pixel, without --adaptive",
+            ));
+        }
+        let mut saved = Ok(());
+        camera.render_progressive(&scene.world, &AtomicBool::new(false), |progress| {
+            saved = save(progress.image);
+            if saved.is_err() {
+                return false;
+            }
+            eprintln!(
+                "{} samples per pixel, {:.0}% done",
+                progress.samples_per_pixel,
+                progress.fraction * 100.0
+            );
+            true
+        });
+        return saved;
+    }
+
+    let (x, y, width, height) = options
+        .crop
+        .unwrap_or((0, 0, camera.hsize(), camera.vsize()));
+    if x as u32 + width as u32 > camera.hsize() as u32
+        || y as u32 + height as u32 > camera.vsize() as u32
+    {
+        return Err(format!(
+            "Crop window is outside of the {}x{} image",
+            camera.hsize(),
+            camera.vsize()
+        ));
+    }
+    if let Some(address) = &options.listen {
+        let job = RenderJob::new(&source, camera)?;
+        let mut coordinator = Coordinator::bind(address.as_str(), &job)?;
+        if let Some(timeout) = options.tile_timeout {
+            coordinator.tile_timeout = timeout;
+        }
+        eprintln!("Waiting for workers on {}", coordinator.address());
+        let region = Tile::new(x, y, width, height);
+        let size = options.tile_size.unwrap_or(32);
+        let total = region.split(size, options.tile_order).len();
+        let mut done = 0;
+        let image = coordinator.render(region, size, options.tile_order, |_, _| {
+            done += 1;
+            eprintln!("{}/{} tiles done", done, total);
+        })?;
+        return save(&image);
+    }
+    if let Some(size) = options.tile_size {
+        let region = Tile::new(x, y, width, height);
+        let total = region.split(size, options.tile_order).len();
+        let mut done = 0;
+        let image = camera.render_tiles(&scene.world, region, size, options.tile_order, |_, _| {
+            done += 1;
+            eprintln!("{}/{} tiles done", done, total);
+        });
+        return save(&image);
+    }
+    save(&camera.render_region(&scene.world, x, y, width, height))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = parse_args(&args).and_then(run);
+    if let Err(err) = result {
+        eprintln!("render: {}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
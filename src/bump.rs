@@ -0,0 +1,183 @@
+use crate::utils::EPSILON;
+use crate::Canvas;
+use crate::Computation;
+use crate::Float;
+use crate::Pattern;
+use crate::{Point3, Vector3};
+
+// Distance between the points compared to find the slope of a height field
+const SLOPE_STEP: Float = 1e-3;
+
+// Surface detail without extra geometry- the normal used for shading is tilted away from the
+// geometric normal, so the lighting shows dents and scratches that are not really there
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bump {
+    // Tangent space normal map, usually an image texture. Red, green and blue of the pattern are
+    // the x (along the tangent), y (along the bitangent) and z (along the normal) coordinates of
+    // the shading normal, from -1 (0.0) to 1 (1.0). A flat surface is (0.5, 0.5, 1.0)
+    // `strength` scales the tilt, 1.0 is the map as it is
+    NormalMap { map: Pattern, strength: Float },
+    // Height field given by the brightness of the pattern, an image or procedural noise
+    // The surface rises by `amount` (in world units) where the pattern goes from black to white
+    Height { pattern: Pattern, amount: Float },
+}
+
+impl Bump {
+    pub fn normal_map(map: Pattern, strength: Float) -> Self {
+        Bump::NormalMap { map, strength }
+    }
+
+    pub fn height(pattern: Pattern, amount: Float) -> Self {
+        Bump::Height { pattern, amount }
+    }
+
+    // Shading normal at the hit of `comps`
+    pub fn shading_normal(&self, comps: &Computation, textures: &[Canvas]) -> Vector3 {
+        let object = comps.object;
+        let normalv = comps.normalv;
+        let (tangentv, bitangentv) = self.tangent_frame(comps);
+        let tilted = match *self {
+            Bump::NormalMap { map, strength } => {
+                let c = map.color_at(object.world_to_object(comps.point), textures);
+                let (x, y, z) = (2.0 * c.red - 1.0, 2.0 * c.green - 1.0, 2.0 * c.blue - 1.0);
+                tangentv * (x * strength) + bitangentv * (y * strength) + normalv * z
+            }
+            Bump::Height { pattern, amount } => {
+                let height = |p: Point3| {
+                    let c = pattern.color_at(object.world_to_object(p), textures);
+                    amount * (c.red + c.green + c.blue) / 3.0
+                };
+                let center = height(comps.point);
+                let slope_u = (height(comps.point + tangentv * SLOPE_STEP) - center) / SLOPE_STEP;
+                let slope_v = (height(comps.point + bitangentv * SLOPE_STEP) - center) / SLOPE_STEP;
+                normalv - tangentv * slope_u - bitangentv * slope_v
+            }
+        };
+        // A broken map could give a zero vector, keep the geometric normal then
+        let length = tilted.magnitude();
+        if length > 0.0 && length.is_finite() {
+            tilted / length
+        } else {
+            normalv
+        }
+    }
+
+    // Tangent (towards increasing u of the pattern) and bitangent (towards increasing v) at the hit
+    // of `comps`, made exactly perpendicular to its normal, which already faces the eye
+    // Where the mapping has no direction, like at the poles, any perpendicular vector does
+    fn tangent_frame(&self, comps: &Computation) -> (Vector3, Vector3) {
+        let pattern = match *self {
+            Bump::NormalMap { map, .. } => map,
+            Bump::Height { pattern, .. } => pattern,
+        };
+        let object = comps.object;
+        let normalv = comps.normalv;
+        let tangent = object.transform() * pattern.tangent_at(object.world_to_object(comps.point));
+        let tangent = tangent - normalv * normalv.dot(&tangent);
+        let tangentv = if tangent.magnitude() > EPSILON {
+            tangent.normalize()
+        } else {
+            normalv.orthonormal_basis().0
+        };
+        (tangentv, tangentv.cross(&normalv))
+    }
+}
+
+#[cfg(test)]
+mod bump_tests {
+    use super::*;
+    use crate::{point, vector, Color, Compare, Ray, Sphere};
+    use crate::{Intersection, Matrix, UvMapping, WrapMode};
+
+    // Computation of a ray hitting the front of the unit sphere at (0, 0, -1)
+    fn front_hit() -> Computation {
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        Intersection::new(4.0, Sphere::default()).prepare_computations(&r)
+    }
+
+    fn solid_texture(color: Color) -> (Pattern, Vec<Canvas>) {
+        let mut image = Canvas::new(1, 1);
+        image.fill(color);
+        let map = Pattern::texture(0, UvMapping::Spherical, WrapMode::Repeat);
+        (map, vec![image])
+    }
+
+    #[test]
+    fn tangent_frame_follows_the_mapping() {
+        let comps = front_hit();
+        assert_eq!(comps.normalv, vector(0.0, 0.0, -1.0));
+        let (spherical, _) = solid_texture(Color::white());
+        let bump = Bump::normal_map(spherical, 1.0);
+        assert_eq!(
+            bump.tangent_frame(&comps),
+            (vector(1.0, 0.0, 0.0), vector(0.0, 1.0, 0.0))
+        );
+
+        // Planar mapping seen from the top, u along x and v along z
+        let r = Ray::new(point(0.3, 5.0, 0.2), vector(0.0, -1.0, 0.0));
+        let xs = Sphere::default().intersect(r);
+        let comps = xs.hit().unwrap().prepare_computations(&r);
+        let planar = Pattern::texture(0, UvMapping::Planar, WrapMode::Repeat);
+        let (tangentv, bitangentv) = Bump::normal_map(planar, 1.0).tangent_frame(&comps);
+        assert!(tangentv.dot(&comps.normalv).eq(0.0));
+        assert!(tangentv.dot(&vector(1.0, 0.0, 0.0)) > 0.9);
+        assert!(bitangentv.dot(&vector(0.0, 0.0, 1.0)) > 0.9);
+
+        // At the pole of the spherical mapping any frame perpendicular to the normal does
+        let r = Ray::new(point(0.0, 5.0, 0.0), vector(0.0, -1.0, 0.0));
+        let comps = Intersection::new(4.0, Sphere::default()).prepare_computations(&r);
+        let (tangentv, bitangentv) = bump.tangent_frame(&comps);
+        assert!(tangentv.magnitude().eq(1.0));
+        assert!(tangentv.dot(&comps.normalv).eq(0.0));
+        assert!(bitangentv.dot(&comps.normalv).eq(0.0));
+    }
+
+    #[test]
+    fn tangent_frame_inside_uses_the_flipped_normal() {
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let comps = Intersection::new(1.0, Sphere::default()).prepare_computations(&r);
+        assert!(comps.inside);
+        let (map, _) = solid_texture(Color::white());
+        let (tangentv, bitangentv) = Bump::normal_map(map, 1.0).tangent_frame(&comps);
+        // Same frame as seen from outside, with the bitangent flipped along with the normal
+        assert_eq!(comps.normalv, vector(0.0, 0.0, -1.0));
+        assert_eq!(tangentv, vector(-1.0, 0.0, 0.0));
+        assert_eq!(bitangentv, vector(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_the_normal() {
+        let comps = front_hit();
+        let (map, textures) = solid_texture(Color::new(0.5, 0.5, 1.0));
+        let n = Bump::normal_map(map, 1.0).shading_normal(&comps, &textures);
+        assert_eq!(n, comps.normalv);
+    }
+
+    #[test]
+    fn normal_map_tilts_along_the_tangent() {
+        let comps = front_hit();
+        let (map, textures) = solid_texture(Color::new(1.0, 0.5, 1.0));
+        let n = Bump::normal_map(map, 1.0).shading_normal(&comps, &textures);
+        let expected = vector(1.0, 0.0, -1.0).normalize();
+        assert_eq!(n, expected);
+        // No strength, no tilt
+        let n = Bump::normal_map(map, 0.0).shading_normal(&comps, &textures);
+        assert_eq!(n, comps.normalv);
+    }
+
+    #[test]
+    fn height_field_tilts_away_from_the_slope() {
+        let comps = front_hit();
+        // Planar texture getting brighter towards +x
+        let mut image = Canvas::new(2, 1);
+        image.write_pixel(0, 0, Color::black());
+        image.write_pixel(1, 0, Color::white());
+        let mut ramp = Pattern::texture(0, UvMapping::Planar, WrapMode::Clamp);
+        // Pixel centers at x = -1 and x = 1, the height grows by `amount` over 2 units
+        ramp.set_transform(Matrix::get_scaling_matrix(4.0, 1.0, 4.0).translate(-2.0, 0.0, 0.0));
+        let n = Bump::height(ramp, 1.0).shading_normal(&comps, &[image]);
+        // Slope of 0.5 along the tangent, the normal leans towards -x
+        let expected = vector(-0.5, 0.0, -1.0).normalize();
+        assert!((n - expected).magnitude() < 1e-2, "{:?}", n);
+    }
+}
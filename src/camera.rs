@@ -1,53 +1,258 @@
+use crate::passes::PixelPasses;
 use crate::point;
+use crate::utils::{consts, halton, Rng};
+use crate::wire::{get_float, get_u16, get_u8, invalid, put_float, put_u16};
 use crate::Canvas;
+use crate::Color;
+use crate::Float;
+use crate::Integrator;
+use crate::Material;
 use crate::Matrix;
 use crate::Ray;
+use crate::RenderPasses;
+use crate::Tile;
+use crate::TileOrder;
 use crate::World;
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+// Image of a progressive render after one of its passes, see `Camera::render_progressive`
+pub struct Progress<'a> {
+    pub image: &'a Canvas,
+    pub samples_per_pixel: u16, // Samples taken so far in every pixel
+    pub fraction: Float,        // Part of the render done, from 0 to 1
+}
+
+// Settings of adaptive sampling- every pixel takes at least `min_samples` samples, then more until
+// the standard error of the average brightness (luminance) of its samples falls below `threshold`,
+// or it reaches `max_samples`
+// Flat areas like the background stop right away while edges and noisy soft shadows keep going
+// `min_samples` has to be high enough to notice an edge crossing the pixel, and is at least 2
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u16,
+    pub max_samples: u16,
+    pub threshold: Float,
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: u16, max_samples: u16, threshold: Float) -> Self {
+        Self {
+            min_samples,
+            max_samples,
+            threshold,
+        }
+    }
+}
 
 // Lets us take pictures of the scene
 // Main responsibility is to map the 3D scene to a 2D canvas, by projecting rays through the camera to the canvas
 // The camera's canvas will always be exactly one unit in front of the camera
+#[derive(Clone)]
 pub struct Camera {
-    hsize: u16,            // Horizontal size in pixels of the canvas
-    vsize: u16,            // Vertical size in pixels of the canvas
-    field_of_view: f32,    // An angle that describes how much the camera can see
-    pub transform: Matrix, // Transformation matrix that describes how the world is moved relative to the camera (is a view transform)
-    aspect: f32,           // Ascpect ratio of the canvas
-    half_width: f32,       // Just half of the width of the canvas
-    half_height: f32,      // Just half of the height of the canvas
-    pixel_size: f32,       // Size of a single pixel
+    hsize: u16,                             // Horizontal size in pixels of the canvas
+    vsize: u16,                             // Vertical size in pixels of the canvas
+    field_of_view: Float,                   // An angle that describes how much the camera can see
+    transform: Matrix, // Transformation matrix that describes how the world is moved relative to the camera (is a view transform)
+    inverse_transform: Matrix, // Cached inverse of `transform`, used for every pixel
+    pub samples_per_pixel: u16, // Number of rays averaged for every pixel (supersampling)
+    pub adaptive: Option<AdaptiveSampling>, // Varies the samples of every pixel instead, when set
+    pub threads: usize, // Number of threads the rows of the canvas are split across
+    pub integrator: Integrator, // How the color along every ray is computed
+    pub shutter_open: Float, // Time the rays are cast from, moving objects get blurred along their way
+    pub shutter_close: Float, // until this time
+    half_width: Float,       // Just half of the width of the canvas
+    half_height: Float,      // Just half of the height of the canvas
+    pixel_size: Float,       // Size of a single pixel
 }
 
 impl Camera {
-    pub fn new(hsize: u16, vsize: u16, field_of_view: f32) -> Camera {
-        let half_view = f32::tan(field_of_view / 2.0);
-        let aspect = hsize as f32 / vsize as f32;
+    pub fn new(hsize: u16, vsize: u16, field_of_view: Float) -> Camera {
+        let mut camera = Camera {
+            hsize,
+            vsize,
+            field_of_view,
+            transform: Matrix::I(),
+            inverse_transform: Matrix::I(),
+            samples_per_pixel: 1,
+            adaptive: None,
+            threads: 1,
+            integrator: Integrator::default(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            half_width: 0.0,
+            half_height: 0.0,
+            pixel_size: 0.0,
+        };
+        camera.set_field_of_view(field_of_view);
+        camera
+    }
+
+    // Size of the canvas in world units depends on the field of view, it is recomputed here
+    pub fn set_field_of_view(&mut self, field_of_view: Float) {
+        let half_view = Float::tan(field_of_view / 2.0);
+        let aspect = self.hsize as Float / self.vsize as Float;
         let (mut half_width, mut half_height) = (half_view, half_view);
         if aspect >= 1.0 {
             half_height = half_view / aspect;
         } else {
             half_width = half_view * aspect;
         }
-        let pixel_size = (half_width * 2.0) / hsize as f32;
+        self.field_of_view = field_of_view;
+        self.half_width = half_width;
+        self.half_height = half_height;
+        self.pixel_size = (half_width * 2.0) / self.hsize as Float;
+    }
 
-        Camera {
+    pub fn hsize(&self) -> u16 {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> u16 {
+        self.vsize
+    }
+
+    pub fn field_of_view(&self) -> Float {
+        self.field_of_view
+    }
+
+    // Changes the size in pixels of the canvas, the rest of the camera stays as it is
+    pub fn set_resolution(&mut self, hsize: u16, vsize: u16) {
+        let resized = Camera::new(hsize, vsize, self.field_of_view);
+        self.hsize = hsize;
+        self.vsize = vsize;
+        self.half_width = resized.half_width;
+        self.half_height = resized.half_height;
+        self.pixel_size = resized.pixel_size;
+    }
+
+    pub fn transform(&self) -> Matrix {
+        self.transform
+    }
+
+    // Inverse is computed here once instead of for every pixel
+    pub fn set_transform(&mut self, t: Matrix) {
+        self.transform = t;
+        self.inverse_transform = t.inverse();
+    }
+
+    // Writes every setting of the camera for another machine, see `read_from`
+    // The threads are left out since every machine uses its own, and the other fields follow
+    // from the ones sent
+    pub(crate) fn write_to(&self, message: &mut Vec<u8>) {
+        // A field added to the camera does not compile until it is sent or left out here
+        let Camera {
             hsize,
             vsize,
             field_of_view,
-            transform: Matrix::I(),
-            aspect,
-            half_width,
-            half_height,
-            pixel_size,
+            transform,
+            inverse_transform: _,
+            samples_per_pixel,
+            adaptive,
+            threads: _,
+            integrator,
+            shutter_open,
+            shutter_close,
+            half_width: _,
+            half_height: _,
+            pixel_size: _,
+        } = *self;
+        put_u16(message, hsize);
+        put_u16(message, vsize);
+        put_float(message, field_of_view);
+        for row in 0..4 {
+            for col in 0..4 {
+                put_float(message, transform[row][col]);
+            }
+        }
+        put_u16(message, samples_per_pixel);
+        match adaptive {
+            None => message.push(0),
+            Some(adaptive) => {
+                message.push(1);
+                put_u16(message, adaptive.min_samples);
+                put_u16(message, adaptive.max_samples);
+                put_float(message, adaptive.threshold);
+            }
+        }
+        match integrator {
+            Integrator::Whitted => message.push(0),
+            Integrator::PathTracing { max_bounces } => {
+                message.push(1);
+                put_u16(message, max_bounces);
+            }
+        }
+        put_float(message, shutter_open);
+        put_float(message, shutter_close);
+    }
+
+    // Reads a camera written by `write_to`, with a single thread
+    // Settings no camera could render with (empty canvas, field of view of half a turn or more,
+    // singular transform...) are rejected, they come from another machine
+    pub(crate) fn read_from(reader: &mut impl Read) -> io::Result<Camera> {
+        let (hsize, vsize) = (get_u16(reader)?, get_u16(reader)?);
+        if hsize == 0 || vsize == 0 {
+            return Err(invalid("camera size must be positive"));
+        }
+        let field_of_view = get_float(reader)?;
+        if !(field_of_view > 0.0 && field_of_view < consts::PI) {
+            return Err(invalid("camera field of view must be between 0 and pi"));
+        }
+        let mut transform = Matrix::new();
+        for row in 0..4 {
+            for col in 0..4 {
+                transform[row][col] = get_float(reader)?;
+            }
         }
+        let finite = (0..4).all(|row| transform[row].iter().all(|value| value.is_finite()));
+        if !finite || !transform.is_invertible() {
+            return Err(invalid("camera transform must be invertible"));
+        }
+        let mut camera = Camera::new(hsize, vsize, field_of_view);
+        camera.set_transform(transform);
+        camera.samples_per_pixel = get_u16(reader)?;
+        camera.adaptive = match get_u8(reader)? {
+            0 => None,
+            1 => {
+                let (min_samples, max_samples) = (get_u16(reader)?, get_u16(reader)?);
+                let threshold = get_float(reader)?;
+                if !(threshold >= 0.0 && threshold.is_finite()) {
+                    return Err(invalid("adaptive threshold must be positive"));
+                }
+                Some(AdaptiveSampling::new(min_samples, max_samples, threshold))
+            }
+            _ => return Err(invalid("unknown adaptive sampling")),
+        };
+        camera.integrator = match get_u8(reader)? {
+            0 => Integrator::Whitted,
+            1 => Integrator::PathTracing {
+                max_bounces: get_u16(reader)?,
+            },
+            _ => return Err(invalid("unknown integrator")),
+        };
+        let (open, close) = (get_float(reader)?, get_float(reader)?);
+        if !(open.is_finite() && close.is_finite() && open <= close) {
+            return Err(invalid("shutter must close after it opens"));
+        }
+        camera.shutter_open = open;
+        camera.shutter_close = close;
+        Ok(camera)
     }
 
     /// Returns a ray that starts at the camera and passes through the (x,y) pixel on the canvas
     /// Camera is at origin and canvas is at (0, 0, -1)
     pub fn ray_for_pixel(&self, x: u16, y: u16) -> Ray {
-        // Get the pixel center
-        let xoffset = (x as f32 + 0.5) * self.pixel_size;
-        let yoffset = (y as f32 + 0.5) * self.pixel_size;
+        self.ray_for_subpixel(x, y, 0.5, 0.5)
+    }
+
+    /// Same as `ray_for_pixel` but the ray passes through (`dx`, `dy`) inside the pixel
+    /// instead of its center. Both offsets are between 0 and 1, (0, 0) being the top left corner
+    pub fn ray_for_subpixel(&self, x: u16, y: u16, dx: Float, dy: Float) -> Ray {
+        // Get the offset of the point inside the pixel
+        let xoffset = (x as Float + dx) * self.pixel_size;
+        let yoffset = (y as Float + dy) * self.pixel_size;
 
         // Change pixel coordinates to world coordinates
         let world_x = self.half_width - xoffset;
@@ -55,33 +260,350 @@ impl Camera {
 
         // Transform the point in the canvas
         // Basically, move the camera relative to the world
-        let camera_transform_inv = self.transform.inverse();
-        let pixel = camera_transform_inv * point(world_x, world_y, -1.0);
-        let origin = camera_transform_inv * point(0.0, 0.0, 0.0);
+        let pixel = self.inverse_transform * point(world_x, world_y, -1.0);
+        let origin = self.inverse_transform * point(0.0, 0.0, 0.0);
         let direction = (pixel - origin).normalize();
         Ray::new(origin, direction)
     }
 
+    /// Color of the (x,y) pixel
+    /// With more than one sample per pixel, the rays are spread over the pixel using the Halton sequence
+    /// and their colors are averaged, which smooths out the jagged edges
+    /// Random numbers are seeded by the pixel position so that renders are reproducible
+    /// The times of the rays are spread over the shutter interval the same way, for motion blur
+    /// With `adaptive` set, the number of samples depends on the pixel (see `AdaptiveSampling`)
+    pub fn pixel_color(&self, world: &World, x: u16, y: u16) -> Color {
+        self.pixel_samples(world, x, y).0
+    }
+
+    // Color of the (x,y) pixel and the number of samples averaged for it
+    fn pixel_samples(&self, world: &World, x: u16, y: u16) -> (Color, u32) {
+        let mut rng = pixel_rng(x, y);
+        if let Some(adaptive) = self.adaptive {
+            return self.adaptive_samples(world, x, y, adaptive, &mut rng);
+        }
+        if self.samples_per_pixel <= 1 {
+            let ray = self.ray_for_pixel(x, y).at_time(self.shutter_time(1));
+            return (self.integrator.color_at(world, &ray, &mut rng), 1);
+        }
+        let samples = self.samples_per_pixel as u32;
+        let sum = self.sample_pixel(world, x, y, 1..=samples, &mut rng);
+        (sum * (1.0 / samples as Float), samples)
+    }
+
+    // Average of the samples of the (x,y) pixel, taken one at a time until the standard error of
+    // their luminance is below the threshold, and the number of samples taken
+    fn adaptive_samples(
+        &self,
+        world: &World,
+        x: u16,
+        y: u16,
+        adaptive: AdaptiveSampling,
+        rng: &mut Rng,
+    ) -> (Color, u32) {
+        let min = adaptive.min_samples.max(2) as u32;
+        let max = (adaptive.max_samples as u32).max(min);
+        // Sums of the colors, and of the luminances and their squares for the variance
+        let (mut sum, mut sum_l, mut sum_l2): (Color, Float, Float) = (Color::black(), 0.0, 0.0);
+        for i in 1..=max {
+            let color = self.sample_pixel(world, x, y, i..=i, rng);
+            let l = color.luminance();
+            sum = sum + color;
+            sum_l += l;
+            sum_l2 += l * l;
+            if i >= min {
+                let n = i as Float;
+                let variance = ((sum_l2 - sum_l * sum_l / n) / (n - 1.0)).max(0.0);
+                if (variance / n).sqrt() <= adaptive.threshold {
+                    return (sum * (1.0 / n), i);
+                }
+            }
+        }
+        (sum * (1.0 / max as Float), max)
+    }
+
+    // Sum of the colors of the `samples` of the (x,y) pixel, numbered from 1
+    // `rng` carries on from the previous samples of the pixel
+    fn sample_pixel(
+        &self,
+        world: &World,
+        x: u16,
+        y: u16,
+        samples: RangeInclusive<u32>,
+        rng: &mut Rng,
+    ) -> Color {
+        let mut color = Color::black();
+        for i in samples {
+            let ray = self
+                .ray_for_subpixel(x, y, halton(i, 2), halton(i, 3))
+                .at_time(self.shutter_time(i));
+            color = color + self.integrator.color_at(world, &ray, rng);
+        }
+        color
+    }
+
+    // Time of the `i`th sample of a pixel while the shutter is open
+    fn shutter_time(&self, i: u32) -> Float {
+        self.shutter_open + (self.shutter_close - self.shutter_open) * halton(i, 5)
+    }
+
     /// Renders the world with the camera and returns the canvas
     /// A ray is casted through the pixel and the pixel is colored with the corresponding intersection
     pub fn render(&self, world: &World) -> Canvas {
-        let mut canvas = Canvas::new(self.hsize as usize, self.vsize as usize);
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let color = world.color_at(&ray);
-                canvas.write_pixel(x as usize, y as usize, color);
+        self.render_region(world, 0, 0, self.hsize, self.vsize)
+    }
+
+    /// Renders the image in passes, for previews- the first pass takes 1 sample per pixel and
+    /// every next one doubles the samples, up to `samples_per_pixel`
+    /// After every pass `on_pass` gets the image so far, and stops the render by returning false
+    /// (send the image to a channel from it to show it on another thread)
+    /// Setting `cancel` from another thread stops the render within a row of pixels, the pass it
+    /// interrupts is dropped
+    /// Returns the last image. When the render was not stopped it has the same samples as the one
+    /// from `render`, summed pass by pass instead of all at once, so the colors only differ by
+    /// rounding
+    /// Every pixel takes `samples_per_pixel` samples, `adaptive` is not used
+    pub fn render_progressive(
+        &self,
+        world: &World,
+        cancel: &AtomicBool,
+        mut on_pass: impl FnMut(Progress) -> bool,
+    ) -> Canvas {
+        let total = self.samples_per_pixel.max(1) as u32;
+        let width = self.hsize as usize;
+        let mut canvas = Canvas::new(width, self.vsize as usize);
+        // Colors summed over the samples so far, and the random numbers of every pixel
+        let mut sums = vec![Color::black(); canvas.data.len()];
+        let mut rngs: Vec<Rng> = (0..canvas.data.len())
+            .map(|ind| pixel_rng((ind % width) as u16, (ind / width) as u16))
+            .collect();
+
+        let mut done = 0;
+        while done < total {
+            let target = (done * 2).clamp(1, total);
+            let empty = (Color::black(), Rng::new(0));
+            let region = Tile::new(0, 0, self.hsize, self.vsize);
+            let pass = self.render_pixels(region, Some(cancel), empty, |col, row| {
+                let mut rng = rngs[row as usize * width + col as usize].clone();
+                if total == 1 {
+                    let ray = self.ray_for_pixel(col, row).at_time(self.shutter_time(1));
+                    return (self.integrator.color_at(world, &ray, &mut rng), rng);
+                }
+                let color = self.sample_pixel(world, col, row, done + 1..=target, &mut rng);
+                (color, rng)
+            });
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+            for (ind, (color, rng)) in pass.into_iter().enumerate() {
+                sums[ind] = sums[ind] + color;
+                rngs[ind] = rng;
+            }
+            done = target;
+            for (pixel, &sum) in canvas.data.iter_mut().zip(&sums) {
+                *pixel = sum * (1.0 / done as Float);
+            }
+            let progress = Progress {
+                image: &canvas,
+                samples_per_pixel: done as u16,
+                fraction: done as Float / total as Float,
+            };
+            if !on_pass(progress) {
+                break;
+            }
+        }
+        canvas
+    }
+
+    /// Renders only the `width` x `height` window of the image whose top left pixel is (x,y)
+    /// The returned canvas is of the size of the window
+    /// Rows are split in equal chunks between `threads` threads
+    pub fn render_region(&self, world: &World, x: u16, y: u16, width: u16, height: u16) -> Canvas {
+        Canvas {
+            width: width as usize,
+            height: height as usize,
+            data: self.render_pixels(
+                Tile::new(x, y, width, height),
+                None,
+                Color::black(),
+                |col, row| self.pixel_color(world, col, row),
+            ),
+        }
+    }
+
+    /// Renders the `region` of the image in tiles of `tile_size` x `tile_size` pixels, taken in
+    /// `order`, and stitches them into a canvas of the size of the region
+    /// Each of the `threads` threads renders whole tiles, picking the next one when it is done,
+    /// and `on_tile` gets every tile as soon as it is finished (in the order they finish)
+    pub fn render_tiles(
+        &self,
+        world: &World,
+        region: Tile,
+        tile_size: u16,
+        order: TileOrder,
+        mut on_tile: impl FnMut(Tile, &Canvas),
+    ) -> Canvas {
+        assert!(
+            region.x as u32 + region.width as u32 <= self.hsize as u32
+                && region.y as u32 + region.height as u32 <= self.vsize as u32,
+            "Region is outside of the camera canvas"
+        );
+        let tiles = region.split(tile_size, order);
+        let mut canvas = Canvas::new(region.width as usize, region.height as usize);
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.clamp(1, tiles.len().max(1)) {
+                let (sender, next, tiles) = (sender.clone(), &next, &tiles);
+                scope.spawn(move || {
+                    while let Some(&tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if sender.send((tile, self.render_tile(world, tile))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            // Receiving stops once every thread is done and dropped its sender
+            drop(sender);
+            for (tile, image) in receiver {
+                let (col, row) = ((tile.x - region.x) as usize, (tile.y - region.y) as usize);
+                canvas.paste(&image, col, row);
+                on_tile(tile, &image);
+            }
+        });
+        canvas
+    }
+
+    // Renders the pixels of `tile` on the current thread
+    fn render_tile(&self, world: &World, tile: Tile) -> Canvas {
+        let mut canvas = Canvas::new(tile.width as usize, tile.height as usize);
+        for row in 0..tile.height {
+            for col in 0..tile.width {
+                let color = self.pixel_color(world, tile.x + col, tile.y + row);
+                canvas.write_pixel(col as usize, row as usize, color);
             }
         }
         canvas
     }
+
+    /// Renders the image together with the depth, normal, albedo, id and shadow passes
+    pub fn render_passes(&self, world: &World) -> RenderPasses {
+        // Objects with equal materials share their material id
+        let mut materials: Vec<Material> = Vec::new();
+        let material_ids: Vec<usize> = world
+            .objects()
+            .iter()
+            .map(|object| {
+                materials
+                    .iter()
+                    .position(|&material| material == object.material)
+                    .unwrap_or_else(|| {
+                        materials.push(object.material);
+                        materials.len() - 1
+                    })
+            })
+            .collect();
+        let empty = PixelPasses {
+            beauty: Color::black(),
+            depth: Float::INFINITY,
+            normal: Color::black(),
+            albedo: Color::black(),
+            object_id: -1.0,
+            material_id: -1.0,
+            shadow: 0.0,
+            samples: 0.0,
+        };
+        let region = Tile::new(0, 0, self.hsize, self.vsize);
+        let pixels = self.render_pixels(region, None, empty, |col, row| {
+            let ray = self.ray_for_pixel(col, row).at_time(self.shutter_time(1));
+            let (beauty, samples) = self.pixel_samples(world, col, row);
+            let mut pixel = PixelPasses {
+                beauty,
+                samples: samples as Float,
+                albedo: world.background.color(ray.direction),
+                ..empty
+            };
+            if let Some((ind, hit)) = world.hit_object(ray) {
+                let comps = world.prepare_computations(&hit, &ray);
+                let n = comps.normalv;
+                pixel.depth = hit.t;
+                pixel.normal = Color::new(n.x, n.y, n.z);
+                pixel.albedo = comps.material.color;
+                pixel.object_id = hit.object.id() as Float;
+                pixel.material_id = material_ids[ind] as Float;
+                let transmission = world.hit_transmission(&comps);
+                pixel.shadow =
+                    1.0 - (transmission.red + transmission.green + transmission.blue) / 3.0;
+            }
+            pixel
+        });
+        RenderPasses::from_pixels(self.hsize as usize, self.vsize as usize, &pixels)
+    }
+
+    // Values of the pixels of `region`, in rows
+    // Rows are split in equal chunks between `threads` threads, which stop at the next row once
+    // `cancel` is set and leave the pixels they did not get to `empty`
+    fn render_pixels<T: Clone + Send>(
+        &self,
+        region: Tile,
+        cancel: Option<&AtomicBool>,
+        empty: T,
+        pixel_value: impl Fn(u16, u16) -> T + Sync,
+    ) -> Vec<T> {
+        let Tile {
+            x,
+            y,
+            width,
+            height,
+        } = region;
+        assert!(
+            x as u32 + width as u32 <= self.hsize as u32
+                && y as u32 + height as u32 <= self.vsize as u32,
+            "Region is outside of the camera canvas"
+        );
+        let mut pixels = vec![empty; width as usize * height as usize];
+        if width == 0 || height == 0 {
+            return pixels;
+        }
+
+        let threads = self.threads.clamp(1, height as usize);
+        let rows_per_thread = (height as usize).div_ceil(threads);
+        let pixel_value = &pixel_value;
+        std::thread::scope(|scope| {
+            for (chunk_ind, chunk) in pixels
+                .chunks_mut(rows_per_thread * width as usize)
+                .enumerate()
+            {
+                let first_row = y + (chunk_ind * rows_per_thread) as u16;
+                scope.spawn(move || {
+                    for (ind, pixel) in chunk.iter_mut().enumerate() {
+                        let row_start = ind % width as usize == 0;
+                        if row_start && cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+                            break;
+                        }
+                        let row = first_row + (ind / width as usize) as u16;
+                        let col = x + (ind % width as usize) as u16;
+                        *pixel = pixel_value(col, row);
+                    }
+                });
+            }
+        });
+        pixels
+    }
+}
+
+// Random numbers of the (x,y) pixel, seeded by its position so renders are reproducible and do not
+// depend on how the pixels are split between threads, tiles or passes
+fn pixel_rng(x: u16, y: u16) -> Rng {
+    Rng::new(((y as u64) << 16) | x as u64)
 }
 
 #[cfg(test)]
 mod camera_test {
     use super::*;
-    use crate::{vector, Color};
-    use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
+    use crate::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
+    use crate::utils::EPSILON;
+    use crate::{vector, Color, Compare, Motion};
 
     #[test]
     fn constructing_camera() {
@@ -114,13 +636,99 @@ mod camera_test {
     #[test]
     fn ray_when_camera_is_transformed() {
         let mut c = Camera::new(201, 101, FRAC_PI_2);
-        c.transform = Matrix::get_rotation_y_matrix(FRAC_PI_4)
-            * Matrix::get_translation_matrix(0.0, -2.0, 5.0);
+        c.set_transform(
+            Matrix::get_rotation_y_matrix(FRAC_PI_4)
+                * Matrix::get_translation_matrix(0.0, -2.0, 5.0),
+        );
         let r = c.ray_for_pixel(100, 50);
         assert_eq!(r.origin, point(0.0, 2.0, -5.0));
         assert_eq!(r.direction, vector(FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2));
     }
 
+    #[test]
+    fn resizing_keeps_the_camera_settings() {
+        let mut c = Camera::new(200, 125, FRAC_PI_2);
+        c.set_transform(Matrix::get_translation_matrix(0.0, -2.0, 5.0));
+        c.samples_per_pixel = 4;
+        c.shutter_close = 0.5;
+        c.set_resolution(100, 50);
+        assert_eq!((c.hsize(), c.vsize()), (100, 50));
+        let mut fresh = Camera::new(100, 50, FRAC_PI_2);
+        fresh.set_transform(Matrix::get_translation_matrix(0.0, -2.0, 5.0));
+        assert_eq!(
+            c.ray_for_pixel(7, 3).direction,
+            fresh.ray_for_pixel(7, 3).direction
+        );
+        assert_eq!(
+            c.transform(),
+            Matrix::get_translation_matrix(0.0, -2.0, 5.0)
+        );
+        assert_eq!(c.samples_per_pixel, 4);
+        assert_eq!(c.shutter_close, 0.5);
+    }
+
+    #[test]
+    fn set_transform_caches_inverse() {
+        let mut c = Camera::new(201, 101, FRAC_PI_2);
+        let t = Matrix::get_rotation_y_matrix(FRAC_PI_4)
+            * Matrix::get_translation_matrix(0.0, -2.0, 5.0);
+        c.set_transform(t);
+        assert_eq!(c.transform(), t);
+        assert_eq!(c.inverse_transform, t.inverse());
+    }
+
+    #[test]
+    fn camera_is_sent_whole() {
+        let mut c = Camera::new(160, 90, FRAC_PI_4);
+        c.set_transform(Matrix::get_translation_matrix(0.0, -2.0, 5.0));
+        c.samples_per_pixel = 3;
+        c.adaptive = Some(AdaptiveSampling::new(4, 64, 0.01));
+        c.threads = 8;
+        c.integrator = Integrator::PathTracing { max_bounces: 5 };
+        c.shutter_open = 0.25;
+        c.shutter_close = 0.75;
+        let mut message = Vec::new();
+        c.write_to(&mut message);
+        let read = Camera::read_from(&mut message.as_slice()).unwrap();
+        assert_eq!((read.hsize, read.vsize), (160, 90));
+        assert_eq!(read.field_of_view, c.field_of_view);
+        assert_eq!(read.transform, c.transform);
+        assert_eq!(read.inverse_transform, c.inverse_transform);
+        assert_eq!(read.samples_per_pixel, 3);
+        assert_eq!(read.adaptive, c.adaptive);
+        assert_eq!(read.threads, 1);
+        assert_eq!(read.integrator, c.integrator);
+        assert_eq!((read.shutter_open, read.shutter_close), (0.25, 0.75));
+        assert_eq!(read.pixel_size, c.pixel_size);
+    }
+
+    #[test]
+    fn broken_cameras_are_not_read() {
+        let read = |change: &dyn Fn(&mut Camera)| {
+            let mut c = Camera::new(160, 90, FRAC_PI_2);
+            change(&mut c);
+            let mut message = Vec::new();
+            c.write_to(&mut message);
+            Camera::read_from(&mut message.as_slice()).map(|_| ())
+        };
+        assert!(read(&|_| {}).is_ok());
+        assert!(read(&|c| c.hsize = 0).is_err());
+        assert!(read(&|c| c.field_of_view = 0.0).is_err());
+        assert!(read(&|c| c.field_of_view = 4.0).is_err());
+        assert!(read(&|c| c.field_of_view = Float::NAN).is_err());
+        assert!(read(&|c| c.transform = Matrix::get_scaling_matrix(1.0, 0.0, 1.0)).is_err());
+        assert!(read(&|c| c.transform[0][3] = Float::INFINITY).is_err());
+        assert!(read(&|c| c.adaptive = Some(AdaptiveSampling::new(2, 8, -1.0))).is_err());
+        assert!(read(&|c| c.shutter_close = -1.0).is_err());
+
+        let mut message = Vec::new();
+        Camera::new(160, 90, FRAC_PI_2).write_to(&mut message);
+        let integrator = message.len() - 17;
+        message[integrator] = 2;
+        assert!(Camera::read_from(&mut message.as_slice()).is_err());
+        assert!(Camera::read_from(&mut &message[..10]).is_err());
+    }
+
     #[test]
     fn render_a_world() {
         let w = World::default();
@@ -128,8 +736,236 @@ mod camera_test {
         let from = point(0.0, 0.0, -5.0);
         let to = point(0.0, 0.0, 0.0);
         let up = vector(0.0, 1.0, 0.0);
-        c.transform = Matrix::get_view_transform(from, to, up);
+        c.set_transform(Matrix::get_view_transform(from, to, up));
         let canvas = c.render(&w);
         assert_eq!(canvas.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
     }
+
+    #[test]
+    fn render_a_world_with_multiple_threads() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let single = c.render(&w);
+        c.threads = 4;
+        let multi = c.render(&w);
+        assert_eq!(single.data, multi.data);
+    }
+
+    #[test]
+    fn render_a_region_of_world() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let full = c.render(&w);
+        let region = c.render_region(&w, 4, 3, 5, 4);
+        assert_eq!(region.width, 5);
+        assert_eq!(region.height, 4);
+        assert_eq!(region.pixel_at(1, 2), full.pixel_at(5, 5));
+    }
+
+    #[test]
+    fn supersampling_averages_colors_over_pixel() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c.samples_per_pixel = 16;
+        // Center of the sphere is flat enough that the average stays close to the single sample
+        let center = c.pixel_color(&w, 5, 5);
+        assert!((center.red - 0.38066).abs() < 0.01);
+        // Pixel on the silhouette of the sphere gets a mix of the sphere and the background
+        let edge = c.pixel_color(&w, 4, 5);
+        let single = w.color_at(&c.ray_for_pixel(4, 5), &mut Rng::new(0));
+        assert_ne!(edge, single);
+    }
+
+    #[test]
+    fn path_traced_render_is_reproducible() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c.integrator = Integrator::PathTracing { max_bounces: 4 };
+        c.samples_per_pixel = 4;
+        let single = c.render(&w);
+        c.threads = 3;
+        assert_eq!(single.data, c.render(&w).data);
+    }
+
+    #[test]
+    fn moving_sphere_is_blurred_over_the_shutter() {
+        let mut w = World::default();
+        w.remove_object(2);
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c.samples_per_pixel = 16;
+        let still = c.pixel_color(&w, 5, 5);
+        // Shutter closed, the sphere is caught at its start
+        w.object_mut(1).unwrap().motion = Some(Motion::linear(0.0, 1.0, vector(0.0, 5.0, 0.0)));
+        assert_eq!(c.pixel_color(&w, 5, 5), still);
+        // Open for the whole motion, the center pixel only sees the sphere part of the time
+        c.shutter_close = 1.0;
+        let blurred = c.pixel_color(&w, 5, 5);
+        assert!(blurred.red < still.red * 0.8 && blurred.red > 0.0);
+    }
+
+    #[test]
+    fn rendering_passes() {
+        let mut w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let passes = c.render_passes(&w);
+        assert_eq!(passes.beauty.data, c.render(&w).data);
+        assert!(passes.depth.value_at(5, 5).eq(4.0));
+        assert_eq!(passes.normal.pixel_at(5, 5), Color::new(0.0, 0.0, -1.0));
+        assert_eq!(passes.albedo.pixel_at(5, 5), Color::new(0.8, 1.0, 0.6));
+        // Same id as picking the pixel gives
+        let pick = w.pick(&c, 5, 5).unwrap();
+        assert_eq!(passes.object_id.value_at(5, 5), pick.id as Float);
+        assert_eq!(passes.object_id.value_at(5, 5), 1.0);
+        assert_eq!(passes.material_id.value_at(5, 5), 0.0);
+        assert_eq!(passes.shadow.value_at(5, 5), 0.0);
+        // Corner ray misses the sphere
+        assert_eq!(passes.depth.value_at(0, 0), Float::INFINITY);
+        assert_eq!(passes.object_id.value_at(0, 0), -1.0);
+        assert_eq!(passes.material_id.value_at(0, 0), -1.0);
+        assert_eq!(passes.albedo.pixel_at(0, 0), Color::black());
+        assert_eq!(passes.samples.value_at(0, 0), 1.0);
+
+        // Light behind the sphere, its front is in its own shadow
+        w.light.position = point(0.0, 0.0, 10.0);
+        let passes = c.render_passes(&w);
+        assert_eq!(passes.shadow.value_at(5, 5), 1.0);
+        assert_eq!(passes.shadow.value_at(0, 0), 0.0);
+    }
+
+    #[test]
+    fn progressive_render_refines_to_the_full_render() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c.integrator = Integrator::PathTracing { max_bounces: 2 };
+        c.samples_per_pixel = 5;
+        c.threads = 2;
+        let mut passes = Vec::new();
+        let cancel = AtomicBool::new(false);
+        let image = c.render_progressive(&w, &cancel, |progress| {
+            passes.push((progress.samples_per_pixel, progress.fraction));
+            true
+        });
+        assert_eq!(passes, vec![(1, 0.2), (2, 0.4), (4, 0.8), (5, 1.0)]);
+        // Same samples as `render` summed in another order, equal up to rounding
+        for (p, f) in image.data.iter().zip(&c.render(&w).data) {
+            let differences = [p.red - f.red, p.green - f.green, p.blue - f.blue];
+            assert!(
+                differences.iter().all(|d| d.abs() < EPSILON),
+                "{:?} {:?}",
+                p,
+                f
+            );
+        }
+
+        // Stopped after the first pass
+        let mut calls = 0;
+        let preview = c.render_progressive(&w, &cancel, |progress| {
+            calls += 1;
+            assert_eq!(progress.image.width, 11);
+            false
+        });
+        assert_eq!(calls, 1);
+        assert_ne!(preview.data, image.data);
+
+        // Cancelled in the middle of the first pass, which is dropped
+        let cancelled = AtomicBool::new(true);
+        let none = c.render_progressive(&w, &cancelled, |_| panic!("No pass is finished"));
+        assert!(none.data.iter().all(|&pixel| pixel == Color::black()));
+    }
+
+    #[test]
+    fn cancelling_stops_at_the_next_row() {
+        let mut c = Camera::new(4, 3, FRAC_PI_2);
+        c.threads = 1;
+        let cancel = AtomicBool::new(false);
+        let pixels = c.render_pixels(Tile::new(0, 0, 4, 3), Some(&cancel), 0, |_, row| {
+            cancel.store(true, Ordering::Relaxed);
+            row + 1
+        });
+        // The first row is finished, the others are left empty
+        assert_eq!(pixels, vec![1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn adaptive_sampling_spends_samples_on_edges() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c.adaptive = Some(AdaptiveSampling::new(4, 64, 0.01));
+        // Background is flat and stops at the minimum
+        assert_eq!(c.pixel_samples(&w, 0, 0), (Color::black(), 4));
+        // Pixel on the silhouette of the sphere keeps sampling
+        let (edge, samples) = c.pixel_samples(&w, 4, 5);
+        assert!(samples > 4);
+        assert_eq!(edge, c.pixel_color(&w, 4, 5));
+        let passes = c.render_passes(&w);
+        assert_eq!(passes.samples.value_at(4, 5), samples as Float);
+        assert_eq!(passes.beauty.data, c.render(&w).data);
+
+        // Maximum is reached when the threshold can not be met
+        c.adaptive = Some(AdaptiveSampling::new(4, 8, 0.0));
+        assert_eq!(c.pixel_samples(&w, 4, 5).1, 8);
+    }
+
+    #[test]
+    fn tiled_render_matches_the_region_render() {
+        let w = World::default();
+        let mut c = Camera::new(21, 15, FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c.threads = 3;
+        let region = Tile::new(2, 1, 17, 13);
+        let expected = c.render_region(&w, 2, 1, 17, 13);
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let mut finished = Vec::new();
+            let image = c.render_tiles(&w, region, 4, order, |tile, image| {
+                assert_eq!(image.width, tile.width as usize);
+                finished.push(tile);
+            });
+            assert_eq!(image.data, expected.data);
+            assert_eq!(finished.len(), 20);
+        }
+    }
 }
@@ -1,6 +1,8 @@
 use crate::Color;
+use crate::Float;
 use std::fs;
 
+#[derive(Clone)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
@@ -30,6 +32,16 @@ impl Canvas {
         self[row][col]
     }
 
+    // Copies `image` over the canvas with its top left pixel at (col,row), like a rendered tile
+    // Parts outside of the canvas are dropped
+    pub fn paste(&mut self, image: &Canvas, col: usize, row: usize) {
+        let width = image.width.min(self.width.saturating_sub(col));
+        for image_row in 0..image.height.min(self.height.saturating_sub(row)) {
+            let start = (row + image_row) * self.width + col;
+            self.data[start..start + width].copy_from_slice(&image[image_row][..width]);
+        }
+    }
+
     // Convert canvas to ppm format
     pub fn get_ppm(&self) -> String {
         let header = self.get_ppm_header();
@@ -38,8 +50,9 @@ impl Canvas {
     }
 
     // Write the string ppm to the `file_path`
-    pub fn write_ppm(&self, ppm_string: &str, file_path: &str) {
-        fs::write(file_path, ppm_string).expect("Unable to write ppm");
+    pub fn write_ppm(&self, ppm_string: &str, file_path: &str) -> Result<(), String> {
+        fs::write(file_path, ppm_string)
+            .map_err(|err| format!("Unable to write ppm {}: {}", file_path, err))
     }
 
     // PPM format-
@@ -83,10 +96,461 @@ impl Canvas {
         pixels
     }
 
+    // Convert canvas to png format
+    // Image data is 8 bit RGB, stored in zlib blocks without any compression
+    pub fn get_png(&self) -> Vec<u8> {
+        let mut png: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+        // IHDR: width, height, bit depth, color type (2 is RGB), compression, filter, interlace
+        let mut header: Vec<u8> = Vec::new();
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]);
+        Self::push_png_chunk(&mut png, b"IHDR", &header);
+
+        // Each row starts with the filter type, 0 means no filter
+        let mut raw: Vec<u8> = Vec::with_capacity(self.height * (self.width * 3 + 1));
+        for row in 0..self.height {
+            raw.push(0);
+            for col in 0..self.width {
+                let color = self.pixel_at(col, row);
+                for pixel_value in [color.red, color.green, color.blue] {
+                    raw.push(self.scale_and_clip_color(pixel_value));
+                }
+            }
+        }
+        Self::push_png_chunk(&mut png, b"IDAT", &zlib_store(&raw));
+        Self::push_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    // Write the png bytes to the `file_path`
+    pub fn write_png(&self, png: &[u8], file_path: &str) -> Result<(), String> {
+        fs::write(file_path, png)
+            .map_err(|err| format!("Unable to write png {}: {}", file_path, err))
+    }
+
+    // PNG chunk format-
+    // LENGTH (4 bytes) CHUNK_TYPE (4 bytes) DATA CRC (4 bytes, of type and data)
+    fn push_png_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+        png.extend((data.len() as u32).to_be_bytes());
+        png.extend(chunk_type);
+        png.extend(data);
+        let mut crc_data = chunk_type.to_vec();
+        crc_data.extend(data);
+        png.extend(crc32(&crc_data).to_be_bytes());
+    }
+
+    // Read a Radiance HDR (.hdr, RGBE) image, colors are kept unclamped
+    pub fn read_hdr(file_path: &str) -> Result<Canvas, String> {
+        let bytes = fs::read(file_path)
+            .map_err(|err| format!("Unable to read hdr image {}: {}", file_path, err))?;
+        Self::from_hdr(&bytes)
+    }
+
+    // Radiance HDR format-
+    // #?RADIANCE
+    // HEADER LINES (eg.- FORMAT=32-bit_rle_rgbe), then an empty line
+    // -Y IMAGE_HEIGHT +X IMAGE_WIDTH
+    // Rows of RGBE pixels- a shared exponent E for the 3 mantissas, color = mantissa * 2^(E - 136)
+    // Rows can be run length encoded, see `read_hdr_row`
+    pub fn from_hdr(bytes: &[u8]) -> Result<Canvas, String> {
+        let mut pos = 0;
+        let mut next_line = || -> Result<String, String> {
+            let end = bytes[pos..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .ok_or("Unexpected end of hdr header")?;
+            let line = String::from_utf8_lossy(&bytes[pos..pos + end]).into_owned();
+            pos += end + 1;
+            Ok(line)
+        };
+
+        if !next_line()?.starts_with("#?") {
+            return Err(String::from("Not a Radiance hdr image"));
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+                return Err(format!("Unsupported hdr {}", line));
+            }
+        }
+        let size = next_line()?;
+        let size: Vec<&str> = size.split_whitespace().collect();
+        let (height, width) = match size[..] {
+            ["-Y", height, "+X", width] => (height.parse::<usize>(), width.parse::<usize>()),
+            _ => {
+                return Err(String::from(
+                    "Unsupported hdr orientation, expected -Y H +X W",
+                ))
+            }
+        };
+        let (height, width) = (
+            height.map_err(|_| "Invalid hdr height")?,
+            width.map_err(|_| "Invalid hdr width")?,
+        );
+
+        let mut canvas = Canvas::new(width, height);
+        let mut data = &bytes[pos..];
+        let mut rgbe = vec![[0_u8; 4]; width];
+        for row in 0..height {
+            data = read_hdr_row(data, &mut rgbe)?;
+            for (col, &[r, g, b, e]) in rgbe.iter().enumerate() {
+                let color = if e == 0 {
+                    Color::black()
+                } else {
+                    let scale = (2.0 as Float).powi(e as i32 - 136);
+                    Color::new(r as Float, g as Float, b as Float) * scale
+                };
+                canvas.write_pixel(col, row, color);
+            }
+        }
+        Ok(canvas)
+    }
+
+    // Read an image, the format is chosen by the extension of `file_path` (.ppm, .png or .hdr)
+    pub fn read_image(file_path: &str) -> Result<Canvas, String> {
+        let bytes = fs::read(file_path)
+            .map_err(|err| format!("Unable to read image {}: {}", file_path, err))?;
+        Self::from_image(file_path, &bytes)
+    }
+
+    // Decode the content of the image file at `file_path`, by its extension like `read_image`
+    pub fn from_image(file_path: &str, bytes: &[u8]) -> Result<Canvas, String> {
+        let extension = file_path.rsplit('.').next().unwrap_or("").to_lowercase();
+        match extension.as_str() {
+            "ppm" => Self::from_ppm(bytes),
+            "png" => Self::from_png(bytes),
+            "hdr" => Self::from_hdr(bytes),
+            _ => Err(format!("Unsupported image format {}", file_path)),
+        }
+    }
+
+    // Read a ppm image, both the plain (P3) and the binary (P6) flavours
+    // Colors are the pixel values divided by the maximum color value, the opposite of `get_ppm`
+    pub fn from_ppm(bytes: &[u8]) -> Result<Canvas, String> {
+        let flavour = String::from_utf8_lossy(bytes.get(..2).unwrap_or(&[])).into_owned();
+        if flavour != "P3" && flavour != "P6" {
+            return Err(String::from("Not a P3 or P6 ppm image"));
+        }
+        // Header tokens are separated by whitespace, `#` comments run to the end of the line
+        let mut pos = 2;
+        let mut next_token = || -> Result<String, String> {
+            loop {
+                match bytes.get(pos) {
+                    Some(byte) if byte.is_ascii_whitespace() => pos += 1,
+                    Some(b'#') => {
+                        while bytes.get(pos).is_some_and(|&byte| byte != b'\n') {
+                            pos += 1;
+                        }
+                    }
+                    Some(_) => break,
+                    None => return Err(String::from("Unexpected end of ppm data")),
+                }
+            }
+            let start = pos;
+            while bytes
+                .get(pos)
+                .is_some_and(|byte| !byte.is_ascii_whitespace())
+            {
+                pos += 1;
+            }
+            Ok(String::from_utf8_lossy(&bytes[start..pos]).into_owned())
+        };
+        let mut next_number = |name: &str| -> Result<usize, String> {
+            let token = next_token()?;
+            token
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid ppm {} `{}`", name, token))
+        };
+
+        let width = next_number("width")?;
+        let height = next_number("height")?;
+        let max_value = next_number("maximum color value")?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(format!("Invalid ppm maximum color value {}", max_value));
+        }
+
+        let count = width * height * 3;
+        let values: Vec<usize> = if flavour == "P3" {
+            (0..count)
+                .map(|_| next_number("color value"))
+                .collect::<Result<_, _>>()?
+        } else {
+            // A single whitespace separates the header from the binary data, values above 255
+            // take 2 bytes, most significant first
+            let size = if max_value > 255 { 2 } else { 1 };
+            let data = bytes
+                .get(pos + 1..pos + 1 + count * size)
+                .ok_or("Unexpected end of ppm data")?;
+            data.chunks(size)
+                .map(|value| value.iter().fold(0, |acc, &byte| acc << 8 | byte as usize))
+                .collect()
+        };
+
+        let mut canvas = Canvas::new(width, height);
+        for (pixel, rgb) in canvas.data.iter_mut().zip(values.chunks(3)) {
+            let [r, g, b] =
+                [rgb[0], rgb[1], rgb[2]].map(|value| value as Float / max_value as Float);
+            *pixel = Color::new(r, g, b);
+        }
+        Ok(canvas)
+    }
+
+    // Read a png image, colors are the pixel values divided by the maximum value like `get_png`
+    // All color types and bit depths are supported, alpha is ignored, interlaced images are not
+    pub fn from_png(bytes: &[u8]) -> Result<Canvas, String> {
+        if bytes.get(..8) != Some(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            return Err(String::from("Not a png image"));
+        }
+        let mut header: &[u8] = &[];
+        let mut palette: &[u8] = &[];
+        let mut compressed: Vec<u8> = Vec::new();
+        let mut pos = 8;
+        loop {
+            let error = || String::from("Unexpected end of png data");
+            let length = bytes.get(pos..pos + 4).ok_or_else(error)?;
+            let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+            let chunk = bytes.get(pos + 4..pos + 8 + length).ok_or_else(error)?;
+            let crc = bytes
+                .get(pos + 8 + length..pos + 12 + length)
+                .ok_or_else(error)?;
+            if crc32(chunk).to_be_bytes() != crc {
+                return Err(String::from("Corrupted png chunk"));
+            }
+            let (chunk_type, data) = chunk.split_at(4);
+            match chunk_type {
+                b"IHDR" => header = data,
+                b"PLTE" => palette = data,
+                b"IDAT" => compressed.extend(data),
+                b"IEND" => break,
+                _ => {}
+            }
+            pos += 12 + length;
+        }
+
+        // IHDR: width, height, bit depth, color type, compression, filter, interlace
+        if header.len() != 13 {
+            return Err(String::from("Invalid png header"));
+        }
+        let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let (depth, color_type) = (header[8] as usize, header[9]);
+        // Color types- 0 gray, 2 RGB, 3 palette, 4 gray and alpha, 6 RGBA
+        let channels = match (color_type, depth) {
+            (0, 1 | 2 | 4 | 8 | 16) => 1,
+            (2, 8 | 16) => 3,
+            (3, 1 | 2 | 4 | 8) => 1,
+            (4, 8 | 16) => 2,
+            (6, 8 | 16) => 4,
+            _ => {
+                return Err(format!(
+                    "Invalid png color type {} with bit depth {}",
+                    color_type, depth
+                ))
+            }
+        };
+        if header[12] != 0 {
+            return Err(String::from("Interlaced png images are not supported"));
+        }
+
+        let mut raw = crate::inflate::zlib_decompress(&compressed)?;
+        let stride = (width * channels * depth).div_ceil(8);
+        if raw.len() < height * (stride + 1) {
+            return Err(String::from("Not enough png image data"));
+        }
+        unfilter_png(&mut raw, height, stride, (channels * depth / 8).max(1))?;
+
+        let max_value = ((1 << depth) - 1) as Float;
+        let mut canvas = Canvas::new(width, height);
+        for row in 0..height {
+            let line = &raw[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+            // Value of the `ind`th sample of the row, samples smaller than a byte are packed
+            // with the leftmost pixel in the most significant bits
+            let sample = |ind: usize| -> usize {
+                match depth {
+                    16 => (line[2 * ind] as usize) << 8 | line[2 * ind + 1] as usize,
+                    8 => line[ind] as usize,
+                    _ => {
+                        let bit = ind * depth;
+                        (line[bit / 8] as usize >> (8 - depth - bit % 8)) & ((1 << depth) - 1)
+                    }
+                }
+            };
+            for col in 0..width {
+                let first = col * channels;
+                let color = match color_type {
+                    0 | 4 => {
+                        let gray = sample(first) as Float / max_value;
+                        Color::new(gray, gray, gray)
+                    }
+                    3 => {
+                        let ind = sample(first) * 3;
+                        let rgb = palette
+                            .get(ind..ind + 3)
+                            .ok_or("Png palette index out of range")?;
+                        Color::new(rgb[0] as Float, rgb[1] as Float, rgb[2] as Float)
+                            * (1.0 / 255.0)
+                    }
+                    _ => {
+                        Color::new(
+                            sample(first) as Float,
+                            sample(first + 1) as Float,
+                            sample(first + 2) as Float,
+                        ) * (1.0 / max_value)
+                    }
+                };
+                canvas.write_pixel(col, row, color);
+            }
+        }
+        Ok(canvas)
+    }
+
     // Scales the color value and clip between 0 and 255
-    fn scale_and_clip_color(&self, color_value: f32) -> u8 {
-        (color_value * 255_f32).clamp(0.0, 255.0).round() as u8
+    fn scale_and_clip_color(&self, color_value: Float) -> u8 {
+        (color_value * 255.0).clamp(0.0, 255.0).round() as u8
+    }
+}
+
+// Read one row of RGBE pixels from `data` into `row`, returns the rest of `data`
+// Run length encoded rows start with 2 2 followed by the width, and then store each of the
+// 4 channels separately as runs (count > 128, repeat the next byte count - 128 times)
+// or literals (count bytes follow). Other rows are stored as plain pixels
+fn read_hdr_row<'a>(data: &'a [u8], row: &mut [[u8; 4]]) -> Result<&'a [u8], String> {
+    let error = || String::from("Unexpected end of hdr data");
+    let width = row.len();
+    let is_rle = data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && data[2] < 128
+        && ((data[2] as usize) << 8 | data[3] as usize) == width
+        && (8..32768).contains(&width);
+    if !is_rle {
+        let pixels = data.get(..width * 4).ok_or_else(error)?;
+        for (pixel, bytes) in row.iter_mut().zip(pixels.chunks(4)) {
+            pixel.copy_from_slice(bytes);
+        }
+        return Ok(&data[width * 4..]);
+    }
+
+    let mut pos = 4;
+    for channel in 0..4 {
+        let mut col = 0;
+        while col < width {
+            let count = *data.get(pos).ok_or_else(error)? as usize;
+            pos += 1;
+            if count > 128 {
+                let value = *data.get(pos).ok_or_else(error)?;
+                pos += 1;
+                let run = row
+                    .get_mut(col..col + count - 128)
+                    .ok_or("Bad hdr run length")?;
+                run.iter_mut().for_each(|pixel| pixel[channel] = value);
+                col += count - 128;
+            } else {
+                if count == 0 {
+                    return Err(String::from("Bad hdr run length"));
+                }
+                let values = data.get(pos..pos + count).ok_or_else(error)?;
+                let literal = row.get_mut(col..col + count).ok_or("Bad hdr run length")?;
+                for (pixel, &value) in literal.iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                pos += count;
+                col += count;
+            }
+        }
+    }
+    Ok(&data[pos..])
+}
+
+// Undo the png filters, each row of `raw` is a filter type byte followed by `stride` bytes
+// Filters predict a byte from the byte `bpp` to the left (a), above (b) and above left (c)
+fn unfilter_png(raw: &mut [u8], height: usize, stride: usize, bpp: usize) -> Result<(), String> {
+    for row in 0..height {
+        let start = row * (stride + 1);
+        let filter = raw[start];
+        for i in 0..stride {
+            let a = if i >= bpp {
+                raw[start + 1 + i - bpp]
+            } else {
+                0
+            };
+            let b = if row > 0 { raw[start - stride + i] } else { 0 };
+            let c = if row > 0 && i >= bpp {
+                raw[start - stride + i - bpp]
+            } else {
+                0
+            };
+            let prediction = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => {
+                    // Paeth- whichever of a, b, c is closest to a + b - c
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = (
+                        (p - a as i16).abs(),
+                        (p - b as i16).abs(),
+                        (p - c as i16).abs(),
+                    );
+                    if pa <= pb && pa <= pc {
+                        a
+                    } else if pb <= pc {
+                        b
+                    } else {
+                        c
+                    }
+                }
+                _ => return Err(format!("Invalid png filter type {}", filter)),
+            };
+            raw[start + 1 + i] = raw[start + 1 + i].wrapping_add(prediction);
+        }
+    }
+    Ok(())
+}
+
+// Wrap `data` in a zlib stream made of uncompressed (stored) deflate blocks
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut stream: Vec<u8> = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        stream.extend([1, 0, 0, 0xFF, 0xFF]); // A single empty final block
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        stream.push(is_final as u8);
+        stream.extend((block.len() as u16).to_le_bytes());
+        stream.extend((!(block.len() as u16)).to_le_bytes());
+        stream.extend(block);
+    }
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFF_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
     }
+    !crc
 }
 
 // For indexing a row in a Canvas
@@ -131,6 +595,22 @@ mod canvas_tests {
         assert_eq!(c.pixel_at(2, 3), red);
     }
 
+    #[test]
+    fn pasting_an_image() {
+        let mut c = Canvas::new(4, 3);
+        let mut tile = Canvas::new(2, 2);
+        tile.fill(Color::white());
+        c.paste(&tile, 1, 0);
+        c.paste(&tile, 3, 2);
+        assert_eq!(c.pixel_at(0, 0), Color::black());
+        assert_eq!(c.pixel_at(1, 0), Color::white());
+        assert_eq!(c.pixel_at(2, 1), Color::white());
+        assert_eq!(c.pixel_at(1, 2), Color::black());
+        // Only the top left pixel of the second tile fits
+        assert_eq!(c.pixel_at(3, 2), Color::white());
+        assert_eq!(c.pixel_at(3, 1), Color::black());
+    }
+
     #[test]
     fn ppm_header() {
         let c = Canvas::new(5, 3);
@@ -191,4 +671,158 @@ mod canvas_tests {
         let ppm = c.get_ppm();
         assert_eq!(ppm.chars().last().unwrap(), '\n');
     }
+
+    #[test]
+    fn png_signature_and_header() {
+        let c = Canvas::new(5, 3);
+        let png = c.get_png();
+        assert_eq!(png[0..8], [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..20], 5_u32.to_be_bytes());
+        assert_eq!(png[20..24], 3_u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+
+    #[test]
+    fn png_pixel_data() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(1.5, 0.0, 0.0));
+        c.write_pixel(1, 0, Color::new(0.0, 0.5, 0.0));
+        let png = c.get_png();
+        // IDAT data starts after signature (8), IHDR chunk (25) and IDAT length and type (8)
+        // Then zlib header (2) and stored block header (5)
+        let pixels = &png[48..55];
+        assert_eq!(pixels, [0, 255, 0, 0, 0, 128, 0]);
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+    }
+
+    #[test]
+    fn adler32_check_value() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn reading_flat_hdr() {
+        let mut hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 1\n".to_vec();
+        hdr.extend([128, 64, 0, 129, 0, 0, 0, 0]);
+        let c = Canvas::from_hdr(&hdr).unwrap();
+        assert_eq!((c.width, c.height), (1, 2));
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.5, 0.0));
+        assert_eq!(c.pixel_at(0, 1), Color::black());
+    }
+
+    #[test]
+    fn reading_run_length_encoded_hdr() {
+        let mut hdr = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+        hdr.extend([2, 2, 0, 8]);
+        hdr.extend([136, 128]); // red- run of 8
+        hdr.extend([4, 0, 64, 128, 255, 132, 32]); // green- 4 literals and a run of 4
+        hdr.extend([136, 0]); // blue
+        hdr.extend([136, 130]); // exponent, scale is 2^(130 - 136)
+        let c = Canvas::from_hdr(&hdr).unwrap();
+        assert_eq!(c.pixel_at(0, 0), Color::new(2.0, 0.0, 0.0));
+        assert_eq!(c.pixel_at(3, 0), Color::new(2.0, 3.984375, 0.0));
+        assert_eq!(c.pixel_at(7, 0), Color::new(2.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn invalid_hdr() {
+        assert!(Canvas::from_hdr(b"P3\n1 1\n255\n").is_err());
+        assert!(Canvas::from_hdr(b"#?RADIANCE\n\n+Y 1 +X 1\n").is_err());
+        assert!(Canvas::from_hdr(b"#?RADIANCE\n\n-Y 1 +X 2\n\x80\x80\x80\x80").is_err());
+    }
+    #[test]
+    fn reading_ppm() {
+        let mut c = Canvas::new(2, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.2));
+        c.write_pixel(1, 1, Color::new(0.0, 0.6, 1.0));
+        let read = Canvas::from_ppm(c.get_ppm().as_bytes()).unwrap();
+        assert_eq!((read.width, read.height), (2, 2));
+        assert_eq!(read.pixel_at(0, 0), Color::new(1.0, 0.0, 0.2));
+        assert_eq!(read.pixel_at(1, 1), Color::new(0.0, 0.6, 1.0));
+
+        let mut binary = b"P6\n# A comment\n2 1\n255\n".to_vec();
+        binary.extend([255, 0, 51, 0, 153, 255]);
+        let read = Canvas::from_ppm(&binary).unwrap();
+        assert_eq!(read.pixel_at(0, 0), Color::new(1.0, 0.0, 0.2));
+        assert_eq!(read.pixel_at(1, 0), Color::new(0.0, 0.6, 1.0));
+
+        assert!(Canvas::from_ppm(b"P3\n2 1\n255\n1 2 3\n").is_err());
+        assert!(Canvas::from_ppm(b"P5\n1 1\n255\n0").is_err());
+    }
+
+    #[test]
+    fn reading_png() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.2));
+        c.write_pixel(2, 1, Color::new(0.0, 0.6, 1.0));
+        let read = Canvas::from_png(&c.get_png()).unwrap();
+        assert_eq!((read.width, read.height), (3, 2));
+        assert_eq!(read.data, c.data);
+    }
+
+    // Png made of the given IHDR fields and (uncompressed) filtered rows
+    fn png(
+        width: u32,
+        height: u32,
+        depth: u8,
+        color_type: u8,
+        palette: &[u8],
+        raw: &[u8],
+    ) -> Vec<u8> {
+        let mut png: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let mut header: Vec<u8> = Vec::new();
+        header.extend(width.to_be_bytes());
+        header.extend(height.to_be_bytes());
+        header.extend([depth, color_type, 0, 0, 0]);
+        Canvas::push_png_chunk(&mut png, b"IHDR", &header);
+        if !palette.is_empty() {
+            Canvas::push_png_chunk(&mut png, b"PLTE", palette);
+        }
+        Canvas::push_png_chunk(&mut png, b"IDAT", &zlib_store(raw));
+        Canvas::push_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn reading_filtered_png() {
+        // Gray and alpha, 2 bytes per pixel. Rows use the Sub, Up, Average and Paeth filters
+        let raw = [
+            1, 10, 255, 20, 0, // 10 30
+            2, 5, 0, 5, 0, // 15 35
+            3, 10, 0, 7, 0, // 17 = 10 + 15 / 2, 33 = 7 + (17 + 35) / 2
+            4, 1, 0, 1, 0, // 18 = 1 + 17 (above), 34 = 1 + 33 (above)
+        ];
+        let c = Canvas::from_png(&png(2, 4, 8, 4, &[], &raw)).unwrap();
+        let gray = |value: Float| Color::new(value, value, value) * (1.0 / 255.0);
+        assert_eq!(c.pixel_at(1, 0), gray(30.0));
+        assert_eq!(c.pixel_at(0, 1), gray(15.0));
+        assert_eq!(c.pixel_at(0, 2), gray(17.0));
+        assert_eq!(c.pixel_at(1, 2), gray(33.0));
+        assert_eq!(c.pixel_at(0, 3), gray(18.0));
+        assert_eq!(c.pixel_at(1, 3), gray(34.0));
+    }
+
+    #[test]
+    fn reading_palette_png() {
+        // 2 bits per pixel, 5 pixels in a row- indices 0 1 2 1 | 0
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let c = Canvas::from_png(&png(5, 1, 2, 3, &palette, &[0, 0b00011001, 0])).unwrap();
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(c.pixel_at(1, 0), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(c.pixel_at(2, 0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(c.pixel_at(3, 0), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(c.pixel_at(4, 0), Color::new(1.0, 0.0, 0.0));
+
+        let out_of_range = png(1, 1, 8, 3, &palette, &[0, 3]);
+        assert!(Canvas::from_png(&out_of_range).is_err());
+        let mut corrupted = png(1, 1, 8, 0, &[], &[0, 3]);
+        corrupted[20] ^= 1;
+        assert!(Canvas::from_png(&corrupted).is_err());
+    }
 }
@@ -1,4 +1,5 @@
 use crate::Compare;
+use crate::Float;
 
 // Color class to represent a color; similar structure to Tuple
 // All the components should be between 0 and 1
@@ -6,13 +7,13 @@ use crate::Compare;
 // if we clip at every stage, then the final image would be too dark or too light
 #[derive(Clone, Copy, Debug)]
 pub struct Color {
-    pub red: f32,   // red component of the color
-    pub green: f32, // green component of the color
-    pub blue: f32,  // blue component of the color
+    pub red: Float,   // red component of the color
+    pub green: Float, // green component of the color
+    pub blue: Float,  // blue component of the color
 }
 
 impl Color {
-    pub fn new(red: f32, green: f32, blue: f32) -> Color {
+    pub fn new(red: Float, green: Float, blue: Float) -> Color {
         Color { red, green, blue }
     }
 
@@ -35,6 +36,16 @@ impl Color {
     pub fn blue() -> Color {
         Color::new(0.0, 0.0, 1.0)
     }
+
+    // Largest of the three components
+    pub fn max_component(&self) -> Float {
+        self.red.max(self.green).max(self.blue)
+    }
+
+    // Perceived brightness of the color
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 // For color + color
@@ -70,16 +81,16 @@ impl PartialEq for Color {
 }
 
 // For color * a
-impl std::ops::Mul<f32> for Color {
+impl std::ops::Mul<Float> for Color {
     type Output = Self;
 
-    fn mul(self, a: f32) -> Self {
+    fn mul(self, a: Float) -> Self {
         Color::new(self.red * a, self.green * a, self.blue * a)
     }
 }
 
 // For a * color
-impl std::ops::Mul<Color> for f32 {
+impl std::ops::Mul<Color> for Float {
     type Output = Color;
 
     fn mul(self, color: Color) -> Color {
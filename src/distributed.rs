@@ -0,0 +1,562 @@
+use crate::wire::{get_bytes, get_float, get_u16, get_u32, get_u8, invalid};
+use crate::wire::{put_bytes, put_float, put_u16, put_u32};
+use crate::Camera;
+use crate::Canvas;
+use crate::Color;
+use crate::Scene;
+use crate::Tile;
+use crate::TileOrder;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Protocol between the coordinator and its workers, over TCP-
+// The worker connects and sends MAGIC, the coordinator answers with the JOB (or DONE when the
+// render is already over)
+// Then the coordinator sends a TILE, the worker renders it and answers with its PIXELS, until
+// there are no tiles left and the coordinator sends DONE
+// Numbers and floats are encoded as in `wire`
+const MAGIC: &[u8; 4] = b"RTD1";
+const JOB: u8 = 0;
+const TILE: u8 = 1;
+const PIXELS: u8 = 2;
+const DONE: u8 = 3;
+
+// How often the coordinator checks for new workers
+const ACCEPT_POLL: Duration = Duration::from_millis(10);
+// How long a connection has to send MAGIC, so that one which never does is dropped instead of
+// keeping the render from finishing
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+// Default `Coordinator::tile_timeout`
+const TILE_TIMEOUT: Duration = Duration::from_secs(300);
+// Workers a tile is handed to before the render gives up on it
+const TILE_ATTEMPTS: u32 = 3;
+
+// Everything a worker needs to render tiles of an image- the scene file, the content of the
+// images it uses (so workers on other machines do not need them) and the camera, which replaces
+// the camera of the scene file
+#[derive(Clone)]
+pub struct RenderJob {
+    pub source: String,
+    pub files: Vec<(String, Vec<u8>)>, // Path as written in the scene file, and content
+    pub camera: Camera,
+}
+
+// Hands out the tiles of a render to the workers which connect to it, see `run_worker`
+// A worker which disconnects, or does not send a tile back within `tile_timeout` (which must not
+// be zero) of getting it, is dropped and its tile goes to the next free worker
+pub struct Coordinator {
+    listener: TcpListener,
+    address: SocketAddr,
+    job: Vec<u8>, // JOB message, the same for every worker
+    camera: Camera,
+    pub tile_timeout: Duration,
+}
+
+// Tiles waiting for a worker with the number of workers which already failed them, and the number
+// of tiles not rendered yet (including those being rendered right now)
+// Once a tile failed `TILE_ATTEMPTS` times the render is `abandoned`
+struct Queue {
+    pending: VecDeque<(Tile, u32)>,
+    left: usize,
+    abandoned: bool,
+}
+
+// Reads from `stream`, failing with `TimedOut` once `deadline` has passed
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl RenderJob {
+    // Job rendering the scene file content `source` with `camera`
+    // The images the scene uses are read from disk now, to be sent along
+    pub fn new(source: &str, camera: Camera) -> Result<RenderJob, String> {
+        let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+        Scene::parse_with(source, |path| {
+            let bytes = fs::read(path).map_err(|err| err.to_string())?;
+            if !files.iter().any(|(name, _)| name == path) {
+                files.push((path.to_string(), bytes.clone()));
+            }
+            Ok(bytes)
+        })?;
+        Ok(RenderJob {
+            source: source.to_string(),
+            files,
+            camera,
+        })
+    }
+
+    // Scene of the job, with the images sent along and its camera
+    pub fn scene(&self) -> Result<Scene, String> {
+        let mut scene = Scene::parse_with(&self.source, |path| {
+            self.files
+                .iter()
+                .find(|(name, _)| name == path)
+                .map(|(_, bytes)| bytes.clone())
+                .ok_or_else(|| String::from("not sent with the job"))
+        })?;
+        scene.camera = self.camera.clone();
+        Ok(scene)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut message = vec![JOB];
+        put_bytes(&mut message, self.source.as_bytes());
+        put_u32(&mut message, self.files.len() as u32);
+        for (path, bytes) in &self.files {
+            put_bytes(&mut message, path.as_bytes());
+            put_bytes(&mut message, bytes);
+        }
+
+        self.camera.write_to(&mut message);
+        message
+    }
+
+    // Reads the job sent by `encode`, after its JOB tag
+    fn decode(reader: &mut impl Read) -> io::Result<RenderJob> {
+        let source = String::from_utf8(get_bytes(reader)?)
+            .map_err(|_| invalid("scene file is not valid UTF-8"))?;
+        let mut files = Vec::new();
+        for _ in 0..get_u32(reader)? {
+            let path = String::from_utf8(get_bytes(reader)?)
+                .map_err(|_| invalid("file path is not valid UTF-8"))?;
+            files.push((path, get_bytes(reader)?));
+        }
+
+        let camera = Camera::read_from(reader)?;
+        Ok(RenderJob {
+            source,
+            files,
+            camera,
+        })
+    }
+}
+
+impl Coordinator {
+    // Listens for workers on `address`, port 0 picks a free port (see `address`)
+    pub fn bind(address: impl ToSocketAddrs, job: &RenderJob) -> Result<Coordinator, String> {
+        let listener = TcpListener::bind(address)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|err| format!("Unable to listen for workers: {}", err))?;
+        let address = listener
+            .local_addr()
+            .map_err(|err| format!("Unable to listen for workers: {}", err))?;
+        Ok(Coordinator {
+            listener,
+            address,
+            job: job.encode(),
+            camera: job.camera.clone(),
+            tile_timeout: TILE_TIMEOUT,
+        })
+    }
+
+    // Address the workers connect to
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Renders the `region` of the image with the workers, in tiles of `tile_size` x `tile_size`
+    /// pixels handed out in `order`, and stitches them into a canvas of the size of the region
+    /// Workers can join at any time, the render waits until one does. Those connecting between
+    /// two renders join the next one
+    /// `on_tile` gets every tile as soon as it is back from its worker
+    /// Fails when a tile could not be rendered by `TILE_ATTEMPTS` workers (they disconnected or ran
+    /// out of `tile_timeout`, raise it for slow tiles), or when a thread of the coordinator panicked
+    pub fn render(
+        &self,
+        region: Tile,
+        tile_size: u16,
+        order: TileOrder,
+        mut on_tile: impl FnMut(Tile, &Canvas),
+    ) -> Result<Canvas, String> {
+        assert!(
+            region.x as u32 + region.width as u32 <= self.camera.hsize() as u32
+                && region.y as u32 + region.height as u32 <= self.camera.vsize() as u32,
+            "Region is outside of the camera canvas"
+        );
+        assert!(
+            !self.tile_timeout.is_zero(),
+            "Tile timeout must not be zero"
+        );
+        let tiles = region.split(tile_size, order);
+        let total = tiles.len();
+        let queue = Mutex::new(Queue {
+            pending: tiles.into_iter().map(|tile| (tile, 0)).collect(),
+            left: total,
+            abandoned: false,
+        });
+        let wake = Condvar::new();
+        let mut canvas = Canvas::new(region.width as usize, region.height as usize);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| -> Result<(), String> {
+            let (queue, wake) = (&queue, &wake);
+            // Once the render is over, the workers still waiting to be accepted are sent home
+            // Without the queue nothing can be handed out anymore, the render fails once the
+            // workers already accepted are gone
+            scope.spawn(move || loop {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        let sender = sender.clone();
+                        // Failed workers are only dropped, their tile is already back in line
+                        scope.spawn(move || self.serve(stream, queue, wake, sender));
+                    }
+                    Err(_) => match lock(queue) {
+                        Ok(queue) if !queue.is_over() => thread::sleep(ACCEPT_POLL),
+                        _ => break,
+                    },
+                }
+            });
+            for _ in 0..total {
+                let (tile, image): (Tile, Canvas) = receiver
+                    .recv()
+                    .map_err(|_| String::from("Workers stopped before the render was over"))??;
+                let (col, row) = ((tile.x - region.x) as usize, (tile.y - region.y) as usize);
+                canvas.paste(&image, col, row);
+                on_tile(tile, &image);
+            }
+            Ok(())
+        })?;
+        Ok(canvas)
+    }
+
+    // Hands out tiles to the worker connected through `stream` until there are none left
+    fn serve(
+        &self,
+        stream: TcpStream,
+        queue: &Mutex<Queue>,
+        wake: &Condvar,
+        sender: mpsc::Sender<Result<(Tile, Canvas), String>>,
+    ) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(self.tile_timeout))?;
+        let mut reader = BufReader::new(DeadlineReader {
+            stream: stream.try_clone()?,
+            deadline: Instant::now() + HANDSHAKE_TIMEOUT,
+        });
+        let mut writer = BufWriter::new(stream);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a worker"));
+        }
+        if lock(queue)?.is_over() {
+            writer.write_all(&[DONE])?;
+            return writer.flush();
+        }
+        writer.write_all(&self.job)?;
+        writer.flush()?;
+
+        loop {
+            let tile = {
+                let mut queue = lock(queue)?;
+                loop {
+                    if queue.is_over() {
+                        break None;
+                    }
+                    if let Some(tile) = queue.pending.pop_front() {
+                        break Some(tile);
+                    }
+                    // Another worker may still fail and give its tile back
+                    queue = wake.wait(queue).map_err(|_| poisoned())?;
+                }
+            };
+            let Some((tile, failures)) = tile else {
+                writer.write_all(&[DONE])?;
+                return writer.flush();
+            };
+            reader.get_mut().deadline = Instant::now() + self.tile_timeout;
+            match render_remotely(&mut reader, &mut writer, tile) {
+                Ok(image) => {
+                    let mut queue = lock(queue)?;
+                    queue.left -= 1;
+                    if queue.left == 0 {
+                        wake.notify_all();
+                    }
+                    drop(queue);
+                    // Error only when the render is over, nobody wants the tile anymore
+                    let _ = sender.send(Ok((tile, image)));
+                }
+                Err(err) => {
+                    let mut queue = lock(queue)?;
+                    if failures + 1 < TILE_ATTEMPTS {
+                        queue.pending.push_front((tile, failures + 1));
+                        wake.notify_one();
+                    } else {
+                        // The other workers are sent home, and the render fails
+                        queue.abandoned = true;
+                        wake.notify_all();
+                        let _ = sender.send(Err(format!(
+                            "Tile at ({}, {}) failed on {} workers, the last one with: {}",
+                            tile.x, tile.y, TILE_ATTEMPTS, err
+                        )));
+                    }
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+// Sends `tile` to the worker and waits for its pixels
+fn render_remotely(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    tile: Tile,
+) -> io::Result<Canvas> {
+    let mut message = vec![TILE];
+    for value in [tile.x, tile.y, tile.width, tile.height] {
+        put_u16(&mut message, value);
+    }
+    writer.write_all(&message)?;
+    writer.flush()?;
+
+    if get_u8(reader)? != PIXELS {
+        return Err(invalid("expected the pixels of a tile"));
+    }
+    let mut image = Canvas::new(tile.width as usize, tile.height as usize);
+    for pixel in image.data.iter_mut() {
+        *pixel = Color::new(get_float(reader)?, get_float(reader)?, get_float(reader)?);
+    }
+    Ok(image)
+}
+
+/// Connects to the coordinator at `address` and renders the tiles it hands out with `threads`
+/// threads, until it has none left. Returns the number of tiles rendered
+pub fn run_worker(address: impl ToSocketAddrs, threads: usize) -> Result<usize, String> {
+    let network = |err: io::Error| format!("Connection to the coordinator failed: {}", err);
+    let stream = TcpStream::connect(address).map_err(network)?;
+    stream.set_nodelay(true).map_err(network)?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(network)?);
+    let mut writer = BufWriter::new(stream);
+    writer
+        .write_all(MAGIC)
+        .and_then(|_| writer.flush())
+        .map_err(network)?;
+
+    let job = match get_u8(&mut reader).map_err(network)? {
+        JOB => RenderJob::decode(&mut reader).map_err(network)?,
+        DONE => return Ok(0),
+        _ => return Err(network(invalid("expected the job"))),
+    };
+    let mut scene = job.scene()?;
+    scene.camera.threads = threads.max(1);
+    let camera = &scene.camera;
+
+    let mut rendered = 0;
+    loop {
+        match get_u8(&mut reader).map_err(network)? {
+            TILE => {
+                let mut values = [0; 4];
+                for value in values.iter_mut() {
+                    *value = get_u16(&mut reader).map_err(network)?;
+                }
+                let [x, y, width, height] = values;
+                if x as u32 + width as u32 > camera.hsize() as u32
+                    || y as u32 + height as u32 > camera.vsize() as u32
+                {
+                    return Err(String::from("Tile is outside of the image"));
+                }
+                let image = camera.render_region(&scene.world, x, y, width, height);
+                let mut message = vec![PIXELS];
+                for pixel in &image.data {
+                    put_float(&mut message, pixel.red);
+                    put_float(&mut message, pixel.green);
+                    put_float(&mut message, pixel.blue);
+                }
+                writer
+                    .write_all(&message)
+                    .and_then(|_| writer.flush())
+                    .map_err(network)?;
+                rendered += 1;
+            }
+            DONE => return Ok(rendered),
+            _ => return Err(network(invalid("unexpected message"))),
+        }
+    }
+}
+
+impl Queue {
+    // Nothing left to hand out, every tile is rendered or the render is abandoned
+    fn is_over(&self) -> bool {
+        self.left == 0 || self.abandoned
+    }
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+// Locks the queue, an error when a thread panicked while holding it
+fn lock(queue: &Mutex<Queue>) -> io::Result<MutexGuard<'_, Queue>> {
+    queue.lock().map_err(|_| poisoned())
+}
+
+fn poisoned() -> io::Error {
+    io::Error::other("a thread of the coordinator panicked")
+}
+
+#[cfg(test)]
+mod distributed_tests {
+    use super::*;
+
+    const SCENE: &str = "
+camera 21 15 pi/2
+view 0 0 -5 0 0 0 0 1 0
+light -10 10 -10 1 1 1
+sphere
+color 0.8 1 0.6
+diffuse 0.7
+specular 0.2
+";
+
+    fn job() -> RenderJob {
+        let mut camera = Scene::parse(SCENE).unwrap().camera;
+        camera.samples_per_pixel = 2;
+        RenderJob::new(SCENE, camera).unwrap()
+    }
+
+    #[test]
+    fn job_is_sent_whole() {
+        let job = job();
+        let decoded = RenderJob::decode(&mut &job.encode()[1..]).unwrap();
+        assert_eq!(decoded.source, job.source);
+        assert_eq!(decoded.camera.transform(), job.camera.transform());
+        assert_eq!(decoded.camera.samples_per_pixel, 2);
+        assert_eq!(decoded.camera.hsize(), 21);
+    }
+
+    #[test]
+    fn workers_render_the_whole_image() {
+        let job = job();
+        let scene = job.scene().unwrap();
+        let coordinator = Coordinator::bind("127.0.0.1:0", &job).unwrap();
+        let address = coordinator.address();
+        let region = Tile::new(0, 0, 21, 15);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..2)
+                .map(|_| scope.spawn(move || run_worker(address, 1)))
+                .collect();
+            let mut finished = 0;
+            let image = coordinator
+                .render(region, 4, TileOrder::Spiral, |_, _| finished += 1)
+                .unwrap();
+            assert_eq!(image.data, scene.camera.render(&scene.world).data);
+            assert_eq!(finished, 24);
+            // A worker which only tries to connect once the coordinator is gone fails
+            drop(coordinator);
+            let rendered: usize = workers
+                .into_iter()
+                .filter_map(|worker| worker.join().unwrap().ok())
+                .sum();
+            assert_eq!(rendered, 24);
+        });
+    }
+
+    #[test]
+    fn tiles_of_a_dead_worker_are_reassigned() {
+        let job = job();
+        let scene = job.scene().unwrap();
+        let coordinator = Coordinator::bind("127.0.0.1:0", &job).unwrap();
+        let address = coordinator.address();
+        let region = Tile::new(0, 0, 21, 15);
+        thread::scope(|scope| {
+            let render =
+                scope.spawn(|| coordinator.render(region, 8, TileOrder::Scanline, |_, _| {}));
+            // Worker takes the first tile and dies before sending it back
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(MAGIC).unwrap();
+            assert_eq!(get_u8(&mut stream).unwrap(), JOB);
+            RenderJob::decode(&mut stream).unwrap();
+            assert_eq!(get_u8(&mut stream).unwrap(), TILE);
+            drop(stream);
+
+            assert_eq!(run_worker(address, 2).unwrap(), 6);
+            let image = render.join().unwrap().unwrap();
+            assert_eq!(image.data, scene.camera.render(&scene.world).data);
+        });
+    }
+
+    #[test]
+    fn tiles_of_a_hung_worker_are_reassigned() {
+        let job = job();
+        let scene = job.scene().unwrap();
+        let mut coordinator = Coordinator::bind("127.0.0.1:0", &job).unwrap();
+        coordinator.tile_timeout = Duration::from_millis(200);
+        let address = coordinator.address();
+        let region = Tile::new(0, 0, 21, 15);
+        thread::scope(|scope| {
+            let render =
+                scope.spawn(|| coordinator.render(region, 8, TileOrder::Scanline, |_, _| {}));
+            // Worker takes the first tile and never answers, but stays connected
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(MAGIC).unwrap();
+            assert_eq!(get_u8(&mut stream).unwrap(), JOB);
+            RenderJob::decode(&mut stream).unwrap();
+            assert_eq!(get_u8(&mut stream).unwrap(), TILE);
+
+            let worker = scope.spawn(move || run_worker(address, 2));
+            let image = render.join().unwrap().unwrap();
+            assert_eq!(image.data, scene.camera.render(&scene.world).data);
+            assert_eq!(worker.join().unwrap().unwrap(), 6);
+            drop(stream);
+        });
+    }
+
+    #[test]
+    fn render_fails_when_a_tile_fails_too_often() {
+        let job = job();
+        let coordinator = Coordinator::bind("127.0.0.1:0", &job).unwrap();
+        let address = coordinator.address();
+        let region = Tile::new(0, 0, 21, 15);
+        thread::scope(|scope| {
+            let render =
+                scope.spawn(|| coordinator.render(region, 8, TileOrder::Scanline, |_, _| {}));
+            // Every worker dies with the first tile
+            for _ in 0..TILE_ATTEMPTS {
+                let mut stream = TcpStream::connect(address).unwrap();
+                stream.write_all(MAGIC).unwrap();
+                assert_eq!(get_u8(&mut stream).unwrap(), JOB);
+                RenderJob::decode(&mut stream).unwrap();
+                assert_eq!(get_u8(&mut stream).unwrap(), TILE);
+                assert_eq!(get_u16(&mut stream).unwrap(), 0);
+                assert_eq!(get_u16(&mut stream).unwrap(), 0);
+            }
+            let err = render.join().unwrap().err().unwrap();
+            assert!(
+                err.starts_with("Tile at (0, 0) failed on 3 workers"),
+                "{}",
+                err
+            );
+        });
+    }
+
+    #[test]
+    fn silent_connections_do_not_block_the_render() {
+        let job = job();
+        let coordinator = Coordinator::bind("127.0.0.1:0", &job).unwrap();
+        let address = coordinator.address();
+        let region = Tile::new(0, 0, 21, 15);
+        thread::scope(|scope| {
+            let render =
+                scope.spawn(|| coordinator.render(region, 8, TileOrder::Scanline, |_, _| {}));
+            // Connects and never sends the magic
+            let silent = TcpStream::connect(address).unwrap();
+            assert_eq!(run_worker(address, 2).unwrap(), 6);
+            assert!(render.join().unwrap().is_ok());
+            drop(silent);
+        });
+    }
+}
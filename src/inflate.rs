@@ -0,0 +1,301 @@
+// Decompression of zlib/deflate streams (RFC 1950 and RFC 1951), needed to read PNG images
+
+// Base values and extra bits of the length codes 257..285 and of the distance codes 0..29
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// Reads the stream bit by bit, least significant bit of each byte first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,  // Index of the next byte
+    buffer: u32, // Bits read but not used yet
+    count: u32,  // Number of bits in `buffer`
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or("Unexpected end of deflate data")?;
+            self.buffer |= (byte as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1_u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    // Skip to the start of the next byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// Canonical Huffman code, given by the code length of every symbol
+struct Huffman {
+    counts: [u16; 16], // Number of codes of each length
+    symbols: Vec<u16>, // Symbols ordered by code
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0_u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0_u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    // Codes of the same length are consecutive numbers, so read bit by bit until the code
+    // falls in the range of its length
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0_i32, 0_i32, 0_i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("Invalid Huffman code in deflate data"))
+    }
+}
+
+// Decompress a zlib stream (2 bytes of header, deflate data, adler32 checksum)
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 2
+        || data[0] & 0x0F != 8
+        || !((data[0] as u16) << 8 | data[1] as u16).is_multiple_of(31)
+    {
+        return Err(String::from("Invalid zlib header"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(String::from("zlib preset dictionaries are not supported"));
+    }
+    inflate(&data[2..])
+}
+
+// Decompress raw deflate data
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader::new(data);
+    let mut output: Vec<u8> = Vec::new();
+    loop {
+        let is_final = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = reader
+                    .data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or("Unexpected end of deflate data")?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(String::from("Corrupted stored deflate block"));
+                }
+                let start = reader.pos + 4;
+                let block = reader
+                    .data
+                    .get(start..start + length as usize)
+                    .ok_or("Unexpected end of deflate data")?;
+                output.extend(block);
+                reader.pos = start + length as usize;
+            }
+            1 => {
+                let mut lengths = [0_u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(String::from("Invalid deflate block type")),
+        }
+        if is_final {
+            return Ok(output);
+        }
+    }
+}
+
+// Huffman codes of a dynamic block, their code lengths are themselves Huffman coded
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0_u8; 19];
+    for &symbol in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths: Vec<u8> = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths.last().ok_or("Invalid deflate code lengths")?,
+                3 + reader.bits(2)?,
+            ),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(String::from("Invalid deflate code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+// Literals are copied as they are, lengths are followed by a distance to copy from
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let ind = symbol - 257;
+                let length =
+                    LENGTH_BASE[ind] as usize + reader.bits(LENGTH_EXTRA[ind] as u32)? as usize;
+                let ind = distances.decode(reader)? as usize;
+                if ind >= 30 {
+                    return Err(String::from("Invalid deflate distance"));
+                }
+                let distance =
+                    DISTANCE_BASE[ind] as usize + reader.bits(DISTANCE_EXTRA[ind] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(String::from("Invalid deflate distance"));
+                }
+                // The copy can overlap with the bytes it produces, so go byte by byte
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+            _ => return Err(String::from("Invalid deflate length")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod inflate_tests {
+    use super::*;
+
+    #[test]
+    fn stored_block() {
+        let data = [
+            0x78, 0x01, 1, 3, 0, 0xFC, 0xFF, b'a', b'b', b'c', 0, 0, 0, 0,
+        ];
+        assert_eq!(zlib_decompress(&data).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn fixed_huffman_block() {
+        let data = [
+            120, 218, 203, 72, 205, 201, 201, 87, 200, 64, 39, 1, 104, 3, 8, 177,
+        ];
+        assert_eq!(zlib_decompress(&data).unwrap(), b"hello hello hello hello");
+    }
+
+    #[test]
+    fn dynamic_huffman_block() {
+        let expected: String = (0..40)
+            .map(|i| {
+                format!(
+                    "The quick brown fox {} jumps over the lazy dog {} times. ",
+                    i,
+                    i * i
+                )
+            })
+            .collect();
+        assert_eq!(zlib_decompress(DYNAMIC).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn invalid_streams() {
+        assert!(zlib_decompress(&[0x78]).is_err());
+        assert!(zlib_decompress(&[0x78, 0x02, 0x00]).is_err());
+        assert!(zlib_decompress(&[120, 218, 203, 72, 205]).is_err());
+    }
+
+    // The text of `dynamic_huffman_block` compressed by zlib at level 9
+    const DYNAMIC: &[u8] = &[
+        120, 218, 133, 149, 91, 86, 194, 64, 16, 5, 183, 50, 43, 240, 164, 187, 103, 38, 51, 251,
+        96, 3, 42, 65, 64, 49, 202, 67, 148, 213, 203, 159, 63, 222, 202, 119, 168, 211, 244, 237,
+        202, 205, 106, 59, 165, 207, 203, 238, 249, 53, 61, 29, 231, 235, 123, 218, 204, 223, 105,
+        72, 251, 203, 225, 227, 148, 230, 175, 233, 152, 206, 247, 31, 188, 61, 222, 126, 210, 122,
+        126, 185, 63, 57, 239, 14, 211, 233, 33, 173, 254, 193, 76, 98, 70, 152, 75, 44, 19, 22,
+        18, 235, 132, 101, 253, 39, 43, 113, 69, 114, 94, 136, 171, 146, 11, 156, 55, 234, 84, 112,
+        191, 38, 185, 138, 113, 118, 201, 53, 188, 158, 105, 89, 108, 96, 93, 192, 23, 231, 153,
+        90, 25, 203, 184, 165, 5, 156, 31, 115, 53, 16, 167, 227, 37, 13, 212, 97, 119, 172, 130,
+        116, 60, 83, 235, 227, 141, 247, 212, 2, 133, 115, 182, 29, 84, 231, 6, 208, 14, 101, 118,
+        200, 181, 67, 57, 243, 76, 168, 157, 134, 123, 186, 118, 168, 56, 102, 235, 218, 161, 50,
+        226, 61, 93, 59, 84, 217, 33, 215, 14, 213, 133, 153, 218, 161, 113, 97, 79, 237, 208, 184,
+        144, 45, 212, 16, 223, 51, 180, 67, 157, 29, 10, 237, 80, 103, 111, 3, 122, 104, 224, 151,
+        37, 160, 136, 6, 126, 67, 3, 154, 200, 184, 22, 162, 64, 229, 178, 71, 81, 1, 229, 2, 12,
+        45, 146, 5, 183, 110, 52, 106, 122, 78, 88, 171, 100, 229, 239, 251, 242, 11, 168, 248, 15,
+        202,
+    ];
+}
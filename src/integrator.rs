@@ -0,0 +1,309 @@
+use crate::consts::PI;
+use crate::utils::Rng;
+use crate::Float;
+use crate::{Color, Computation, Ray, Vector3, World};
+
+// Bounces every path makes before Russian roulette may stop it
+const MIN_BOUNCES: u16 = 3;
+
+// Decides how the color seen along a camera ray is computed, picked per render on the `Camera`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Integrator {
+    // Direct lighting with Phong shading and a constant ambient term, see `World::color_at`
+    #[default]
+    Whitted,
+    // Monte Carlo path tracing, light bounces between the objects so there is indirect light
+    // and color bleeding. Noisy, use many samples per pixel
+    // Paths are cut after `max_bounces`, Russian roulette stops most of them earlier
+    PathTracing {
+        max_bounces: u16,
+    },
+}
+
+impl Integrator {
+    // Color seen along `ray`, `rng` drives the random sampling of the path tracer
+    pub fn color_at(&self, world: &World, ray: &Ray, rng: &mut Rng) -> Color {
+        match *self {
+            Integrator::Whitted => world.color_at(ray, rng),
+            Integrator::PathTracing { max_bounces } => trace_path(world, *ray, max_bounces, rng),
+        }
+    }
+}
+
+// Follow one random path of light backwards from the camera
+// At every hit the light sources are sampled directly (next event estimation) and the path continues
+// in a random direction, so only the light reaching the hit through other objects is left to chance
+// Reflection is described by the BRDF of the material (see `Material::brdf`),
+// the Phong `ambient` term has no meaning here and is ignored
+fn trace_path(world: &World, mut ray: Ray, max_bounces: u16, rng: &mut Rng) -> Color {
+    let mut color = Color::black();
+    // Fraction of the light at the current hit which makes it back to the camera
+    let mut throughput = Color::white();
+    // Probability density of the direction of `ray`, from sampling the material at the last hit
+    let mut material_pdf = 0.0;
+    // Whether the path only went straight through transparent surfaces since the camera
+    let mut from_camera = true;
+
+    for bounce in 0..=max_bounces {
+        let hit = world.intersect_world(ray).hit();
+        // Fog and volumes on the way light the path and dim whatever is behind them
+        let distance = hit.map_or(Float::INFINITY, |hit| hit.t);
+        let (transmittance, scattered) = world.march_media(&ray, distance, rng);
+        color = color + throughput * scattered;
+        throughput = throughput * transmittance;
+
+        let hit = match hit {
+            Some(hit) => hit,
+            None => {
+                // Background is both sampled directly and found by the bounces, weigh the two
+                let background = world.background.color(ray.direction);
+                let weight = if from_camera {
+                    1.0
+                } else {
+                    power_heuristic(material_pdf, world.background.pdf(ray.direction))
+                };
+                color = color + throughput * background * weight;
+                break;
+            }
+        };
+        let comps = world.prepare_computations(&hit, &ray);
+        let material = comps.material;
+
+        // Light goes straight through a transparent surface with the chance of its `transparency`,
+        // tinted by its color. The light sampled at the last bounce went through the surface the
+        // same way (see `World::transmission`), so nothing is counted twice
+        if material.transparency > 0.0 && rng.next_float() < material.transparency {
+            throughput = throughput * material.color;
+            ray = Ray::new(comps.under_point, ray.direction).at_time(comps.time);
+            continue;
+        }
+
+        // Emitters found by later bounces are not added, their light is already sampled directly
+        if from_camera {
+            color = color + throughput * material.emission;
+        }
+        let direct = direct_light(world, &comps)
+            + world.emitted_light(&comps, 1, rng)
+            + background_light(world, &comps, rng);
+        color = color + throughput * direct;
+        if bounce == max_bounces {
+            break;
+        }
+
+        // Russian roulette- randomly stop the dim paths, and boost the surviving ones to stay unbiased
+        if bounce >= MIN_BOUNCES {
+            let survival = throughput.max_component().min(0.95);
+            if rng.next_float() >= survival {
+                break;
+            }
+            throughput = throughput * (1.0 / survival);
+        }
+
+        let (direction, weight) = match material.sample(comps.normalv, comps.eyev, rng) {
+            Some(sample) => sample,
+            None => break,
+        };
+        material_pdf = material.pdf(comps.normalv, comps.eyev, direction);
+        throughput = throughput * weight;
+        ray = Ray::new(comps.over_point, direction).at_time(comps.time);
+        from_camera = false;
+    }
+    color
+}
+
+// Light reaching the hit straight from the light source and reflected towards the eye
+// Like in the Phong model the intensity of the point light does not fall off with distance,
+// so a scene looks alike with both integrators
+fn direct_light(world: &World, comps: &Computation) -> Color {
+    let lightv = (world.light.position - comps.point).normalize();
+    let light_dot_normal = lightv.dot(&comps.normalv);
+    if light_dot_normal <= 0.0 {
+        return Color::black();
+    }
+    let transmission = world.hit_transmission(comps);
+    if transmission.max_component() <= 0.0 {
+        return Color::black();
+    }
+    let brdf = comps.material.brdf(comps.normalv, comps.eyev, lightv);
+    brdf * world.light.intensity * transmission * (PI * light_dot_normal)
+}
+
+// One importance sampled direction of the background, weighted against the chance of the
+// material sampling the same direction (multiple importance sampling)
+// Small bright spots of the environment are found by this, mirror like reflections by the bounces
+fn background_light(world: &World, comps: &Computation, rng: &mut Rng) -> Color {
+    if world.background.is_black() {
+        return Color::black();
+    }
+    match world.sample_background(comps, rng) {
+        Some((lightv, radiance, light_pdf)) => {
+            let material = comps.material;
+            let brdf = material.brdf(comps.normalv, comps.eyev, lightv);
+            let material_pdf = material.pdf(comps.normalv, comps.eyev, lightv);
+            let weight = power_heuristic(light_pdf, material_pdf);
+            radiance * brdf * (lightv.dot(&comps.normalv) * weight / light_pdf)
+        }
+        None => Color::black(),
+    }
+}
+
+// Weight of a sample taken with density `pdf` when `other_pdf` could also have produced it
+fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+// Random direction on the hemisphere around the unit `normal`, more likely close to the normal
+// (pdf = cos / pi). Uniform point on the unit disk projected up onto the hemisphere
+pub fn cosine_sample_hemisphere(normal: Vector3, rng: &mut Rng) -> Vector3 {
+    let r = rng.next_float().sqrt();
+    let phi = 2.0 * PI * rng.next_float();
+    let height: Float = (1.0 - r * r).max(0.0).sqrt();
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * height
+}
+
+#[cfg(test)]
+mod integrator_tests {
+    use super::*;
+    use crate::{point, vector, Background, Compare, Light, Material, Matrix, Sphere};
+
+    #[test]
+    fn whitted_integrator_is_color_at() {
+        let w = World::default();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(0);
+        assert_eq!(
+            Integrator::default().color_at(&w, &r, &mut rng),
+            w.color_at(&r, &mut Rng::new(0))
+        );
+    }
+
+    #[test]
+    fn cosine_samples_are_on_the_hemisphere() {
+        let normal = vector(1.0, 1.0, 0.0).normalize();
+        let mut rng = Rng::new(3);
+        let mut average_cos = 0.0;
+        for _ in 0..2000 {
+            let v = cosine_sample_hemisphere(normal, &mut rng);
+            assert!(v.magnitude().eq(1.0));
+            assert!(v.dot(&normal) >= 0.0);
+            average_cos += v.dot(&normal) / 2000.0;
+        }
+        // Mean of cos with pdf cos / pi is 2/3
+        assert!((average_cos - 2.0 / 3.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn path_without_bounces_is_direct_diffuse_light() {
+        let w = World::default();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let path = Integrator::PathTracing { max_bounces: 0 };
+        let c = path.color_at(&w, &r, &mut Rng::new(0));
+
+        // Phong without the ambient and specular terms
+        let material = Material {
+            ambient: 0.0,
+            specular: 0.0,
+            ..w.objects()[0].material
+        };
+        let p = point(0.0, 0.0, -1.0);
+        let expected = material.lighting(w.light, p, -r.direction, vector(0.0, 0.0, -1.0), false);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn path_tracing_brings_indirect_light_into_shadow() {
+        // Light is above a floor-like big sphere, the bottom of the small sphere only sees the floor
+        let mut floor = Sphere::default();
+        floor.set_transform(
+            Matrix::get_scaling_matrix(100.0, 100.0, 100.0).translate(0.0, -101.0, 0.0),
+        );
+        let mut w = World::new(Light::new(point(5.0, 10.0, -10.0), Color::white()));
+        w.add_object(Sphere::default());
+        w.add_object(floor);
+        // Looking up at the bottom of the sphere from below its equator
+        let r = Ray::new(point(0.0, -0.9, -5.0), vector(0.0, 0.0, 1.0));
+        let direct = Integrator::PathTracing { max_bounces: 0 };
+        let path = Integrator::PathTracing { max_bounces: 4 };
+        let mut rng = Rng::new(11);
+        assert_eq!(direct.color_at(&w, &r, &mut rng), Color::black());
+
+        let mut total = Color::black();
+        for _ in 0..64 {
+            total = total + path.color_at(&w, &r, &mut rng);
+        }
+        assert!(total.red > 0.0 && total.red == total.green && total.green == total.blue);
+    }
+
+    #[test]
+    fn path_tracing_under_white_sky() {
+        // Every path ends in the sky, the reflected light converges to the albedo
+        let mut s = Sphere::default();
+        s.material.color = Color::new(0.5, 0.5, 0.5);
+        s.material.diffuse = 1.0;
+        let mut w = World::new(Light::new(point(0.0, 10.0, 0.0), Color::black()));
+        w.add_object(s);
+        w.background = Background::Solid(Color::white());
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let path = Integrator::PathTracing { max_bounces: 4 };
+        let mut rng = Rng::new(21);
+        let mut c = Color::black();
+        for _ in 0..2000 {
+            c = c + path.color_at(&w, &r, &mut rng) * (1.0 / 2000.0);
+        }
+        assert!((c.red - 0.5).abs() < 0.02, "{:?}", c);
+        assert_eq!(
+            path.color_at(&w, &Ray::new(r.origin, -r.direction), &mut rng),
+            Color::white()
+        );
+    }
+
+    #[test]
+    fn emissive_sphere_lights_the_floor() {
+        let mut floor = Sphere::default();
+        floor.set_transform(Matrix::get_scaling_matrix(10.0, 0.01, 10.0));
+        let mut lamp = Sphere::default();
+        lamp.set_transform(Matrix::get_translation_matrix(0.0, 4.0, 0.0));
+        lamp.material.emission = Color::new(2.0, 2.0, 2.0);
+        let mut w = World::new(Light::new(point(0.0, 10.0, 0.0), Color::black()));
+        w.add_object(floor);
+        w.add_object(lamp);
+
+        // Right under the lamp, a sphere of radius R at distance D gives an irradiance of
+        // pi * L * (R / D)^2, which a diffuse surface reflects as albedo * L * (R / D)^2
+        let r = Ray::new(point(0.0, 1.0, -1.0), vector(0.0, -1.0, 1.0).normalize());
+        let direct = Integrator::PathTracing { max_bounces: 0 };
+        let mut rng = Rng::new(4);
+        let mut c = Color::black();
+        for _ in 0..800 {
+            c = c + direct.color_at(&w, &r, &mut rng) * (1.0 / 800.0);
+        }
+        let expected = 0.9 * 2.0 / (3.99 * 3.99);
+        assert!((c.red - expected).abs() < 0.02 * expected, "{:?}", c);
+
+        // The floor is dark where the lamp is hidden behind a blocker
+        let mut blocker = Sphere::default();
+        blocker.set_transform(Matrix::get_scaling_matrix(2.0, 0.01, 2.0).translate(0.0, 2.0, 0.0));
+        w.add_object(blocker);
+        assert_eq!(direct.color_at(&w, &r, &mut rng), Color::black());
+    }
+
+    #[test]
+    fn path_goes_through_transparent_sphere() {
+        let mut glass = Sphere::default();
+        glass.material.color = Color::new(0.5, 1.0, 1.0);
+        glass.material.transparency = 1.0;
+        let mut w = World::new(Light::new(point(0.0, 10.0, 0.0), Color::black()));
+        w.add_object(glass);
+        w.background = Background::Solid(Color::white());
+        // Tinted once going in and once going out
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let path = Integrator::PathTracing { max_bounces: 4 };
+        let c = path.color_at(&w, &r, &mut Rng::new(0));
+        assert_eq!(c, Color::new(0.25, 1.0, 1.0));
+    }
+}
@@ -1,12 +1,14 @@
-use crate::utils::EPSILON;
+use crate::utils::SURFACE_OFFSET;
+use crate::Float;
+use crate::Material;
 use crate::Ray;
 use crate::Sphere;
-use crate::Tuple;
+use crate::{Point3, Vector3};
 
 // Store data for ray intersection with a object in the scene
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Intersection {
-    pub t: f32,         // At what time hit occured
+    pub t: Float,       // At what time hit occured
     pub object: Sphere, // TODO: use dynamic data type
 }
 
@@ -18,17 +20,20 @@ pub struct Intersections {
 
 // Store some precomputations for the intersection
 pub struct Computation {
-    t: f32,
+    pub t: Float,
     pub object: Sphere,
-    pub point: Tuple,
-    pub eyev: Tuple,
-    pub normalv: Tuple,
-    inside: bool,
-    pub over_point: Tuple, // Just slightly above the point towards the normal to avoid `acne`
+    pub material: Material, // Material at the hit, the color of its pattern already looked up by the world
+    pub point: Point3,
+    pub eyev: Vector3,
+    pub normalv: Vector3,
+    pub inside: bool,
+    pub over_point: Point3, // Just slightly above the point towards the normal to avoid `acne`
+    pub under_point: Point3, // Just slightly below the surface, where the rays going through it start
+    pub time: Float, // Time of the ray, the rays leaving the hit are cast at the same instant
 }
 
 impl Intersection {
-    pub fn new(t: f32, object: Sphere) -> Self {
+    pub fn new(t: Float, object: Sphere) -> Self {
         Self { t, object }
     }
 
@@ -44,17 +49,22 @@ impl Intersection {
             inside = true;
             normalv = -normalv;
         }
-        // let over_point = point + normalv * EPSILON * 1000.0; // NOTE: why do we need this so large?? To compensate for f32 rounding errors -> use f64
-        let over_point = point + normalv * EPSILON; // NOTE: use this in f32 to pass tests
+        // Rounding errors grow with the coordinates, so does the offset
+        let offset = SURFACE_OFFSET * point.x.abs().max(point.y.abs()).max(point.z.abs()).max(1.0);
+        let over_point = point + normalv * offset;
+        let under_point = point - normalv * offset;
 
         Computation {
             t: self.t,
             object: self.object,
+            material: self.object.material,
             point,
             eyev,
             normalv,
             inside,
             over_point,
+            under_point,
+            time: ray.time,
         }
     }
 }
@@ -259,7 +269,7 @@ mod sphere_tests {
         shape.set_transform(Matrix::get_translation_matrix(0.0, 0.0, 1.0));
         let i = Intersection::new(5.0, shape);
         let comps = i.prepare_computations(&r);
-        assert!(comps.over_point.z < -EPSILON / 2.0);
+        assert!(comps.over_point.z < -SURFACE_OFFSET / 2.0);
         assert!(comps.point.z > comps.over_point.z);
     }
 }
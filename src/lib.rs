@@ -1,31 +1,68 @@
+mod animation;
+mod background;
+mod bump;
 mod camera;
 mod canvas;
 mod color;
+mod distributed;
+mod inflate;
+mod integrator;
 mod intersection;
 mod light;
 mod material;
 mod matrix;
 mod matrix_small;
+mod medium;
+mod motion;
+mod noise;
+mod passes;
+mod pattern;
+mod pbr;
+mod point;
 mod projectile;
+mod quaternion;
 mod ray;
+mod scene;
+pub mod simd;
 mod sphere;
+mod tiles;
 mod transformation;
 mod tuple;
 mod utils;
+mod vector;
+mod wire;
 mod world;
 
 // Use in chapter end exercises
-pub use camera::Camera;
+pub use animation::{
+    Animation, Channel, Easing, Interpolate, Key, MaterialTrack, Track, TransformTrack,
+};
+pub use background::{Background, EnvironmentMap};
+pub use bump::Bump;
+pub use camera::{AdaptiveSampling, Camera, Progress};
 pub use canvas::Canvas;
 pub use color::Color;
+pub use distributed::{run_worker, Coordinator, RenderJob};
+pub use integrator::Integrator;
 pub use intersection::{Computation, Intersection, Intersections};
 pub use light::Light;
 pub use material::Material;
 pub use matrix::Matrix;
 pub use matrix_small::{Matrix2, Matrix3};
+pub use medium::{Fog, Medium, Volume};
+pub use motion::{Keyframe, Motion};
+pub use noise::Noise;
+pub use passes::{FloatBuffer, RenderPasses};
+pub use pattern::{Pattern, PatternKind, UvMapping, WrapMode};
+pub use pbr::Pbr;
+pub use point::{point, Point3};
 pub use projectile::{Environment, Projectile};
+pub use quaternion::Quaternion;
 pub use ray::Ray;
+pub use scene::Scene;
 pub use sphere::Sphere;
-pub use tuple::{point, vector, Tuple};
-pub use utils::Compare;
-pub use world::World;
+pub use tiles::{Tile, TileOrder};
+pub use tuple::Tuple;
+pub use utils::{consts, Compare, Float, Rng};
+pub use vector::{vector, Vector3};
+pub use world::{Pick, World};
@@ -1,14 +1,14 @@
-use crate::{Color, Tuple};
+use crate::{Color, Point3};
 
 // A point light with no size, existing at a single point in space with a given intensity.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Light {
-    pub position: Tuple,
+    pub position: Point3,
     pub intensity: Color,
 }
 
 impl Light {
-    pub fn new(position: Tuple, intensity: Color) -> Self {
+    pub fn new(position: Point3, intensity: Color) -> Self {
         Self {
             position,
             intensity,
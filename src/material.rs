@@ -1,23 +1,50 @@
+use crate::consts::PI;
+use crate::integrator::cosine_sample_hemisphere;
+use crate::utils::Rng;
+use crate::Bump;
+use crate::Float;
 use crate::Light;
-use crate::{Color, Tuple};
+use crate::Pattern;
+use crate::Pbr;
+use crate::{Color, Point3, Vector3};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Material {
     pub color: Color,
-    pub ambient: f32,   // 0.0 to 1.0
-    pub diffuse: f32,   // 0.0 to 1.0
-    pub specular: f32,  // 0.0 to 1.0
-    pub shininess: f32, // usally between 10.0 (very large highlight) to 200.0 (very small highlight)
+    pub ambient: Float,           // 0.0 to 1.0
+    pub diffuse: Float,           // 0.0 to 1.0
+    pub specular: Float,          // 0.0 to 1.0
+    pub shininess: Float, // usally between 10.0 (very large highlight) to 200.0 (very small highlight)
+    pub emission: Color,  // Light given off by the surface, black when it does not glow
+    pub pbr: Option<Pbr>, // When set, shade with the metallic/roughness model instead of Phong (`diffuse`, `specular` and `shininess` are unused)
+    pub pattern: Option<Pattern>, // When set, gives the color of the surface at each point instead of `color`
+    pub bump: Option<Bump>,       // When set, tilts the normal used for shading
+    pub transparency: Float, // 0.0 (opaque) to 1.0, light goes straight through tinted by `color`
+    pub casts_shadow: bool,  // When false, the light goes through the object to the others
+    pub receives_shadow: bool, // When false, the surface is lit as if nothing was in the way
 }
 
 impl Material {
-    pub fn new(color: Color, ambient: f32, diffuse: f32, specular: f32, shininess: f32) -> Self {
+    pub fn new(
+        color: Color,
+        ambient: Float,
+        diffuse: Float,
+        specular: Float,
+        shininess: Float,
+    ) -> Self {
         Self {
             color,
             ambient,
             diffuse,
             specular,
             shininess,
+            emission: Color::black(),
+            pbr: None,
+            pattern: None,
+            bump: None,
+            transparency: 0.0,
+            casts_shadow: true,
+            receives_shadow: true,
         }
     }
 
@@ -25,11 +52,30 @@ impl Material {
     pub fn lighting(
         &self,
         light: Light,
-        hit_point: Tuple,
-        eyev: Tuple,
-        normalv: Tuple,
+        hit_point: Point3,
+        eyev: Vector3,
+        normalv: Vector3,
         in_shadow: bool, // whether the point is in the shadow
     ) -> Color {
+        let transmission = if in_shadow {
+            Color::black()
+        } else {
+            Color::white()
+        };
+        self.lighting_filtered(light, hit_point, eyev, normalv, transmission)
+    }
+
+    // Same as `lighting` when only `transmission` of the light reaches the point, like in the
+    // colored shadow of a transparent object. The ambient term stays the same
+    pub fn lighting_filtered(
+        &self,
+        light: Light,
+        hit_point: Point3,
+        eyev: Vector3,
+        normalv: Vector3,
+        transmission: Color,
+    ) -> Color {
+        let in_shadow = transmission.max_component() <= 0.0;
         let effective_color = self.color * light.intensity;
         let lightv = (light.position - hit_point).normalize();
         let ambient = effective_color * self.ambient;
@@ -37,6 +83,13 @@ impl Material {
         let mut specular = Color::black();
 
         let light_dot_normal = lightv.dot(&normalv);
+        if let Some(pbr) = self.pbr {
+            if light_dot_normal <= 0.0 || in_shadow {
+                return ambient;
+            }
+            let reflected = pbr.brdf(self.color, normalv, eyev, lightv);
+            return ambient + reflected * light.intensity * transmission * (PI * light_dot_normal);
+        }
         // Don't compute diffuse and specular when the point is on shadow
         if (light_dot_normal >= 0.0) && !in_shadow {
             diffuse = effective_color * self.diffuse * light_dot_normal;
@@ -48,7 +101,44 @@ impl Material {
                 specular = light.intensity * self.specular * factor;
             }
         }
-        ambient + diffuse + specular
+        ambient + (diffuse + specular) * transmission
+    }
+
+    // BRDF for light coming from `lightv` and leaving towards `eyev`, used by the path tracer
+    // A Phong material is treated as perfectly diffuse with albedo `color * diffuse`
+    // A point light of intensity I lights the surface with I * PI * cos, so that a white diffuse
+    // surface facing the light reflects I like in the Phong model
+    pub fn brdf(&self, normalv: Vector3, eyev: Vector3, lightv: Vector3) -> Color {
+        match self.pbr {
+            Some(pbr) => pbr.brdf(self.color, normalv, eyev, lightv),
+            None => self.color * (self.diffuse / PI),
+        }
+    }
+
+    // Probability density of `sample` returning `lightv`
+    pub fn pdf(&self, normalv: Vector3, eyev: Vector3, lightv: Vector3) -> Float {
+        match self.pbr {
+            Some(pbr) => pbr.pdf(normalv, eyev, lightv),
+            None => lightv.dot(&normalv).max(0.0) / PI,
+        }
+    }
+
+    // Random direction for the path to continue in and its weight brdf * cos / pdf
+    // `None` when the path is absorbed
+    pub fn sample(
+        &self,
+        normalv: Vector3,
+        eyev: Vector3,
+        rng: &mut Rng,
+    ) -> Option<(Vector3, Color)> {
+        match self.pbr {
+            Some(pbr) => pbr.sample(self.color, normalv, eyev, rng),
+            // With cosine weighted sampling, brdf * cos / pdf = (albedo / pi) * cos / (cos / pi) = albedo
+            None => Some((
+                cosine_sample_hemisphere(normalv, rng),
+                self.color * self.diffuse,
+            )),
+        }
     }
 }
 
@@ -61,8 +151,8 @@ impl Default for Material {
 #[cfg(test)]
 mod material_tests {
     use super::*;
+    use crate::consts::FRAC_1_SQRT_2;
     use crate::{point, vector};
-    use std::f32::consts::FRAC_1_SQRT_2;
 
     #[test]
     fn material_default() {
@@ -72,6 +162,25 @@ mod material_tests {
         assert_eq!(m.diffuse, 0.9);
         assert_eq!(m.specular, 0.9);
         assert_eq!(m.shininess, 200.0);
+        assert_eq!(m.emission, Color::black());
+        assert_eq!(m.pbr, None);
+        assert_eq!(m.pattern, None);
+        assert_eq!(m.bump, None);
+        assert_eq!(m.transparency, 0.0);
+        assert!(m.casts_shadow && m.receives_shadow);
+    }
+
+    #[test]
+    fn lighting_with_part_of_the_light() {
+        let m = Material::default();
+        let position = point(0.0, 0.0, 0.0);
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = Light::new(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let transmission = Color::new(0.5, 0.0, 1.0);
+        let result = m.lighting_filtered(light, position, eyev, normalv, transmission);
+        // Ambient 0.1, diffuse and specular 0.9 each
+        assert_eq!(result, Color::new(1.0, 0.1, 1.9));
     }
 
     #[test]
@@ -119,7 +228,7 @@ mod material_tests {
         let light = Light::new(point(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
         let in_shadow = false;
         let result = m.lighting(light, position, eyev, normalv, in_shadow);
-        assert_eq!(result, Color::new(1.6363853, 1.6363853, 1.6363853));
+        assert_eq!(result, Color::new(1.63639, 1.63639, 1.63639));
     }
 
     #[test]
@@ -145,4 +254,37 @@ mod material_tests {
         let result = m.lighting(light, hit_point, eyev, normalv, in_shadow);
         assert_eq!(result, Color::new(0.1, 0.1, 0.1));
     }
+
+    #[test]
+    fn lighting_pbr_material() {
+        let m = Material {
+            ambient: 0.0,
+            pbr: Some(Pbr::new(0.0, 1.0)),
+            ..Default::default()
+        };
+        let position = point(0.0, 0.0, 0.0);
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = Light::new(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let lit = m.lighting(light, position, eyev, normalv, false);
+        // Mostly diffuse white with a little specular, it can't reflect more than it gets
+        assert!(lit.red > 0.9 && lit.red < 1.0);
+        assert_eq!(lit, m.brdf(normalv, eyev, vector(0.0, 0.0, -1.0)) * PI);
+        assert_eq!(
+            m.lighting(light, position, eyev, normalv, true),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn phong_brdf_is_lambertian() {
+        let m = Material::default();
+        let normalv = vector(0.0, 1.0, 0.0);
+        let mut rng = Rng::new(1);
+        let brdf = m.brdf(normalv, normalv, vector(1.0, 0.0, 0.0));
+        assert_eq!(brdf * PI, Color::new(0.9, 0.9, 0.9));
+        let (lightv, weight) = m.sample(normalv, normalv, &mut rng).unwrap();
+        assert!(lightv.dot(&normalv) >= 0.0);
+        assert_eq!(weight, Color::new(0.9, 0.9, 0.9));
+    }
 }
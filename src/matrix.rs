@@ -1,12 +1,18 @@
+use crate::simd;
+use crate::utils::EPSILON;
 use crate::Compare;
+use crate::Float;
 use crate::Matrix3;
-use crate::Tuple;
+use crate::{vector, Tuple, Vector3};
 
 const MATRIX_SIZE: usize = 4;
 
+// Every row is aligned so that it can be loaded in a single SIMD register
 #[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(feature = "f64"), repr(C, align(16)))]
+#[cfg_attr(feature = "f64", repr(C, align(32)))]
 pub struct Matrix {
-    data: [[f32; MATRIX_SIZE]; MATRIX_SIZE], // TODO: create this into 1D array to increase performance
+    data: [[Float; MATRIX_SIZE]; MATRIX_SIZE],
 }
 
 impl Matrix {
@@ -27,7 +33,7 @@ impl Matrix {
 
     // Create a copy of input and transpose it
     pub fn transpose(&self) -> Self {
-        let mut result = self.clone(); // TODO: fix clippy lint
+        let mut result = *self;
         for i in 1..MATRIX_SIZE {
             for j in 0..i {
                 (result[i][j], result[j][i]) = (result[j][i], result[i][j]);
@@ -58,48 +64,184 @@ impl Matrix {
     }
 
     // Minor is determinant of a submatrix
-    pub fn minor(&self, row_ind: usize, col_ind: usize) -> f32 {
+    pub fn minor(&self, row_ind: usize, col_ind: usize) -> Float {
         self.submatrix(row_ind, col_ind).determinant()
     }
 
     // Cofactor is minor with possibly a sign depending on row and col index
-    pub fn cofactor(&self, row_ind: usize, col_ind: usize) -> f32 {
-        let sign = (-1_f32).powi((row_ind + col_ind) as i32);
+    pub fn cofactor(&self, row_ind: usize, col_ind: usize) -> Float {
+        let sign = (-1.0 as Float).powi((row_ind + col_ind) as i32);
         sign * self.minor(row_ind, col_ind)
     }
 
     // Find determinant using cofactors
-    pub fn determinant(&self) -> f32 {
-        let mut result: f32 = 0.0;
+    pub fn cofactor_determinant(&self) -> Float {
+        let mut result: Float = 0.0;
         for i in 0..MATRIX_SIZE {
             result += self.cofactor(0, i) * self[0][i];
         }
         result
     }
 
+    // Determinant in closed form, expanded over the 2x2 determinants of the top two rows (`s`)
+    // and of the bottom two rows (`c`) (Laplace expansion)
+    pub fn determinant(&self) -> Float {
+        let (s, c) = self.sub_determinants();
+        Self::laplace_expansion(&s, &c)
+    }
+
     pub fn is_invertible(&self) -> bool {
-        self.determinant() != 0.0
+        if self.is_affine() {
+            let (a, b, c) = self.linear_columns();
+            !is_singular(
+                a.dot(&b.cross(&c)),
+                a.magnitude() * b.magnitude() * c.magnitude(),
+            )
+        } else {
+            !is_singular(self.determinant(), self.hadamard_bound())
+        }
+    }
+
+    // Affine matrices (translation, scaling, rotation, shearing and their products) have
+    // 0, 0, 0, 1 as last row
+    pub fn is_affine(&self) -> bool {
+        self[3] == [0.0, 0.0, 0.0, 1.0]
     }
 
     // Calculate inverse of the matrix, panic if no inverse possible
+    // Affine matrices use the cheaper `inverse_affine`, others use the adjugate in closed form
     pub fn inverse(&self) -> Self {
-        let determinant = self.determinant();
-        if !self.is_invertible() {
+        if self.is_affine() {
+            return self.inverse_affine();
+        }
+        let (s, c) = self.sub_determinants();
+        let determinant = Self::laplace_expansion(&s, &c);
+        if is_singular(determinant, self.hadamard_bound()) {
             panic!("Matrix is not invertible");
         }
+
+        // Each entry is a cofactor built from the 2x2 determinants, already transposed
+        let m = &self.data;
+        let adjugate = [
+            [
+                m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3],
+                -m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3],
+                m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3],
+                -m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3],
+            ],
+            [
+                -m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1],
+                m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1],
+                -m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1],
+                m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1],
+            ],
+            [
+                m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0],
+                -m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0],
+                m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0],
+                -m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0],
+            ],
+            [
+                -m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0],
+                m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0],
+                -m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0],
+                m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0],
+            ],
+        ];
+
         let mut result: Matrix = Default::default();
         for i in 0..MATRIX_SIZE {
             for j in 0..MATRIX_SIZE {
-                result[j][i] = self.cofactor(i, j) / determinant; // Also doing the transpose
+                result[i][j] = adjugate[i][j] / determinant;
             }
         }
         result
     }
+
+    // Inverse of an affine matrix M = [A t; 0 1] is [A^-1 -A^-1*t; 0 1]
+    // Rows of A^-1 are the cross products of the columns (a, b, c) of A: b x c, c x a and a x b
+    // divided by det(A) = a . (b x c)
+    // Panics if the matrix is not affine or not invertible
+    pub fn inverse_affine(&self) -> Self {
+        assert!(self.is_affine(), "Matrix is not affine");
+        let (a, b, c) = self.linear_columns();
+        let (bc, ca, ab) = (b.cross(&c), c.cross(&a), a.cross(&b));
+        let determinant = a.dot(&bc);
+        if is_singular(determinant, a.magnitude() * b.magnitude() * c.magnitude()) {
+            panic!("Matrix is not invertible");
+        }
+
+        let mut result = Matrix::I();
+        for (i, row) in [bc, ca, ab].iter().enumerate() {
+            result[i][0] = row.x / determinant;
+            result[i][1] = row.y / determinant;
+            result[i][2] = row.z / determinant;
+        }
+        let translation = result * vector(self[0][3], self[1][3], self[2][3]);
+        result[0][3] = -translation.x;
+        result[1][3] = -translation.y;
+        result[2][3] = -translation.z;
+        result
+    }
+
+    // 2x2 determinants of the top two rows (`s`) and of the bottom two rows (`c`)
+    // for every pair of columns, shared by `determinant` and `inverse`
+    fn sub_determinants(&self) -> ([Float; 6], [Float; 6]) {
+        let m = &self.data;
+        let pair = |r1: usize, r2: usize, c1: usize, c2: usize| {
+            m[r1][c1] * m[r2][c2] - m[r2][c1] * m[r1][c2]
+        };
+        let s = [
+            pair(0, 1, 0, 1),
+            pair(0, 1, 0, 2),
+            pair(0, 1, 0, 3),
+            pair(0, 1, 1, 2),
+            pair(0, 1, 1, 3),
+            pair(0, 1, 2, 3),
+        ];
+        let c = [
+            pair(2, 3, 0, 1),
+            pair(2, 3, 0, 2),
+            pair(2, 3, 0, 3),
+            pair(2, 3, 1, 2),
+            pair(2, 3, 1, 3),
+            pair(2, 3, 2, 3),
+        ];
+        (s, c)
+    }
+
+    fn laplace_expansion(s: &[Float; 6], c: &[Float; 6]) -> Float {
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // Columns of the top left 3x3 block, the linear part of an affine matrix
+    fn linear_columns(&self) -> (Vector3, Vector3, Vector3) {
+        let column = |j: usize| vector(self[0][j], self[1][j], self[2][j]);
+        (column(0), column(1), column(2))
+    }
+
+    // Largest absolute determinant a matrix with the same row lengths, or column lengths, can
+    // have (Hadamard's inequality)
+    fn hadamard_bound(&self) -> Float {
+        let length =
+            |values: [Float; MATRIX_SIZE]| values.iter().map(|v| v * v).sum::<Float>().sqrt();
+        let rows: Float = self.data.iter().map(|row| length(*row)).product();
+        let columns: Float = (0..MATRIX_SIZE)
+            .map(|j| length([self[0][j], self[1][j], self[2][j], self[3][j]]))
+            .product();
+        rows.min(columns)
+    }
+}
+
+// Rounding errors keep the determinant of a singular matrix from being exactly 0, so it is compared
+// to the largest determinant possible for the size of the matrix entries (`bound`) instead
+fn is_singular(determinant: Float, bound: Float) -> bool {
+    !determinant.is_finite() || determinant.abs() <= EPSILON * bound
 }
 
 // Use this to get a row of matrix when indexing Matrix
 impl std::ops::Index<usize> for Matrix {
-    type Output = [f32; MATRIX_SIZE];
+    type Output = [Float; MATRIX_SIZE];
 
     fn index(&self, row: usize) -> &Self::Output {
         &self.data[row]
@@ -130,15 +272,7 @@ impl std::ops::Mul<Matrix> for Matrix {
     type Output = Self;
 
     fn mul(self, other: Self) -> Self {
-        let mut result: Matrix = Default::default();
-        for i in 0..MATRIX_SIZE {
-            for j in 0..MATRIX_SIZE {
-                for k in 0..MATRIX_SIZE {
-                    result[i][j] += self[i][k] * other[k][j];
-                }
-            }
-        }
-        result
+        simd::mul_matrix_matrix(&self, &other)
     }
 }
 
@@ -147,24 +281,7 @@ impl std::ops::Mul<Tuple> for Matrix {
     type Output = Tuple;
 
     fn mul(self, other: Tuple) -> Self::Output {
-        Tuple::new(
-            self[0][0] * other.x
-                + self[0][1] * other.y
-                + self[0][2] * other.z
-                + self[0][3] * other.w,
-            self[1][0] * other.x
-                + self[1][1] * other.y
-                + self[1][2] * other.z
-                + self[1][3] * other.w,
-            self[2][0] * other.x
-                + self[2][1] * other.y
-                + self[2][2] * other.z
-                + self[2][3] * other.w,
-            self[3][0] * other.x
-                + self[3][1] * other.y
-                + self[3][2] * other.z
-                + self[3][3] * other.w,
-        )
+        simd::mul_matrix_tuple(&self, &other)
     }
 }
 
@@ -797,4 +914,71 @@ mod matrix_tests {
         let c = a * b;
         assert_eq!(c * b.inverse(), a);
     }
+
+    #[test]
+    fn closed_form_determinant_matches_cofactors() {
+        let mut a: Matrix = Default::default();
+        let values = [
+            -2.0, -8.0, 3.0, 5.0, -3.0, 1.0, 7.0, 3.0, 1.0, 2.0, -9.0, 6.0, -6.0, 7.0, 7.0, -9.0,
+        ];
+        for (ind, value) in values.iter().enumerate() {
+            a[ind / 4][ind % 4] = *value;
+        }
+        assert_eq!(a.determinant(), a.cofactor_determinant());
+        assert_eq!(a.determinant(), -4071.0);
+    }
+
+    #[test]
+    fn affine_inverse() {
+        let a = Matrix::get_translation_matrix(1.0, -2.0, 3.0)
+            * Matrix::get_rotation_x_matrix(0.7)
+            * Matrix::get_shearing_matrix(0.5, 0.0, 0.0, 1.0, 0.0, 0.0)
+            * Matrix::get_scaling_matrix(2.0, 0.5, 4.0);
+        assert!(a.is_affine());
+        assert_eq!(a.inverse_affine() * a, Matrix::I());
+        assert_eq!(a * a.inverse_affine(), Matrix::I());
+    }
+
+    #[test]
+    fn non_affine_matrix() {
+        let mut a = Matrix::I();
+        a[3][2] = 1.0;
+        assert!(!a.is_affine());
+        assert_eq!(a * a.inverse(), Matrix::I());
+    }
+
+    #[test]
+    fn nearly_singular_matrices() {
+        // The third row is a combination of the first two, up to rounding errors
+        let mut a = Matrix::I();
+        a[0] = [0.3, 0.7, 0.1, 2.0];
+        a[1] = [0.9, 0.2, 0.6, 0.4];
+        a[2] = [
+            0.3 * 0.1 + 0.9 * 0.7,
+            0.7 * 0.1 + 0.2 * 0.7,
+            0.1 * 0.1 + 0.6 * 0.7,
+            2.0 * 0.1 + 0.4 * 0.7,
+        ];
+        a[3] = [0.1, 0.7, 0.0, 1.0];
+        assert!(!a.is_invertible());
+        a[3] = [0.0, 0.0, 0.0, 1.0];
+        assert!(!a.is_invertible());
+
+        // Tiny but well shaped matrices stay invertible
+        let b = Matrix::get_scaling_matrix(1e-6, 1e-6, 1e-6);
+        assert!(b.is_invertible());
+        assert_eq!(b * b.inverse(), Matrix::I());
+
+        let mut c = Matrix::I();
+        c[1][1] = Float::NAN;
+        assert!(!c.is_invertible());
+        c[3][2] = 1.0;
+        assert!(!c.is_invertible());
+    }
+
+    #[test]
+    #[should_panic(expected = "Matrix is not invertible")]
+    fn inverse_of_singular_affine_matrix() {
+        Matrix::get_scaling_matrix(1.0, 0.0, 1.0).inverse();
+    }
 }
@@ -1,13 +1,14 @@
 use crate::Compare;
+use crate::Float;
 
 #[derive(Debug, Default)]
 pub struct Matrix2 {
-    pub data: [[f32; 2]; 2],
+    pub data: [[Float; 2]; 2],
 }
 
 #[derive(Debug, Default)]
 pub struct Matrix3 {
-    pub data: [[f32; 3]; 3],
+    pub data: [[Float; 3]; 3],
 }
 
 impl Matrix2 {
@@ -16,7 +17,7 @@ impl Matrix2 {
         Default::default()
     }
 
-    pub fn determinant(&self) -> f32 {
+    pub fn determinant(&self) -> Float {
         self[0][0] * self[1][1] - self[0][1] * self[1][0]
     }
 }
@@ -49,19 +50,19 @@ impl Matrix3 {
     }
 
     // Minor is determinant of a submatrix
-    pub fn minor(&self, row_ind: usize, col_ind: usize) -> f32 {
+    pub fn minor(&self, row_ind: usize, col_ind: usize) -> Float {
         self.submatrix(row_ind, col_ind).determinant()
     }
 
     // Cofactor is minor with possibly a sign depending on row and col index
-    pub fn cofactor(&self, row_ind: usize, col_ind: usize) -> f32 {
-        let sign = (-1_f32).powi((row_ind + col_ind) as i32);
+    pub fn cofactor(&self, row_ind: usize, col_ind: usize) -> Float {
+        let sign = (-1.0 as Float).powi((row_ind + col_ind) as i32);
         sign * self.minor(row_ind, col_ind)
     }
 
     // Find determinant using cofactors
-    pub fn determinant(&self) -> f32 {
-        let mut result: f32 = 0.0;
+    pub fn determinant(&self) -> Float {
+        let mut result: Float = 0.0;
         for i in 0..3 {
             result += self.cofactor(0, i) * self[0][i];
         }
@@ -71,7 +72,7 @@ impl Matrix3 {
 
 // Use this to get a row of matrix when indexing Matrix
 impl std::ops::Index<usize> for Matrix2 {
-    type Output = [f32; 2];
+    type Output = [Float; 2];
 
     fn index(&self, row: usize) -> &Self::Output {
         &self.data[row]
@@ -85,7 +86,7 @@ impl std::ops::IndexMut<usize> for Matrix2 {
 }
 
 impl std::ops::Index<usize> for Matrix3 {
-    type Output = [f32; 3];
+    type Output = [Float; 3];
 
     fn index(&self, row: usize) -> &Self::Output {
         &self.data[row]
@@ -104,7 +105,7 @@ impl PartialEq for Matrix2 {
         for row in 0..2 {
             for col in 0..2 {
                 if self.data[row][col].neq(other[row][col]) {
-                    false;
+                    return false;
                 }
             }
         }
@@ -0,0 +1,136 @@
+use crate::Color;
+use crate::Float;
+use crate::Sphere;
+use crate::{point, Point3};
+use crate::{Matrix, Ray};
+
+// Participating medium- fog, smoke or dust which light travels through
+// Coefficients are per unit of distance, and per color channel so the medium can be tinted
+// Light is absorbed with `absorption` and bounced in a random direction with `scattering`,
+// the light scattered towards the eye is what makes the medium visible (and gives god rays)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Medium {
+    pub absorption: Color,
+    pub scattering: Color,
+}
+
+// Medium filling the whole world
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fog {
+    // Same density everywhere
+    Homogeneous(Medium),
+    // Thick close to the ground, like mist in a valley- `medium` as it is at y = `height`,
+    // getting e times thinner every 1 / `falloff` units higher (and thicker lower)
+    Height {
+        medium: Medium,
+        height: Float,
+        falloff: Float,
+    },
+}
+
+// Medium filling the inside of a sphere, the sphere itself is invisible
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Volume {
+    pub bounds: Sphere,
+    pub medium: Medium,
+}
+
+impl Medium {
+    pub fn new(absorption: Color, scattering: Color) -> Self {
+        Self {
+            absorption,
+            scattering,
+        }
+    }
+
+    // Total loss of light along the way, absorbed or scattered away
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+}
+
+// Fraction of the light left after going through the given optical depth (extinction * distance)
+pub(crate) fn transmittance(optical_depth: Color) -> Color {
+    Color::new(
+        (-optical_depth.red).exp(),
+        (-optical_depth.green).exp(),
+        (-optical_depth.blue).exp(),
+    )
+}
+
+impl Fog {
+    pub fn medium(&self) -> Medium {
+        match *self {
+            Fog::Homogeneous(medium) => medium,
+            Fog::Height { medium, .. } => medium,
+        }
+    }
+
+    // How much of the medium there is at `p`, 1.0 is the medium as it is
+    pub fn density(&self, p: Point3) -> Float {
+        match *self {
+            Fog::Homogeneous(_) => 1.0,
+            Fog::Height {
+                height, falloff, ..
+            } => (-falloff * (p.y - height)).exp(),
+        }
+    }
+}
+
+impl Volume {
+    // Volume inside the unit sphere transformed by `transform`
+    pub fn new(transform: Matrix, medium: Medium) -> Self {
+        let mut bounds = Sphere::default();
+        bounds.set_transform(transform);
+        Self { bounds, medium }
+    }
+
+    // Distances along `ray` where it enters and leaves the volume, `None` when it misses
+    // The entry is negative when the ray starts inside
+    pub fn span(&self, ray: Ray) -> Option<(Float, Float)> {
+        let xs = self.bounds.intersect(ray);
+        if xs.len() < 2 {
+            return None;
+        }
+        Some((xs[0].t, xs[1].t))
+    }
+
+    pub fn contains(&self, p: Point3) -> bool {
+        let v = self.bounds.world_to_object(p) - point(0.0, 0.0, 0.0);
+        v.dot(&v) <= 1.0
+    }
+}
+
+#[cfg(test)]
+mod medium_tests {
+    use super::*;
+    use crate::{vector, Compare};
+
+    #[test]
+    fn height_fog_thins_out_upwards() {
+        let medium = Medium::new(Color::black(), Color::white());
+        let fog = Fog::Height {
+            medium,
+            height: 1.0,
+            falloff: 2.0,
+        };
+        assert!(fog.density(point(5.0, 1.0, 3.0)).eq(1.0));
+        assert!(fog.density(point(0.0, 1.5, 0.0)).eq((-1.0 as Float).exp()));
+        assert!(fog.density(point(0.0, 0.0, 0.0)) > 1.0);
+        assert!(Fog::Homogeneous(medium)
+            .density(point(0.0, 100.0, 0.0))
+            .eq(1.0));
+    }
+
+    #[test]
+    fn volume_span_and_inside() {
+        let medium = Medium::new(Color::white(), Color::black());
+        let volume = Volume::new(Matrix::get_scaling_matrix(2.0, 2.0, 2.0), medium);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(volume.span(r), Some((3.0, 7.0)));
+        let r = Ray::new(point(0.0, 5.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(volume.span(r), None);
+        assert!(volume.contains(point(1.5, 0.0, 0.0)));
+        assert!(!volume.contains(point(1.5, 1.5, 0.0)));
+    }
+}
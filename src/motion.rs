@@ -0,0 +1,113 @@
+use crate::Float;
+use crate::Matrix;
+use crate::Quaternion;
+use crate::{vector, Vector3};
+
+// Placement of a moving object at one instant- scaled, then rotated, then translated
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: Float,
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+}
+
+// Movement of an object while the shutter is open, between two keyframes
+// In between, translation and scale are interpolated linearly and rotation with slerp, so a
+// spinning object keeps its shape (interpolating the matrices would squash it)
+// Before `start` and after `end` the object stays where the keyframe puts it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Motion {
+    pub start: Keyframe,
+    pub end: Keyframe,
+}
+
+impl Keyframe {
+    pub fn new(time: Float, translation: Vector3, rotation: Quaternion, scale: Vector3) -> Self {
+        Self {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    // Object where it was placed, at `time`
+    pub fn identity(time: Float) -> Self {
+        Self::new(
+            time,
+            vector(0.0, 0.0, 0.0),
+            Quaternion::identity(),
+            vector(1.0, 1.0, 1.0),
+        )
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        let (t, s) = (self.translation, self.scale);
+        Matrix::get_translation_matrix(t.x, t.y, t.z)
+            * self.rotation.to_matrix()
+            * Matrix::get_scaling_matrix(s.x, s.y, s.z)
+    }
+}
+
+impl Motion {
+    pub fn new(start: Keyframe, end: Keyframe) -> Self {
+        Self { start, end }
+    }
+
+    // Object translated by `offset` between `start` and `end`, at constant speed
+    pub fn linear(start: Float, end: Float, offset: Vector3) -> Self {
+        let mut last = Keyframe::identity(end);
+        last.translation = offset;
+        Self::new(Keyframe::identity(start), last)
+    }
+
+    // Transformation at `time`, applied on top of the transformation of the object
+    pub fn transform_at(&self, time: Float) -> Matrix {
+        let span = self.end.time - self.start.time;
+        let t = if span > 0.0 {
+            ((time - self.start.time) / span).clamp(0.0, 1.0)
+        } else if time < self.end.time {
+            0.0
+        } else {
+            1.0
+        };
+        let (a, b) = (self.start, self.end);
+        Keyframe::new(
+            time,
+            a.translation + (b.translation - a.translation) * t,
+            a.rotation.slerp(&b.rotation, t),
+            a.scale + (b.scale - a.scale) * t,
+        )
+        .to_matrix()
+    }
+}
+
+#[cfg(test)]
+mod motion_tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::point;
+
+    #[test]
+    fn linear_motion_is_interpolated_and_clamped() {
+        let motion = Motion::linear(0.0, 1.0, vector(2.0, 0.0, 0.0));
+        let p = point(1.0, 1.0, 1.0);
+        assert_eq!(motion.transform_at(0.0) * p, p);
+        assert_eq!(motion.transform_at(0.25) * p, point(1.5, 1.0, 1.0));
+        assert_eq!(motion.transform_at(1.0) * p, point(3.0, 1.0, 1.0));
+        assert_eq!(motion.transform_at(-1.0) * p, p);
+        assert_eq!(motion.transform_at(2.0) * p, point(3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn rotation_is_slerped() {
+        let mut end = Keyframe::identity(2.0);
+        end.rotation = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), PI / 2.0);
+        let motion = Motion::new(Keyframe::identity(0.0), end);
+        // Halfway the point is rotated by 45 degrees and still at distance 1
+        let p = motion.transform_at(1.0) * point(0.0, 0.0, 1.0);
+        let half = (PI / 4.0).sin();
+        assert_eq!(p, point(half, 0.0, half));
+    }
+}
@@ -0,0 +1,241 @@
+use crate::utils::splitmix64;
+use crate::Float;
+use crate::{point, Point3};
+
+// Octaves of fBm are spaced by this factor in frequency
+const LACUNARITY: Float = 2.0;
+// and by this factor in amplitude
+const GAIN: Float = 0.5;
+
+// Seeded gradient noise- smooth random values which vary at the scale of 1 unit
+// The same seed always gives the same noise, on every platform and number of threads
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Ken Perlin's improved noise, between about -1 and 1 and 0 at every integer point
+    // Every corner of the unit cell around `p` gets a random gradient, their ramps are blended
+    // with a smooth curve so the noise has no visible grid
+    pub fn perlin(&self, p: Point3) -> Float {
+        let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (x, y, z) = (p.x - x0, p.y - y0, p.z - z0);
+        let (i, j, k) = (x0 as i64, y0 as i64, z0 as i64);
+        let corner = |di: i64, dj: i64, dk: i64| {
+            let hash = self.hash(i + di, j + dj, k + dk);
+            gradient(hash, x - di as Float, y - dj as Float, z - dk as Float)
+        };
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    // Simplex noise (Perlin 2001, following Gustavson's "Simplex noise demystified"), between
+    // about -1 and 1. Sums the gradients of the 4 corners of a tetrahedron instead of the 8 of a
+    // cube, so it is cheaper and looks the same in every direction
+    pub fn simplex(&self, p: Point3) -> Float {
+        const SKEW: Float = 1.0 / 3.0;
+        const UNSKEW: Float = 1.0 / 6.0;
+        // Skew space so the tetrahedrons become cubes, find the cube and the position in it
+        let s = (p.x + p.y + p.z) * SKEW;
+        let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+        let t = (i + j + k) * UNSKEW;
+        let (x, y, z) = (p.x - (i - t), p.y - (j - t), p.z - (k - t));
+
+        // The order of the coordinates tells which of the 6 tetrahedrons of the cube `p` is in
+        let (second, third) = if x >= y {
+            if y >= z {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x >= z {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y < z {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x < z {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        let mut total = 0.0;
+        for (n, (di, dj, dk)) in [(0, 0, 0), second, third, (1, 1, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            let offset = n as Float * UNSKEW;
+            let (cx, cy, cz) = (
+                x - di as Float + offset,
+                y - dj as Float + offset,
+                z - dk as Float + offset,
+            );
+            let falloff = 0.6 - cx * cx - cy * cy - cz * cz;
+            if falloff > 0.0 {
+                let hash = self.hash(i + di, j + dj, k + dk);
+                total += falloff.powi(4) * gradient(hash, cx, cy, cz);
+            }
+        }
+        32.0 * total
+    }
+
+    // Fractal Brownian motion- `octaves` layers of Perlin noise, each one twice as detailed and
+    // half as strong as the previous one. Between about -1 and 1
+    pub fn fbm(&self, p: Point3, octaves: u32) -> Float {
+        self.fractal(p, octaves, |value| value)
+    }
+
+    // Like `fbm` but adding up the absolute values, which gives sharp creases where the noise
+    // crosses 0. Between 0 and about 1
+    pub fn turbulence(&self, p: Point3, octaves: u32) -> Float {
+        self.fractal(p, octaves, Float::abs)
+    }
+
+    fn fractal(&self, p: Point3, octaves: u32, shape: impl Fn(Float) -> Float) -> Float {
+        let (mut total, mut amplitude, mut frequency, mut max) = (0.0, 1.0, 1.0, 0.0);
+        for _ in 0..octaves {
+            let q = point(p.x * frequency, p.y * frequency, p.z * frequency);
+            total += amplitude * shape(self.perlin(q));
+            max += amplitude;
+            amplitude *= GAIN;
+            frequency *= LACUNARITY;
+        }
+        if max > 0.0 {
+            total / max
+        } else {
+            0.0
+        }
+    }
+
+    // Random bits of the lattice point (i, j, k) for this seed
+    fn hash(&self, i: i64, j: i64, k: i64) -> u64 {
+        let h = splitmix64(self.seed ^ i as u64);
+        let h = splitmix64(h ^ j as u64);
+        splitmix64(h ^ k as u64)
+    }
+}
+
+// Dot product of (x, y, z) with one of the 12 vectors from the center to the edges of a cube,
+// picked by the last 4 bits of `hash` (the 4 extra values repeat some of them)
+fn gradient(hash: u64, x: Float, y: Float, z: Float) -> Float {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+// 6t^5 - 15t^4 + 10t^3, goes from 0 to 1 with zero first and second derivatives at both ends
+fn fade(t: Float) -> Float {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    a + t * (b - a)
+}
+
+#[cfg(test)]
+mod noise_tests {
+    use super::*;
+    use crate::Compare;
+
+    // Points spread over a few cells, away from the lattice
+    fn samples() -> impl Iterator<Item = Point3> {
+        (0..2000).map(|i| {
+            let i = i as Float;
+            point(i * 0.0731 - 20.0, i * 0.0197 + 3.3, i * -0.0413 + 0.7)
+        })
+    }
+
+    #[test]
+    fn perlin_is_zero_on_the_lattice() {
+        let noise = Noise::new(3);
+        for p in [
+            point(0.0, 0.0, 0.0),
+            point(1.0, -2.0, 5.0),
+            point(-7.0, 3.0, 2.0),
+        ] {
+            assert!(noise.perlin(p).eq(0.0));
+        }
+    }
+
+    #[test]
+    fn noise_is_in_range_and_not_flat() {
+        let noise = Noise::new(11);
+        let (mut min, mut max): (Float, Float) = (0.0, 0.0);
+        for p in samples() {
+            for value in [noise.perlin(p), noise.simplex(p), noise.fbm(p, 5)] {
+                assert!((-1.05..=1.05).contains(&value), "{} at {:?}", value, p);
+                min = min.min(value);
+                max = max.max(value);
+            }
+            let turbulence = noise.turbulence(p, 5);
+            assert!((0.0..=1.05).contains(&turbulence));
+        }
+        assert!(min < -0.4 && max > 0.4);
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let noise = Noise::new(5);
+        let step = 1e-3;
+        for p in samples().take(200) {
+            let q = point(p.x + step, p.y, p.z);
+            assert!((noise.perlin(p) - noise.perlin(q)).abs() < 0.01);
+            assert!((noise.simplex(p) - noise.simplex(q)).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn noise_is_deterministic_and_seeded() {
+        let p = point(0.3, 1.7, -2.4);
+        let (a, b) = (Noise::new(1), Noise::new(2));
+        assert_eq!(a.perlin(p), Noise::new(1).perlin(p));
+        assert_eq!(a.fbm(p, 4), Noise::new(1).fbm(p, 4));
+        assert!(a.perlin(p).neq(b.perlin(p)));
+        assert!(a.simplex(p).neq(b.simplex(p)));
+    }
+
+    #[test]
+    fn noise_snapshot() {
+        // Values of the noise must not change between versions, scenes would render differently
+        let noise = Noise::new(42);
+        let p = point(0.3, 1.7, -2.4);
+        let snapshot = [
+            noise.perlin(p),
+            noise.simplex(p),
+            noise.fbm(p, 4),
+            noise.turbulence(p, 4),
+        ];
+        let expected = [-0.0905882, -0.0448088, -0.0607810, 0.1318976];
+        for (value, expected) in snapshot.into_iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-4, "{:?}", snapshot);
+        }
+    }
+}
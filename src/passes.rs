@@ -0,0 +1,123 @@
+use crate::Canvas;
+use crate::Color;
+use crate::Float;
+
+// Image of one number per pixel, like the depth or the ids of a render
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<Float>,
+}
+
+// Extra images (AOVs, arbitrary output values) rendered alongside the beauty image, for
+// compositing, denoising and debugging. All but `beauty` and `samples` are taken from the ray
+// through the center of each pixel
+#[derive(Clone)]
+pub struct RenderPasses {
+    pub beauty: Canvas, // The image `Camera::render` gives
+    // Distance from the camera to the hit, infinity where the ray misses everything
+    pub depth: FloatBuffer,
+    // World space shading normal at the hit (facing the camera), red, green and blue are x, y and z
+    // between -1 and 1. Black where the ray misses everything
+    pub normal: Canvas,
+    // Color of the surface at the hit without any lighting, the background where the ray misses
+    pub albedo: Canvas,
+    // Id of the hit object (see `World::add_object`), -1 where the ray misses everything
+    pub object_id: FloatBuffer,
+    // Materials are numbered in the order they first appear in `World::objects`, -1 for misses
+    pub material_id: FloatBuffer,
+    // Fraction of the light of the point light blocked at the hit, averaged over red, green and
+    // blue. 1.0 in a full shadow, 0.0 where it is lit or missed
+    pub shadow: FloatBuffer,
+    // Number of samples averaged in the beauty image, shows where adaptive sampling spent them
+    pub samples: FloatBuffer,
+}
+
+// Passes of a single pixel
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PixelPasses {
+    pub beauty: Color,
+    pub depth: Float,
+    pub normal: Color,
+    pub albedo: Color,
+    pub object_id: Float,
+    pub material_id: Float,
+    pub shadow: Float,
+    pub samples: Float,
+}
+
+impl FloatBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0.0; width * height],
+        }
+    }
+
+    pub fn write_value(&mut self, col: usize, row: usize, value: Float) {
+        self.data[row * self.width + col] = value;
+    }
+
+    pub fn value_at(&self, col: usize, row: usize) -> Float {
+        self.data[row * self.width + col]
+    }
+
+    // Gray image to look at the buffer, `low` is black and `high` white
+    // Values which are not finite (like the depth of a miss) are black too
+    pub fn to_canvas(&self, low: Float, high: Float) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for (pixel, &value) in canvas.data.iter_mut().zip(&self.data) {
+            if value.is_finite() {
+                let gray = ((value - low) / (high - low)).clamp(0.0, 1.0);
+                *pixel = Color::new(gray, gray, gray);
+            }
+        }
+        canvas
+    }
+}
+
+impl RenderPasses {
+    // Gather the passes of every pixel of a `width` x `height` image, in rows
+    pub(crate) fn from_pixels(width: usize, height: usize, pixels: &[PixelPasses]) -> Self {
+        let canvas = |pass: fn(&PixelPasses) -> Color| Canvas {
+            width,
+            height,
+            data: pixels.iter().map(pass).collect(),
+        };
+        let buffer = |pass: fn(&PixelPasses) -> Float| FloatBuffer {
+            width,
+            height,
+            data: pixels.iter().map(pass).collect(),
+        };
+        Self {
+            beauty: canvas(|pixel| pixel.beauty),
+            depth: buffer(|pixel| pixel.depth),
+            normal: canvas(|pixel| pixel.normal),
+            albedo: canvas(|pixel| pixel.albedo),
+            object_id: buffer(|pixel| pixel.object_id),
+            material_id: buffer(|pixel| pixel.material_id),
+            shadow: buffer(|pixel| pixel.shadow),
+            samples: buffer(|pixel| pixel.samples),
+        }
+    }
+}
+
+#[cfg(test)]
+mod passes_tests {
+    use super::*;
+
+    #[test]
+    fn float_buffer_to_canvas() {
+        let mut buffer = FloatBuffer::new(3, 1);
+        buffer.write_value(0, 0, 2.0);
+        buffer.write_value(1, 0, 4.0);
+        buffer.write_value(2, 0, Float::INFINITY);
+        assert_eq!(buffer.value_at(1, 0), 4.0);
+        let canvas = buffer.to_canvas(2.0, 6.0);
+        assert_eq!(canvas.pixel_at(0, 0), Color::black());
+        assert_eq!(canvas.pixel_at(1, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(canvas.pixel_at(2, 0), Color::black());
+    }
+}
@@ -0,0 +1,471 @@
+use crate::consts::PI;
+use crate::Canvas;
+use crate::Color;
+use crate::Float;
+use crate::Noise;
+use crate::{point, vector, Matrix, Point3, Vector3};
+
+// Octaves of noise of the procedural patterns
+const OCTAVES: u32 = 6;
+// Veins of marble per unit along x, and how far the turbulence pushes them
+const MARBLE_VEINS: Float = 1.5;
+const MARBLE_TURBULENCE: Float = 3.0;
+// Growth rings of wood per unit, and how much noise bends them
+const WOOD_RINGS: Float = 8.0;
+const WOOD_GRAIN: Float = 0.1;
+// Offsets of the noise for the y and z displacements of the jitter, so the 3 are unrelated
+const JITTER_OFFSET_Y: Float = 31.7;
+const JITTER_OFFSET_Z: Float = -57.3;
+
+// Color varying over the surface of an object, replaces the `color` of its material
+// The pattern has its own transformation, applied on top of the transformation of the object
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    transform: Matrix, // Transformation matrix, from pattern space to object space
+    inverse_transform: Matrix, // Cached inverse of `transform`
+    // Noise displacing the points before the pattern is looked up, and its strength
+    jitter: Option<(Noise, Float)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PatternKind {
+    // Image wrapped around the object, `id` is the index of the image in `World::textures`
+    Texture {
+        id: usize,
+        mapping: UvMapping,
+        wrap: WrapMode,
+    },
+    // Veins of `b` through `a`, stripes along x bent by turbulence
+    Marble {
+        a: Color,
+        b: Color,
+        noise: Noise,
+    },
+    // Rings of `a` and `b` around the y axis, distorted by noise
+    Wood {
+        a: Color,
+        b: Color,
+        noise: Noise,
+    },
+    // Blend of `a` (thin) and `b` (thick) following fBm noise
+    Clouds {
+        a: Color,
+        b: Color,
+        noise: Noise,
+    },
+}
+
+// How a point of the object is turned into texture coordinates (u, v)
+// u goes left to right and v bottom to top of the image, both from 0 to 1
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum UvMapping {
+    // Longitude and latitude around the y axis, like a map of the earth
+    #[default]
+    Spherical,
+    // The x and z coordinates, the image repeats every unit along both axes
+    Planar,
+    // Angle around the y axis and height, the image repeats every unit along the axis
+    Cylindrical,
+    // Projection on the faces of the cube from -1 to 1, the image is the unfolded cube-
+    //      up
+    // left front right back
+    //      down
+    // Every face is a square of a 4 by 3 grid
+    Cube,
+}
+
+// What is seen outside of the image, when u or v are not between 0 and 1
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat, // The image is tiled
+    Clamp,  // The border pixels are stretched
+    Mirror, // The image is tiled, every other copy flipped
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind) -> Self {
+        Self {
+            kind,
+            transform: Matrix::I(),
+            inverse_transform: Matrix::I(),
+            jitter: None,
+        }
+    }
+
+    // Image texture, wrapped around the object following `mapping`
+    pub fn texture(id: usize, mapping: UvMapping, wrap: WrapMode) -> Self {
+        Self::new(PatternKind::Texture { id, mapping, wrap })
+    }
+
+    pub fn marble(a: Color, b: Color, seed: u64) -> Self {
+        Self::new(PatternKind::Marble {
+            a,
+            b,
+            noise: Noise::new(seed),
+        })
+    }
+
+    pub fn wood(a: Color, b: Color, seed: u64) -> Self {
+        Self::new(PatternKind::Wood {
+            a,
+            b,
+            noise: Noise::new(seed),
+        })
+    }
+
+    pub fn clouds(a: Color, b: Color, seed: u64) -> Self {
+        Self::new(PatternKind::Clouds {
+            a,
+            b,
+            noise: Noise::new(seed),
+        })
+    }
+
+    // Displace the points by up to about `amount` with noise before looking up the pattern,
+    // which makes straight lines wavy and breaks the regularity of any pattern. 0 turns it off
+    pub fn set_jitter(&mut self, amount: Float, seed: u64) {
+        self.jitter = if amount != 0.0 {
+            Some((Noise::new(seed), amount))
+        } else {
+            None
+        };
+    }
+
+    pub fn set_transform(&mut self, t: Matrix) {
+        self.transform = t;
+        self.inverse_transform = t.inverse();
+    }
+
+    pub fn transform(&self) -> Matrix {
+        self.transform
+    }
+
+    // Direction in object space along which u of the image grows at `object_point`
+    // Procedural patterns have no u, they follow the spherical mapping
+    pub fn tangent_at(&self, object_point: Point3) -> Vector3 {
+        let mapping = match self.kind {
+            PatternKind::Texture { mapping, .. } => mapping,
+            _ => UvMapping::Spherical,
+        };
+        self.transform * mapping.tangent(self.inverse_transform * object_point)
+    }
+
+    // Color of the pattern at `object_point`, a point in the object space of the shape
+    // Images are looked up in `textures`, a missing image is black
+    pub fn color_at(&self, object_point: Point3, textures: &[Canvas]) -> Color {
+        let mut p = self.inverse_transform * object_point;
+        if let Some((noise, amount)) = self.jitter {
+            let offset = |dy: Float, dz: Float| point(p.x, p.y + dy, p.z + dz);
+            p = point(
+                p.x + amount * noise.fbm(p, 3),
+                p.y + amount * noise.fbm(offset(JITTER_OFFSET_Y, 0.0), 3),
+                p.z + amount * noise.fbm(offset(0.0, JITTER_OFFSET_Z), 3),
+            );
+        }
+        match self.kind {
+            PatternKind::Texture { id, mapping, wrap } => match textures.get(id) {
+                Some(image) => {
+                    let (u, v) = mapping.uv(p);
+                    sample_bilinear(image, u, v, wrap)
+                }
+                None => Color::black(),
+            },
+            PatternKind::Marble { a, b, noise } => {
+                let t =
+                    (p.x * MARBLE_VEINS + MARBLE_TURBULENCE * noise.turbulence(p, OCTAVES)) * PI;
+                blend(a, b, 0.5 - 0.5 * t.cos())
+            }
+            PatternKind::Wood { a, b, noise } => {
+                let distance = (p.x * p.x + p.z * p.z).sqrt() + WOOD_GRAIN * noise.fbm(p, OCTAVES);
+                let rings = distance * WOOD_RINGS;
+                blend(a, b, rings - rings.floor())
+            }
+            PatternKind::Clouds { a, b, noise } => {
+                blend(a, b, (0.5 + noise.fbm(p, OCTAVES)).clamp(0.0, 1.0))
+            }
+        }
+    }
+}
+
+impl UvMapping {
+    // Texture coordinates of the point `p`
+    pub fn uv(&self, p: Point3) -> (Float, Float) {
+        match self {
+            UvMapping::Spherical => {
+                // Angle around the y axis, 0 towards -z so the middle of the image faces the camera
+                let theta = p.x.atan2(-p.z);
+                let radius = (p.x * p.x + p.y * p.y + p.z * p.z).sqrt();
+                let phi = (p.y / radius).clamp(-1.0, 1.0).acos();
+                (theta / (2.0 * PI) + 0.5, 1.0 - phi / PI)
+            }
+            UvMapping::Planar => (p.x, p.z),
+            UvMapping::Cylindrical => (p.x.atan2(-p.z) / (2.0 * PI) + 0.5, p.y),
+            UvMapping::Cube => {
+                // Face is picked by the largest coordinate, then each face gets a square of the image
+                // (column, row from the bottom) and the coordinates of `p` on it from -1 to 1
+                let (ax, ay, az) = (p.x.abs(), p.y.abs(), p.z.abs());
+                let (col, row, u, v) = if ax >= ay && ax >= az {
+                    if p.x > 0.0 {
+                        (2.0, 1.0, p.z, p.y) // right
+                    } else {
+                        (0.0, 1.0, -p.z, p.y) // left
+                    }
+                } else if ay >= az {
+                    if p.y > 0.0 {
+                        (1.0, 2.0, p.x, p.z) // up
+                    } else {
+                        (1.0, 0.0, p.x, -p.z) // down
+                    }
+                } else if p.z > 0.0 {
+                    (3.0, 1.0, -p.x, p.y) // back
+                } else {
+                    (1.0, 1.0, p.x, p.y) // front
+                };
+                let max = ax.max(ay).max(az);
+                let face = |c: Float| ((c / max + 1.0) / 2.0).clamp(0.0, 1.0);
+                ((col + face(u)) / 4.0, (row + face(v)) / 3.0)
+            }
+        }
+    }
+
+    // Direction along which u grows at the point `p`, not normalized and zero where the mapping
+    // has no direction (the poles of the spherical and cylindrical mappings)
+    pub fn tangent(&self, p: Point3) -> Vector3 {
+        match self {
+            UvMapping::Spherical | UvMapping::Cylindrical => vector(-p.z, 0.0, p.x),
+            UvMapping::Planar => vector(1.0, 0.0, 0.0),
+            UvMapping::Cube => {
+                // Same faces as `uv`, u of each follows one axis
+                let (ax, ay, az) = (p.x.abs(), p.y.abs(), p.z.abs());
+                if ax >= ay && ax >= az {
+                    vector(0.0, 0.0, p.x.signum()) // right and left
+                } else if ay >= az {
+                    vector(1.0, 0.0, 0.0) // up and down
+                } else {
+                    vector(-p.z.signum(), 0.0, 0.0) // back and front
+                }
+            }
+        }
+    }
+}
+
+impl WrapMode {
+    // Index of the pixel to use for pixel `ind` of a row or column of `size` pixels
+    fn wrap(&self, ind: i64, size: usize) -> usize {
+        let size = size as i64;
+        let ind = match self {
+            WrapMode::Repeat => ind.rem_euclid(size),
+            WrapMode::Clamp => ind.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let ind = ind.rem_euclid(2 * size);
+                if ind < size {
+                    ind
+                } else {
+                    2 * size - 1 - ind
+                }
+            }
+        };
+        ind as usize
+    }
+}
+
+// `a` when t = 0, `b` when t = 1
+fn blend(a: Color, b: Color, t: Float) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+// Color of `image` at (u, v), blending the 4 pixels around it
+// Pixel centers are at half integers, so the color is exactly the pixel color at its center
+fn sample_bilinear(image: &Canvas, u: Float, v: Float, wrap: WrapMode) -> Color {
+    if image.width == 0 || image.height == 0 {
+        return Color::black();
+    }
+    let x = u * image.width as Float - 0.5;
+    let y = (1.0 - v) * image.height as Float - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |dx: i64, dy: i64| {
+        let col = wrap.wrap(x0 as i64 + dx, image.width);
+        let row = wrap.wrap(y0 as i64 + dy, image.height);
+        image.pixel_at(col, row)
+    };
+    let top = pixel(0, 0) * (1.0 - fx) + pixel(1, 0) * fx;
+    let bottom = pixel(0, 1) * (1.0 - fx) + pixel(1, 1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+#[cfg(test)]
+mod pattern_tests {
+    use super::*;
+    use crate::{point, Compare};
+
+    // 2 by 2 image- red green on the top row, blue white on the bottom row
+    fn image() -> Canvas {
+        let mut image = Canvas::new(2, 2);
+        image.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        image.write_pixel(1, 0, Color::new(0.0, 1.0, 0.0));
+        image.write_pixel(0, 1, Color::new(0.0, 0.0, 1.0));
+        image.write_pixel(1, 1, Color::white());
+        image
+    }
+
+    #[test]
+    fn spherical_mapping() {
+        let mapping = UvMapping::Spherical;
+        let cases = [
+            (point(0.0, 0.0, -1.0), (0.5, 0.5)),
+            (point(1.0, 0.0, 0.0), (0.75, 0.5)),
+            (point(0.0, 0.0, 1.0), (1.0, 0.5)),
+            (point(-1.0, 0.0, 0.0), (0.25, 0.5)),
+        ];
+        for (p, (u, v)) in cases {
+            let (mu, mv) = mapping.uv(p);
+            assert!((mu.eq(u) || (mu + 1.0).eq(u)) && mv.eq(v), "{:?}", p);
+        }
+        // Any u is right at the poles
+        assert!(mapping.uv(point(0.0, 1.0, 0.0)).1.eq(1.0));
+        assert!(mapping.uv(point(0.0, -1.0, 0.0)).1.eq(0.0));
+    }
+
+    #[test]
+    fn planar_and_cylindrical_mapping() {
+        let p = point(0.25, 0.5, -1.75);
+        assert_eq!(UvMapping::Planar.uv(p), (0.25, -1.75));
+        let (u, v) = UvMapping::Cylindrical.uv(point(1.0, 0.5, 0.0));
+        assert!(u.eq(0.75) && v.eq(0.5));
+    }
+
+    #[test]
+    fn cube_mapping_picks_faces() {
+        let cases = [
+            (point(0.0, 0.0, -1.0), (1.5 / 4.0, 1.5 / 3.0)), // front
+            (point(1.0, 0.0, 0.0), (2.5 / 4.0, 1.5 / 3.0)),  // right
+            (point(0.0, 0.0, 1.0), (3.5 / 4.0, 1.5 / 3.0)),  // back
+            (point(-1.0, 0.0, 0.0), (0.5 / 4.0, 1.5 / 3.0)), // left
+            (point(0.0, 1.0, 0.0), (1.5 / 4.0, 2.5 / 3.0)),  // up
+            (point(0.0, -1.0, 0.0), (1.5 / 4.0, 0.5 / 3.0)), // down
+            (point(-1.0, 1.0, -0.5), (0.75 / 4.0, 2.0 / 3.0)), // top edge of the left face
+        ];
+        for (p, (u, v)) in cases {
+            let (mu, mv) = UvMapping::Cube.uv(p);
+            assert!(mu.eq(u) && mv.eq(v), "{:?} {:?}", p, (mu, mv));
+        }
+    }
+
+    #[test]
+    fn bilinear_sampling() {
+        let image = image();
+        // Pixel centers give the pixel colors, v = 0 is the bottom of the image
+        let c = sample_bilinear(&image, 0.25, 0.75, WrapMode::Clamp);
+        assert_eq!(c, Color::new(1.0, 0.0, 0.0));
+        let c = sample_bilinear(&image, 0.75, 0.25, WrapMode::Clamp);
+        assert_eq!(c, Color::white());
+        // Halfway between all 4 pixels
+        let c = sample_bilinear(&image, 0.5, 0.5, WrapMode::Clamp);
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+        // Halfway between red and green
+        let c = sample_bilinear(&image, 0.5, 0.75, WrapMode::Clamp);
+        assert_eq!(c, Color::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(WrapMode::Repeat.wrap(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.wrap(5, 4), 1);
+        assert_eq!(WrapMode::Clamp.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Clamp.wrap(5, 4), 3);
+        assert_eq!(WrapMode::Mirror.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.wrap(5, 4), 2);
+
+        let image = image();
+        // On the left border- repeat blends with the right column, clamp does not
+        let c = sample_bilinear(&image, 0.0, 0.75, WrapMode::Repeat);
+        assert_eq!(c, Color::new(0.5, 0.5, 0.0));
+        let c = sample_bilinear(&image, 0.0, 0.75, WrapMode::Clamp);
+        assert_eq!(c, Color::new(1.0, 0.0, 0.0));
+        let c = sample_bilinear(&image, 1.25, 0.75, WrapMode::Repeat);
+        assert_eq!(c, Color::new(1.0, 0.0, 0.0));
+        let c = sample_bilinear(&image, -0.25, 0.75, WrapMode::Mirror);
+        assert_eq!(c, Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn texture_pattern_with_transform() {
+        let textures = [image()];
+        let mut pattern = Pattern::texture(0, UvMapping::Planar, WrapMode::Repeat);
+        assert_eq!(
+            pattern.color_at(point(0.25, 0.0, 0.25), &textures),
+            Color::new(0.0, 0.0, 1.0)
+        );
+        // Scaling the pattern up by 2 makes the same pixel appear twice as far
+        pattern.set_transform(Matrix::get_scaling_matrix(2.0, 2.0, 2.0));
+        assert_eq!(
+            pattern.color_at(point(0.5, 0.0, 0.5), &textures),
+            Color::new(0.0, 0.0, 1.0)
+        );
+        let missing = Pattern::texture(1, UvMapping::Planar, WrapMode::Repeat);
+        assert_eq!(
+            missing.color_at(point(0.0, 0.0, 0.0), &textures),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn procedural_patterns_start_from_first_color() {
+        // All the noise is 0 at the origin
+        let (a, b) = (Color::new(0.9, 0.9, 0.8), Color::new(0.2, 0.2, 0.3));
+        let origin = point(0.0, 0.0, 0.0);
+        assert_eq!(Pattern::marble(a, b, 1).color_at(origin, &[]), a);
+        assert_eq!(Pattern::wood(a, b, 1).color_at(origin, &[]), a);
+        assert_eq!(
+            Pattern::clouds(a, b, 1).color_at(origin, &[]),
+            Color::new(0.55, 0.55, 0.55)
+        );
+    }
+
+    #[test]
+    fn procedural_patterns_stay_between_their_colors() {
+        let (a, b) = (Color::black(), Color::white());
+        for pattern in [
+            Pattern::marble(a, b, 7),
+            Pattern::wood(a, b, 7),
+            Pattern::clouds(a, b, 7),
+        ] {
+            let (mut min, mut max): (Float, Float) = (1.0, 0.0);
+            for i in 0..500 {
+                let i = i as Float;
+                let p = point(i * 0.013 - 3.0, i * 0.007, 1.0 - i * 0.011);
+                let c = pattern.color_at(p, &[]);
+                assert!(c.red >= 0.0 && c.red <= 1.0, "{:?} {:?}", pattern.kind, c);
+                min = min.min(c.red);
+                max = max.max(c.red);
+            }
+            assert!(max - min > 0.5, "{:?} is too flat", pattern.kind);
+        }
+    }
+
+    #[test]
+    fn procedural_patterns_are_seeded() {
+        let (a, b) = (Color::black(), Color::white());
+        let p = point(0.37, -1.2, 0.81);
+        let c = Pattern::marble(a, b, 3).color_at(p, &[]);
+        assert_eq!(c, Pattern::marble(a, b, 3).color_at(p, &[]));
+        assert_ne!(c, Pattern::marble(a, b, 4).color_at(p, &[]));
+    }
+
+    #[test]
+    fn jitter_displaces_the_pattern() {
+        let textures = [image()];
+        let mut pattern = Pattern::texture(0, UvMapping::Planar, WrapMode::Repeat);
+        let p = point(0.2, 0.0, 0.3);
+        let plain = pattern.color_at(p, &textures);
+        pattern.set_jitter(0.2, 9);
+        let jittered = pattern.color_at(p, &textures);
+        assert_ne!(plain, jittered);
+        assert_eq!(jittered, pattern.color_at(p, &textures));
+        pattern.set_jitter(0.0, 9);
+        assert_eq!(plain, pattern.color_at(p, &textures));
+    }
+}
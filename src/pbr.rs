@@ -0,0 +1,264 @@
+use crate::consts::PI;
+use crate::integrator::cosine_sample_hemisphere;
+use crate::utils::Rng;
+use crate::Float;
+use crate::{Color, Vector3};
+
+// Reflectance at normal incidence of all the non metals (plastic, wood, stone, ...)
+const DIELECTRIC_F0: Float = 0.04;
+
+// Physically based metallic/roughness shading (the model of glTF, Unreal, Blender's Principled BSDF)
+// Specular is a GGX microfacet distribution with the Smith geometry term and Fresnel-Schlick,
+// diffuse is Lambertian and only gets the light which is not reflected specularly,
+// so a surface never reflects more light than it receives
+// The base color is the `color` of the `Material`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pbr {
+    pub metallic: Float,  // 0.0 (dielectric) to 1.0 (metal)
+    pub roughness: Float, // 0.0 (mirror like) to 1.0 (completely rough)
+}
+
+impl Pbr {
+    pub fn new(metallic: Float, roughness: Float) -> Self {
+        Self {
+            metallic,
+            roughness,
+        }
+    }
+
+    // GGX alpha, a perfect mirror would need a delta distribution so the roughness is kept above 0
+    fn alpha(&self) -> Float {
+        let roughness = self.roughness.clamp(0.03, 1.0);
+        roughness * roughness
+    }
+
+    // Color of the specular reflection at normal incidence
+    // Metals tint their reflection with the base color and have no diffuse part
+    fn f0(&self, base_color: Color) -> Color {
+        let metallic = self.metallic.clamp(0.0, 1.0);
+        Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0) * (1.0 - metallic)
+            + base_color * metallic
+    }
+
+    // Value of the BRDF for light coming from `lightv` and leaving towards `eyev`
+    // All vectors are unit vectors pointing away from the surface
+    pub fn brdf(
+        &self,
+        base_color: Color,
+        normalv: Vector3,
+        eyev: Vector3,
+        lightv: Vector3,
+    ) -> Color {
+        let n_dot_l = normalv.dot(&lightv);
+        let n_dot_v = normalv.dot(&eyev);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return Color::black();
+        }
+        let halfv = (eyev + lightv).normalize();
+        let n_dot_h = normalv.dot(&halfv).max(0.0);
+        let v_dot_h = eyev.dot(&halfv).max(0.0);
+        let alpha = self.alpha();
+
+        let f0 = self.f0(base_color);
+        let specular = fresnel_schlick(f0, v_dot_h)
+            * (ggx_distribution(n_dot_h, alpha) * smith_geometry(n_dot_l, n_dot_v, alpha)
+                / (4.0 * n_dot_l * n_dot_v));
+        // Light enters the surface through the interface and leaves through it again,
+        // each time losing the part which is reflected specularly
+        let transmitted = (Color::white() - fresnel_schlick(f0, n_dot_l))
+            * (Color::white() - fresnel_schlick(f0, n_dot_v));
+        let diffuse = transmitted * base_color * ((1.0 - self.metallic.clamp(0.0, 1.0)) / PI);
+        diffuse + specular
+    }
+
+    // Pick a random direction for the light to come from, following the shape of the BRDF
+    // Returns the direction and its weight brdf * cos / pdf, `None` when the sample goes below the surface
+    pub fn sample(
+        &self,
+        base_color: Color,
+        normalv: Vector3,
+        eyev: Vector3,
+        rng: &mut Rng,
+    ) -> Option<(Vector3, Color)> {
+        let specular_probability = self.specular_probability();
+        let lightv = if rng.next_float() < specular_probability {
+            let halfv = sample_ggx(normalv, self.alpha(), rng);
+            (-eyev).reflect(&halfv)
+        } else {
+            cosine_sample_hemisphere(normalv, rng)
+        };
+
+        let n_dot_l = normalv.dot(&lightv);
+        if n_dot_l <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(normalv, eyev, lightv);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((
+            lightv,
+            self.brdf(base_color, normalv, eyev, lightv) * (n_dot_l / pdf),
+        ))
+    }
+
+    // Probability density of `sample` returning `lightv`
+    pub fn pdf(&self, normalv: Vector3, eyev: Vector3, lightv: Vector3) -> Float {
+        let n_dot_l = normalv.dot(&lightv);
+        if n_dot_l <= 0.0 {
+            return 0.0;
+        }
+        let halfv = (eyev + lightv).normalize();
+        let n_dot_h = normalv.dot(&halfv).max(0.0);
+        let v_dot_h = eyev.dot(&halfv);
+        let specular_pdf = if v_dot_h > 0.0 {
+            ggx_distribution(n_dot_h, self.alpha()) * n_dot_h / (4.0 * v_dot_h)
+        } else {
+            0.0
+        };
+        let diffuse_pdf = n_dot_l / PI;
+        let p = self.specular_probability();
+        p * specular_pdf + (1.0 - p) * diffuse_pdf
+    }
+
+    // Share of the samples spent on the specular lobe, metals have no diffuse lobe
+    fn specular_probability(&self) -> Float {
+        (0.5 + 0.5 * self.metallic).clamp(0.5, 1.0)
+    }
+}
+
+impl Default for Pbr {
+    // Rough plastic
+    fn default() -> Self {
+        Self::new(0.0, 0.5)
+    }
+}
+
+// Fraction of the light reflected at an angle whose cosine is `cos`, Schlick's approximation
+fn fresnel_schlick(f0: Color, cos: Float) -> Color {
+    let factor = (1.0 - cos).clamp(0.0, 1.0).powi(5);
+    f0 + (Color::white() - f0) * factor
+}
+
+// GGX (Trowbridge-Reitz) distribution of the microfacet normals around the surface normal
+fn ggx_distribution(n_dot_h: Float, alpha: Float) -> Float {
+    let alpha2 = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denominator * denominator)
+}
+
+// Smith masking-shadowing for GGX, fraction of the microfacets seen from both directions
+fn smith_geometry(n_dot_l: Float, n_dot_v: Float, alpha: Float) -> Float {
+    let alpha2 = alpha * alpha;
+    let g1 = |cos: Float| 2.0 * cos / (cos + (alpha2 + (1.0 - alpha2) * cos * cos).sqrt());
+    g1(n_dot_l) * g1(n_dot_v)
+}
+
+// Random microfacet normal distributed as ggx_distribution * cos around `normalv`
+fn sample_ggx(normalv: Vector3, alpha: Float, rng: &mut Rng) -> Vector3 {
+    let u = rng.next_float();
+    let phi = 2.0 * PI * rng.next_float();
+    let cos_theta = ((1.0 - u) / (1.0 + (alpha * alpha - 1.0) * u)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let (tangent, bitangent) = normalv.orthonormal_basis();
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normalv * cos_theta
+}
+
+#[cfg(test)]
+mod pbr_tests {
+    use super::*;
+    use crate::{vector, Compare};
+
+    // Monte Carlo estimate of the fraction of light reflected towards `eyev`, using `sample`
+    fn albedo(pbr: &Pbr, base_color: Color, eyev: Vector3) -> Color {
+        let normalv = vector(0.0, 0.0, 1.0);
+        let mut rng = Rng::new(5);
+        let mut total = Color::black();
+        let count = 20000;
+        for _ in 0..count {
+            if let Some((_, weight)) = pbr.sample(base_color, normalv, eyev, &mut rng) {
+                total = total + weight;
+            }
+        }
+        total * (1.0 / count as Float)
+    }
+
+    #[test]
+    fn fresnel_at_normal_incidence_is_f0() {
+        let f0 = Color::new(0.04, 0.04, 0.04);
+        assert_eq!(fresnel_schlick(f0, 1.0), f0);
+        assert_eq!(fresnel_schlick(f0, 0.0), Color::white());
+    }
+
+    #[test]
+    fn ggx_distribution_is_normalized() {
+        // Projected area of the microfacets is the area of the surface- integral of D * cos = 1
+        let alpha: Float = 0.25;
+        let steps = 4000;
+        let mut integral: Float = 0.0;
+        for i in 0..steps {
+            let theta = (i as Float + 0.5) / steps as Float * PI / 2.0;
+            let d_theta = PI / 2.0 / steps as Float;
+            integral += ggx_distribution(theta.cos(), alpha)
+                * theta.cos()
+                * theta.sin()
+                * d_theta
+                * 2.0
+                * PI;
+        }
+        assert!((integral - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn metal_has_no_diffuse() {
+        let pbr = Pbr::new(1.0, 1.0);
+        let normalv = vector(0.0, 0.0, 1.0);
+        // Far away from the mirror direction only the (absent) diffuse part would remain
+        let c = pbr.brdf(
+            Color::new(1.0, 0.5, 0.2),
+            normalv,
+            vector(0.0, 0.0, 1.0),
+            vector(0.0, 0.999, 0.045).normalize(),
+        );
+        assert!(c.red > c.green && c.green > c.blue);
+        assert_eq!(pbr.specular_probability(), 1.0);
+    }
+
+    #[test]
+    fn light_below_the_surface_is_not_reflected() {
+        let pbr = Pbr::default();
+        let normalv = vector(0.0, 0.0, 1.0);
+        let c = pbr.brdf(Color::white(), normalv, normalv, vector(0.0, 0.0, -1.0));
+        assert_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn white_surfaces_conserve_energy() {
+        for (metallic, roughness) in [(0.0, 0.1), (0.0, 0.5), (0.0, 1.0), (1.0, 0.3), (1.0, 1.0)] {
+            let pbr = Pbr::new(metallic, roughness);
+            for eyev in [vector(0.0, 0.0, 1.0), vector(0.0, 0.8, 0.6)] {
+                let reflected = albedo(&pbr, Color::white(), eyev);
+                assert!(reflected.max_component() <= 1.0, "{:?}", reflected);
+                // Single scattering GGX loses some light on rough surfaces, but not all of it
+                assert!(reflected.max_component() > 0.25, "{:?}", reflected);
+            }
+        }
+    }
+
+    #[test]
+    fn sample_weight_matches_brdf_and_pdf() {
+        let pbr = Pbr::new(0.3, 0.4);
+        let normalv = vector(0.0, 0.0, 1.0);
+        let eyev = vector(0.0, 0.6, 0.8);
+        let base_color = Color::new(0.9, 0.5, 0.1);
+        let mut rng = Rng::new(9);
+        for _ in 0..100 {
+            if let Some((lightv, weight)) = pbr.sample(base_color, normalv, eyev, &mut rng) {
+                let expected = pbr.brdf(base_color, normalv, eyev, lightv)
+                    * (normalv.dot(&lightv) / pbr.pdf(normalv, eyev, lightv));
+                assert_eq!(weight, expected);
+                assert!(lightv.magnitude().eq(1.0));
+            }
+        }
+    }
+}
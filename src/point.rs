@@ -0,0 +1,116 @@
+use crate::Compare;
+use crate::Float;
+use crate::{Matrix, Tuple, Vector3};
+
+// A position in space, like a Tuple with w=1.0 but checked by the compiler
+// Only the operations that make sense compile-
+// point - point = vector, point + vector = point, point - vector = point, no point + point
+#[derive(Clone, Copy, Debug)]
+pub struct Point3 {
+    pub x: Float, // x coordinate of the point
+    pub y: Float, // y coordinate of the point
+    pub z: Float, // z coordinate of the point
+}
+
+// Factory method for creating point
+pub fn point(x: Float, y: Float, z: Float) -> Point3 {
+    Point3::new(x, y, z)
+}
+
+impl Point3 {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl PartialEq for Point3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.x.eq(other.x) && self.y.eq(other.y) && self.z.eq(other.z)
+    }
+}
+
+// Point is a Tuple with w=1.0
+impl From<Point3> for Tuple {
+    fn from(p: Point3) -> Self {
+        Tuple::new(p.x, p.y, p.z, 1.0)
+    }
+}
+
+// Only Tuples with w=1.0 are points
+impl TryFrom<Tuple> for Point3 {
+    type Error = String;
+
+    fn try_from(t: Tuple) -> Result<Self, Self::Error> {
+        if t.is_point() {
+            Ok(Point3::new(t.x, t.y, t.z))
+        } else {
+            Err(format!("Tuple with w={} is not a point", t.w))
+        }
+    }
+}
+
+// For point - point, gives the vector from `other` to `self`
+impl std::ops::Sub for Point3 {
+    type Output = Vector3;
+
+    fn sub(self, other: Self) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+// For point + vector, moves the point along the vector
+impl std::ops::Add<Vector3> for Point3 {
+    type Output = Self;
+
+    fn add(self, other: Vector3) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+// For point - vector, moves the point backwards along the vector
+impl std::ops::Sub<Vector3> for Point3 {
+    type Output = Self;
+
+    fn sub(self, other: Vector3) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+// For matrix * point
+// Points are translated, the matrix is expected to be affine like all the transformations
+impl std::ops::Mul<Point3> for Matrix {
+    type Output = Point3;
+
+    fn mul(self, other: Point3) -> Point3 {
+        let t = self * Tuple::from(other);
+        Point3::new(t.x, t.y, t.z)
+    }
+}
+
+#[cfg(test)]
+mod point_tests {
+    use super::*;
+    use crate::vector;
+
+    #[test]
+    fn point_from_tuple() {
+        let p = Point3::try_from(Tuple::new(4.0, -4.0, 3.0, 1.0));
+        assert_eq!(p, Ok(point(4.0, -4.0, 3.0)));
+        assert!(Point3::try_from(Tuple::new(4.0, -4.0, 3.0, 0.0)).is_err());
+    }
+
+    #[test]
+    fn point_vector_addition_and_subtraction() {
+        let p = point(3.0, 2.0, 1.0);
+        let v = vector(5.0, 6.0, 7.0);
+        assert_eq!(p + v, point(8.0, 8.0, 8.0));
+        assert_eq!(p - v, point(-2.0, -4.0, -6.0));
+    }
+
+    #[test]
+    fn translation_moves_point() {
+        let transform = Matrix::get_translation_matrix(5.0, -3.0, 2.0);
+        let p = point(-3.0, 4.0, 5.0);
+        assert_eq!(transform * p, point(2.0, 1.0, 7.0));
+    }
+}
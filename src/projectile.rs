@@ -1,13 +1,13 @@
-use crate::Tuple;
+use crate::{Point3, Vector3};
 
 pub struct Projectile {
-    pub position: Tuple,
-    pub velocity: Tuple,
+    pub position: Point3,
+    pub velocity: Vector3,
 }
 
 pub struct Environment {
-    pub gravity: Tuple,
-    pub wind: Tuple,
+    pub gravity: Vector3,
+    pub wind: Vector3,
     pub projectile: Projectile,
 }
 
@@ -0,0 +1,288 @@
+use crate::Compare;
+use crate::Float;
+use crate::{vector, Matrix, Point3, Vector3};
+
+// Unit quaternions represent rotations without the gimbal lock of chained x/y/z rotations
+// and can be interpolated smoothly with `slerp`
+// A rotation of `r` radians around the unit `axis` is (cos(r/2), sin(r/2) * axis)
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub w: Float, // real (scalar) part
+    pub x: Float, // x component of the imaginary (vector) part
+    pub y: Float, // y component of the imaginary (vector) part
+    pub z: Float, // z component of the imaginary (vector) part
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Self {
+        Self { w, x, y, z }
+    }
+
+    // No rotation
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Rotation of `r` radians around `axis`, which does not need to be normalized
+    // Same direction as the rotation matrices, left-hand thumb rule
+    // A zero axis has no direction to turn around, it gives no rotation
+    pub fn from_axis_angle(axis: Vector3, r: Float) -> Self {
+        let Some(axis) = axis.try_normalize() else {
+            return Self::identity();
+        };
+        let (sin, cos) = (r / 2.0).sin_cos();
+        Self::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    // Rotation part of a matrix (Shepperd's method)
+    // The upper 3x3 part of the matrix should be a pure rotation
+    pub fn from_matrix(m: &Matrix) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // Divide by the largest of the four components to stay numerically stable
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0; // 4 * w
+            Self::new(
+                s / 4.0,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0; // 4 * x
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0; // 4 * y
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0; // 4 * z
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+            )
+        };
+        q.normalize()
+    }
+
+    // Rotation matrix of the (unit) quaternion
+    pub fn to_matrix(&self) -> Matrix {
+        let Self { w, x, y, z } = *self;
+        let mut m = Matrix::I();
+        m[0][0] = 1.0 - 2.0 * (y * y + z * z);
+        m[0][1] = 2.0 * (x * y - w * z);
+        m[0][2] = 2.0 * (x * z + w * y);
+        m[1][0] = 2.0 * (x * y + w * z);
+        m[1][1] = 1.0 - 2.0 * (x * x + z * z);
+        m[1][2] = 2.0 * (y * z - w * x);
+        m[2][0] = 2.0 * (x * z - w * y);
+        m[2][1] = 2.0 * (y * z + w * x);
+        m[2][2] = 1.0 - 2.0 * (x * x + y * y);
+        m
+    }
+
+    pub fn magnitude(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        *self * (1.0 / self.magnitude())
+    }
+
+    // For a unit quaternion it is the inverse rotation
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(&self, other: &Self) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    // Rotate a vector, same as multiplying it by `to_matrix`
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        let q = Self::new(0.0, v.x, v.y, v.z);
+        let r = *self * q * self.conjugate();
+        vector(r.x, r.y, r.z)
+    }
+
+    // Rotate a point around the origin
+    pub fn rotate_point(&self, p: Point3) -> Point3 {
+        let v = self.rotate(vector(p.x, p.y, p.z));
+        Point3::new(v.x, v.y, v.z)
+    }
+
+    // Spherical linear interpolation, `t` = 0 gives `self` and `t` = 1 gives `other`
+    // Rotates at constant angular speed along the shortest path between the two rotations
+    pub fn slerp(&self, other: &Self, t: Float) -> Self {
+        let mut other = *other;
+        let mut cos = self.dot(&other);
+        // q and -q are the same rotation, pick the one which is closer
+        if cos < 0.0 {
+            other = other * -1.0;
+            cos = -cos;
+        }
+        // Nearly identical rotations, sin(angle) would be close to 0 so interpolate linearly
+        if cos > 0.9995 {
+            return (*self * (1.0 - t) + other * t).normalize();
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        let a = ((1.0 - t) * angle).sin() / sin;
+        let b = (t * angle).sin() / sin;
+        *self * a + other * b
+    }
+
+    // Axis and angle (in radians) of the rotation
+    pub fn to_axis_angle(&self) -> (Vector3, Float) {
+        let q = if self.w < 0.0 { *self * -1.0 } else { *self };
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin.eq(0.0) {
+            return (vector(1.0, 0.0, 0.0), 0.0); // No rotation, any axis works
+        }
+        (vector(q.x / sin, q.y / sin, q.z / sin), angle)
+    }
+}
+
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Self) -> bool {
+        self.w.eq(other.w) && self.x.eq(other.x) && self.y.eq(other.y) && self.z.eq(other.z)
+    }
+}
+
+// For quaternion + quaternion
+impl std::ops::Add for Quaternion {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(
+            self.w + other.w,
+            self.x + other.x,
+            self.y + other.y,
+            self.z + other.z,
+        )
+    }
+}
+
+// For quaternion * scalar
+impl std::ops::Mul<Float> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, a: Float) -> Self {
+        Self::new(self.w * a, self.x * a, self.y * a, self.z * a)
+    }
+}
+
+// For quaternion * quaternion (Hamilton product)
+// Applies the rotation `other` first and then `self`, like matrix multiplication
+impl std::ops::Mul<Quaternion> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod quaternion_tests {
+    use super::*;
+    use crate::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use crate::point;
+
+    #[test]
+    fn axis_angle_matches_rotation_matrices() {
+        let r = 0.7;
+        let qx = Quaternion::from_axis_angle(vector(1.0, 0.0, 0.0), r);
+        let qy = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), r);
+        let qz = Quaternion::from_axis_angle(vector(0.0, 0.0, 2.0), r);
+        assert_eq!(qx.to_matrix(), Matrix::get_rotation_x_matrix(r));
+        assert_eq!(qy.to_matrix(), Matrix::get_rotation_y_matrix(r));
+        assert_eq!(qz.to_matrix(), Matrix::get_rotation_z_matrix(r));
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let m = Matrix::I().rotate_x(0.3).rotate_y(-2.5).rotate_z(PI * 0.9);
+        let q = Quaternion::from_matrix(&m);
+        assert!(q.magnitude().eq(1.0));
+        assert_eq!(q.to_matrix(), m);
+    }
+
+    #[test]
+    fn matrix_round_trip_half_turn() {
+        // Trace is negative, exercises the other branches
+        for axis in [
+            vector(1.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+            vector(0.0, 0.0, 1.0),
+        ] {
+            let m = Matrix::get_rotation_matrix(axis, PI);
+            assert_eq!(Quaternion::from_matrix(&m).to_matrix(), m);
+        }
+    }
+
+    #[test]
+    fn zero_axis_gives_no_rotation() {
+        let q = Quaternion::from_axis_angle(vector(0.0, 0.0, 0.0), 1.2);
+        assert_eq!(q.to_matrix(), Matrix::I());
+    }
+
+    #[test]
+    fn rotating_a_point() {
+        let q = Quaternion::from_axis_angle(vector(0.0, 0.0, 1.0), FRAC_PI_2);
+        assert_eq!(q.rotate_point(point(0.0, 1.0, 0.0)), point(-1.0, 0.0, 0.0));
+        assert_eq!(q.rotate(vector(1.0, 0.0, 0.0)), vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn multiplication_chains_rotations() {
+        let a = Quaternion::from_axis_angle(vector(1.0, 0.0, 0.0), 0.4);
+        let b = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), 1.1);
+        assert_eq!((b * a).to_matrix(), b.to_matrix() * a.to_matrix());
+        assert_eq!(a * a.conjugate(), Quaternion::identity());
+    }
+
+    #[test]
+    fn slerp_between_rotations() {
+        let axis = vector(1.0, 1.0, 0.0);
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(axis, FRAC_PI_2);
+        assert_eq!(a.slerp(&b, 0.0), a);
+        assert_eq!(a.slerp(&b, 1.0), b);
+        assert_eq!(
+            a.slerp(&b, 0.5),
+            Quaternion::from_axis_angle(axis, FRAC_PI_4)
+        );
+    }
+
+    #[test]
+    fn slerp_takes_shortest_path() {
+        let a = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), 0.1);
+        let b = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), 2.0 * PI - 0.1);
+        // Halfway between +0.1 and -0.1 radians, not halfway around the circle
+        assert_eq!(a.slerp(&b, 0.5), Quaternion::identity());
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let q = Quaternion::from_axis_angle(vector(0.0, 2.0, 0.0), 1.2);
+        let (axis, angle) = q.to_axis_angle();
+        assert_eq!(axis, vector(0.0, 1.0, 0.0));
+        assert!(angle.eq(1.2));
+    }
+}
@@ -1,18 +1,30 @@
-use crate::{Matrix, Tuple};
+use crate::Float;
+use crate::{Matrix, Point3, Vector3};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Ray {
-    pub origin: Tuple,    // Is a point
-    pub direction: Tuple, // Is a vector
+    pub origin: Point3,
+    pub direction: Vector3,
+    pub time: Float, // Instant the ray is cast at, between the shutter open and close of the camera
 }
 
 impl Ray {
-    pub fn new(origin: Tuple, direction: Tuple) -> Self {
-        Self { origin, direction }
+    pub fn new(origin: Point3, direction: Vector3) -> Self {
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    // Same ray cast at `time`, moving objects are seen where they are at that instant
+    pub fn at_time(mut self, time: Float) -> Self {
+        self.time = time;
+        self
     }
 
     // Computes new position of ray after time `t`
-    pub fn position(&self, t: f32) -> Tuple {
+    pub fn position(&self, t: Float) -> Point3 {
         self.origin + self.direction * t
     }
 
@@ -21,6 +33,7 @@ impl Ray {
         Self {
             origin: m * self.origin,
             direction: m * self.direction,
+            time: self.time,
         }
     }
 }
@@ -67,4 +80,14 @@ mod ray_tests {
         assert_eq!(r2.origin, point(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, vector(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn transform_keeps_the_time() {
+        let r = Ray::new(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
+        assert_eq!(r.time, 0.0);
+        let r2 = r
+            .at_time(0.5)
+            .transform(Matrix::get_scaling_matrix(2.0, 3.0, 4.0));
+        assert_eq!(r2.time, 0.5);
+    }
 }
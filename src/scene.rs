@@ -0,0 +1,694 @@
+use crate::consts::PI;
+use crate::Float;
+use crate::{point, vector, Background, Bump, Camera, Canvas, Color, EnvironmentMap};
+use crate::{Fog, Light, Matrix, Medium, Pattern, Pbr, Sphere, UvMapping, Volume, World, WrapMode};
+use crate::{Keyframe, Motion, Quaternion};
+use std::fs;
+
+// A world together with the camera looking at it, read from a scene file
+//
+// Scene file format- one statement per line, `#` starts a comment
+// camera HSIZE VSIZE FIELD_OF_VIEW
+// shutter OPEN CLOSE              (times the shutter of the camera is open between, for motion blur)
+// view FROM_X FROM_Y FROM_Z TO_X TO_Y TO_Z UP_X UP_Y UP_Z
+// light X Y Z RED GREEN BLUE
+// background RED GREEN BLUE | gradient BOTTOM_RED BOTTOM_GREEN BOTTOM_BLUE TOP_RED TOP_GREEN TOP_BLUE
+// environment HDR_IMAGE_PATH [INTENSITY]   (equirectangular .hdr image, path relative to the working directory)
+// sphere                          (starts a new sphere, the statements below apply to it)
+// translate X Y Z | scale X Y Z | rotate_x R | rotate_y R | rotate_z R | rotate AXIS_X AXIS_Y AXIS_Z R
+// shear XY XZ YX YZ ZX ZY
+// color RED GREEN BLUE | ambient A | diffuse D | specular S | shininess S
+// emission RED GREEN BLUE         (the sphere glows and lights the scene)
+// casts_shadow 0|1 | receives_shadow 0|1   (both 1 by default)
+// transparency T                  (0 opaque to 1, the light going through is tinted by `color`)
+// metallic M | roughness R        (switch the sphere to the metallic/roughness model, `color` is the base color)
+// texture IMAGE_PATH [spherical | planar | cylindrical | cube] [repeat | clamp | mirror]
+//                                 (.ppm, .png or .hdr image replacing `color`, default spherical and repeat)
+// marble | wood | clouds  RED_A GREEN_A BLUE_A RED_B GREEN_B BLUE_B [SEED]
+//                                 (procedural noise pattern replacing `color`, blending 2 colors)
+// jitter AMOUNT [SEED]            (wobbles the pattern of the sphere)
+// normal_map IMAGE_PATH [spherical | planar | cylindrical | cube] [repeat | clamp | mirror] [STRENGTH]
+//                                 (tangent space normal map, default spherical, repeat and 1)
+// bump HEIGHT SIZE [SEED]         (noise bumps up to HEIGHT high and about SIZE wide)
+// fog ABSORPTION SCATTERING [HEIGHT FALLOFF]
+//                                 (fog in the whole world, per unit of distance. With HEIGHT and
+//                                  FALLOFF it is as given at y = HEIGHT and e times thinner every
+//                                  1 / FALLOFF units higher)
+// volume ABSORPTION SCATTERING    (the sphere becomes an invisible container of fog)
+// motion START END X Y Z [AXIS_X AXIS_Y AXIS_Z R]
+//                                 (the sphere moves by X Y Z, and turns by R around the axis,
+//                                  between the times START and END)
+//
+// Transformations are applied in the order they are written
+// Angles are in radians and can also be written as `pi`, `pi/4`, `-pi/2` etc.
+#[derive(Clone)]
+pub struct Scene {
+    pub camera: Camera,
+    pub world: World,
+}
+
+impl Scene {
+    // Read and parse the scene file at `file_path`
+    pub fn from_file(file_path: &str) -> Result<Scene, String> {
+        let source = fs::read_to_string(file_path)
+            .map_err(|err| format!("Unable to read scene file {}: {}", file_path, err))?;
+        Scene::parse(&source)
+    }
+
+    // Parse the scene from the content of a scene file, images are read from disk
+    pub fn parse(source: &str) -> Result<Scene, String> {
+        Scene::parse_with(source, |path| fs::read(path).map_err(|err| err.to_string()))
+    }
+
+    // Parse the scene, getting the content of the image files it uses from `read_file`
+    // (paths are given as written in the scene)
+    pub fn parse_with(
+        source: &str,
+        mut read_file: impl FnMut(&str) -> Result<Vec<u8>, String>,
+    ) -> Result<Scene, String> {
+        let mut camera: Option<Camera> = None;
+        let mut view = Matrix::I();
+        let mut shutter: (Float, Float) = (0.0, 0.0);
+        let mut light: Option<Light> = None;
+        let mut objects: Vec<Sphere> = Vec::new();
+        let mut background = Background::default();
+        let mut textures: Vec<Canvas> = Vec::new();
+        let mut fog: Option<Fog> = None;
+        // Index of the spheres turned into volumes, and the medium inside them
+        let mut volumes: Vec<(usize, Medium)> = Vec::new();
+
+        for (line_ind, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap_or("");
+            if keyword == "environment" {
+                let path = tokens.next().ok_or(format!(
+                    "line {}: `environment` expects an image path",
+                    line_ind + 1
+                ))?;
+                let intensity = tokens
+                    .next()
+                    .map_or(Ok(1.0), parse_number)
+                    .map_err(|err| format!("line {}: {}", line_ind + 1, err))?;
+                let image = read_file(path)
+                    .map_err(|err| format!("Unable to read hdr image {}: {}", path, err))
+                    .and_then(|bytes| Canvas::from_hdr(&bytes))
+                    .map_err(|err| format!("line {}: {}", line_ind + 1, err))?;
+                background = Background::Environment(EnvironmentMap::new(image, intensity));
+                continue;
+            }
+            if keyword == "texture" || keyword == "normal_map" {
+                let error = |message: &str| format!("line {}: {}", line_ind + 1, message);
+                let object = objects
+                    .last_mut()
+                    .ok_or_else(|| error(&format!("`{}` used before any `sphere`", keyword)))?;
+                let path = tokens
+                    .next()
+                    .ok_or_else(|| error(&format!("`{}` expects an image path", keyword)))?;
+                let mut mapping = UvMapping::default();
+                let mut wrap = WrapMode::default();
+                let mut strength = 1.0;
+                for token in tokens {
+                    match token {
+                        "spherical" => mapping = UvMapping::Spherical,
+                        "planar" => mapping = UvMapping::Planar,
+                        "cylindrical" => mapping = UvMapping::Cylindrical,
+                        "cube" => mapping = UvMapping::Cube,
+                        "repeat" => wrap = WrapMode::Repeat,
+                        "clamp" => wrap = WrapMode::Clamp,
+                        "mirror" => wrap = WrapMode::Mirror,
+                        _ if keyword == "normal_map" && parse_number(token).is_ok() => {
+                            strength = parse_number(token)?
+                        }
+                        _ => return Err(error(&format!("unknown {} option `{}`", keyword, token))),
+                    }
+                }
+                let image = read_file(path)
+                    .map_err(|err| format!("Unable to read image {}: {}", path, err))
+                    .and_then(|bytes| Canvas::from_image(path, &bytes))
+                    .map_err(|err| error(&err))?;
+                textures.push(image);
+                let pattern = Pattern::texture(textures.len() - 1, mapping, wrap);
+                if keyword == "texture" {
+                    object.material.pattern = Some(pattern);
+                } else {
+                    object.material.bump = Some(Bump::normal_map(pattern, strength));
+                }
+                continue;
+            }
+            let args = tokens
+                .map(parse_number)
+                .collect::<Result<Vec<Float>, String>>()
+                .map_err(|err| format!("line {}: {}", line_ind + 1, err))?;
+            let expect = |count: usize| {
+                if args.len() == count {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "line {}: `{}` expects {} numbers, found {}",
+                        line_ind + 1,
+                        keyword,
+                        count,
+                        args.len()
+                    ))
+                }
+            };
+            // Axis of `rotate` and `motion`, a zero one would be no rotation at all
+            let rotation_axis = |x: Float, y: Float, z: Float| {
+                vector(x, y, z).try_normalize().ok_or(format!(
+                    "line {}: rotation axis must not be zero",
+                    line_ind + 1
+                ))
+            };
+
+            match keyword {
+                "camera" => {
+                    expect(3)?;
+                    let size = |value: Float| {
+                        (1.0..=u16::MAX as Float).contains(&value) && value.fract() == 0.0
+                    };
+                    if !size(args[0]) || !size(args[1]) {
+                        return Err(format!(
+                            "line {}: camera size must be a whole number from 1 to {}",
+                            line_ind + 1,
+                            u16::MAX
+                        ));
+                    }
+                    if !(args[2] > 0.0 && args[2] < PI) {
+                        return Err(format!(
+                            "line {}: field of view must be between 0 and pi",
+                            line_ind + 1
+                        ));
+                    }
+                    camera = Some(Camera::new(args[0] as u16, args[1] as u16, args[2]));
+                }
+                "shutter" => {
+                    expect(2)?;
+                    shutter = (args[0], args[1]);
+                }
+                "view" => {
+                    expect(9)?;
+                    view = Matrix::get_view_transform(
+                        point(args[0], args[1], args[2]),
+                        point(args[3], args[4], args[5]),
+                        vector(args[6], args[7], args[8]),
+                    );
+                    if !view.is_invertible() {
+                        return Err(format!("line {}: singular transform", line_ind + 1));
+                    }
+                }
+                "light" => {
+                    expect(6)?;
+                    light = Some(Light::new(
+                        point(args[0], args[1], args[2]),
+                        Color::new(args[3], args[4], args[5]),
+                    ));
+                }
+                "background" => {
+                    expect(3)?;
+                    background = Background::Solid(Color::new(args[0], args[1], args[2]));
+                }
+                "gradient" => {
+                    expect(6)?;
+                    background = Background::Gradient {
+                        bottom: Color::new(args[0], args[1], args[2]),
+                        top: Color::new(args[3], args[4], args[5]),
+                    };
+                }
+                "fog" => {
+                    if args.len() != 4 {
+                        expect(2)?;
+                    }
+                    let medium = gray_medium(args[0], args[1]);
+                    fog = Some(match args[..] {
+                        [_, _, height, falloff] => Fog::Height {
+                            medium,
+                            height,
+                            falloff,
+                        },
+                        _ => Fog::Homogeneous(medium),
+                    });
+                }
+                "volume" => {
+                    expect(2)?;
+                    if objects.is_empty() {
+                        return Err(format!(
+                            "line {}: `volume` used before any `sphere`",
+                            line_ind + 1
+                        ));
+                    }
+                    if volumes
+                        .last()
+                        .is_some_and(|&(ind, _)| ind == objects.len() - 1)
+                    {
+                        return Err(format!(
+                            "line {}: sphere is already a `volume`",
+                            line_ind + 1
+                        ));
+                    }
+                    volumes.push((objects.len() - 1, gray_medium(args[0], args[1])));
+                }
+                "sphere" => {
+                    expect(0)?;
+                    objects.push(Sphere::default());
+                }
+                _ => {
+                    let object = objects.last_mut().ok_or(format!(
+                        "line {}: `{}` used before any `sphere`",
+                        line_ind + 1,
+                        keyword
+                    ))?;
+                    let transform = match keyword {
+                        "translate" => {
+                            expect(3)?;
+                            Some(Matrix::get_translation_matrix(args[0], args[1], args[2]))
+                        }
+                        "scale" => {
+                            expect(3)?;
+                            Some(Matrix::get_scaling_matrix(args[0], args[1], args[2]))
+                        }
+                        "rotate_x" => {
+                            expect(1)?;
+                            Some(Matrix::get_rotation_x_matrix(args[0]))
+                        }
+                        "rotate_y" => {
+                            expect(1)?;
+                            Some(Matrix::get_rotation_y_matrix(args[0]))
+                        }
+                        "rotate_z" => {
+                            expect(1)?;
+                            Some(Matrix::get_rotation_z_matrix(args[0]))
+                        }
+                        "rotate" => {
+                            expect(4)?;
+                            let axis = rotation_axis(args[0], args[1], args[2])?;
+                            Some(Matrix::get_rotation_matrix(axis, args[3]))
+                        }
+                        "shear" => {
+                            expect(6)?;
+                            Some(Matrix::get_shearing_matrix(
+                                args[0], args[1], args[2], args[3], args[4], args[5],
+                            ))
+                        }
+                        _ => None,
+                    };
+                    if let Some(transform) = transform {
+                        let transform = transform * object.transform();
+                        if !transform.is_invertible() {
+                            return Err(format!("line {}: singular transform", line_ind + 1));
+                        }
+                        object.set_transform(transform);
+                        continue;
+                    }
+                    if keyword == "motion" {
+                        if args.len() != 9 {
+                            expect(5)?;
+                        }
+                        let mut end = Keyframe::identity(args[1]);
+                        end.translation = vector(args[2], args[3], args[4]);
+                        if let [_, _, _, _, _, x, y, z, r] = args[..] {
+                            let axis = rotation_axis(x, y, z)?;
+                            end.rotation = Quaternion::from_axis_angle(axis, r);
+                        }
+                        object.motion = Some(Motion::new(Keyframe::identity(args[0]), end));
+                        continue;
+                    }
+
+                    let material = &mut object.material;
+                    match keyword {
+                        "color" => {
+                            expect(3)?;
+                            material.color = Color::new(args[0], args[1], args[2]);
+                        }
+                        "ambient" => {
+                            expect(1)?;
+                            material.ambient = args[0];
+                        }
+                        "diffuse" => {
+                            expect(1)?;
+                            material.diffuse = args[0];
+                        }
+                        "specular" => {
+                            expect(1)?;
+                            material.specular = args[0];
+                        }
+                        "shininess" => {
+                            expect(1)?;
+                            material.shininess = args[0];
+                        }
+                        "emission" => {
+                            expect(3)?;
+                            material.emission = Color::new(args[0], args[1], args[2]);
+                        }
+                        "transparency" => {
+                            expect(1)?;
+                            material.transparency = args[0];
+                        }
+                        "casts_shadow" => {
+                            expect(1)?;
+                            material.casts_shadow = args[0] != 0.0;
+                        }
+                        "receives_shadow" => {
+                            expect(1)?;
+                            material.receives_shadow = args[0] != 0.0;
+                        }
+                        "metallic" => {
+                            expect(1)?;
+                            material.pbr.get_or_insert_with(Pbr::default).metallic = args[0];
+                        }
+                        "roughness" => {
+                            expect(1)?;
+                            material.pbr.get_or_insert_with(Pbr::default).roughness = args[0];
+                        }
+                        "marble" | "wood" | "clouds" => {
+                            if args.len() != 7 {
+                                expect(6)?;
+                            }
+                            let a = Color::new(args[0], args[1], args[2]);
+                            let b = Color::new(args[3], args[4], args[5]);
+                            let seed = args.get(6).map_or(0, |&seed| seed as u64);
+                            material.pattern = Some(match keyword {
+                                "marble" => Pattern::marble(a, b, seed),
+                                "wood" => Pattern::wood(a, b, seed),
+                                _ => Pattern::clouds(a, b, seed),
+                            });
+                        }
+                        "bump" => {
+                            if args.len() != 3 {
+                                expect(2)?;
+                            }
+                            if args[1] <= 0.0 {
+                                return Err(format!(
+                                    "line {}: bump size must be positive",
+                                    line_ind + 1
+                                ));
+                            }
+                            // Height of clouds noise, with features of about `args[1]` units
+                            let seed = args.get(2).map_or(0, |&seed| seed as u64);
+                            let mut pattern = Pattern::clouds(Color::black(), Color::white(), seed);
+                            pattern.set_transform(Matrix::get_scaling_matrix(
+                                args[1], args[1], args[1],
+                            ));
+                            material.bump = Some(Bump::height(pattern, args[0]));
+                        }
+                        "jitter" => {
+                            if args.len() != 2 {
+                                expect(1)?;
+                            }
+                            let pattern = material.pattern.as_mut().ok_or(format!(
+                                "line {}: `jitter` used before any pattern",
+                                line_ind + 1
+                            ))?;
+                            pattern.set_jitter(args[0], args.get(1).map_or(0, |&seed| seed as u64));
+                        }
+                        _ => {
+                            return Err(format!(
+                                "line {}: unknown statement `{}`",
+                                line_ind + 1,
+                                keyword
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut camera = camera.ok_or("Scene has no `camera`")?;
+        camera.set_transform(view);
+        (camera.shutter_open, camera.shutter_close) = shutter;
+        let light = light.ok_or("Scene has no `light`")?;
+        // Volumes keep the transformation of their sphere, and are not drawn as spheres
+        let volumes: Vec<Volume> = volumes
+            .into_iter()
+            .rev()
+            .map(|(ind, medium)| Volume::new(objects.remove(ind).transform(), medium))
+            .rev()
+            .collect();
+        let mut world = World::new(light);
+        world.background = background;
+        world.textures = textures;
+        world.fog = fog;
+        world.volumes = volumes;
+        for object in objects {
+            world.add_object(object);
+        }
+        Ok(Scene { camera, world })
+    }
+}
+
+// Medium with the same coefficients for red, green and blue
+fn gray_medium(absorption: Float, scattering: Float) -> Medium {
+    Medium::new(
+        Color::new(absorption, absorption, absorption),
+        Color::new(scattering, scattering, scattering),
+    )
+}
+
+// Parse a number, which can also be a multiple or fraction of pi like `pi`, `-pi/2`, `2pi`
+// Numbers are finite, `inf` and `nan` (and divisions by zero) are not numbers here
+fn parse_number(token: &str) -> Result<Float, String> {
+    let error = || format!("`{}` is not a number", token);
+    if let Ok(value) = token.parse::<Float>() {
+        return Some(value)
+            .filter(|value| value.is_finite())
+            .ok_or_else(error);
+    }
+    let (numerator, denominator) = match token.split_once('/') {
+        Some((numerator, denominator)) => (
+            numerator,
+            denominator.parse::<Float>().map_err(|_| error())?,
+        ),
+        None => (token, 1.0),
+    };
+    let factor = match numerator.strip_suffix("pi").ok_or_else(error)? {
+        "" => 1.0,
+        "-" => -1.0,
+        factor => factor.parse::<Float>().map_err(|_| error())?,
+    };
+    Some(factor * PI / denominator)
+        .filter(|value| value.is_finite())
+        .ok_or_else(error)
+}
+
+#[cfg(test)]
+mod scene_tests {
+    use super::*;
+    use crate::consts::FRAC_PI_3;
+    use crate::Compare;
+
+    #[test]
+    fn parsing_numbers() {
+        assert_eq!(parse_number("1.5"), Ok(1.5));
+        assert_eq!(parse_number("-2"), Ok(-2.0));
+        assert_eq!(parse_number("pi"), Ok(PI));
+        assert_eq!(parse_number("-pi/2"), Ok(-PI / 2.0));
+        assert_eq!(parse_number("2pi/3"), Ok(2.0 * PI / 3.0));
+        assert!(parse_number("abc").is_err());
+        assert!(parse_number("pi/x").is_err());
+        assert!(parse_number("inf").is_err());
+        assert!(parse_number("nan").is_err());
+        assert!(parse_number("pi/0").is_err());
+    }
+
+    #[test]
+    fn parsing_a_scene() {
+        let source = "
+            # A small scene
+            camera 100 50 pi/3
+            shutter 0 0.5
+            view 0 1.5 -5  0 1 0  0 1 0
+            light -10 10 -10 1 1 1
+            gradient 0 0 0  0.5 0.7 1
+
+            sphere
+              scale 0.5 0.5 0.5
+              translate 1.5 0.5 -0.5 # applied after scaling
+              color 0.5 1 0.1
+              diffuse 0.7
+              transparency 0.25
+              motion 0 1  0 1 0
+
+            sphere
+              metallic 1
+              emission 1 0.5 0
+              casts_shadow 0
+              marble 1 1 1  0.1 0.1 0.1  7
+              jitter 0.5
+              bump 0.1 0.5 3
+
+            fog 0 0.1  0 2
+            sphere
+              scale 2 2 2
+              volume 0.5 0.2
+        ";
+        let scene = Scene::parse(source).unwrap();
+        assert_eq!(scene.camera.hsize(), 100);
+        assert_eq!(scene.camera.vsize(), 50);
+        assert!(scene.camera.field_of_view().eq(FRAC_PI_3));
+        assert_eq!(
+            scene.camera.transform(),
+            Matrix::get_view_transform(
+                point(0.0, 1.5, -5.0),
+                point(0.0, 1.0, 0.0),
+                vector(0.0, 1.0, 0.0)
+            )
+        );
+        assert_eq!(
+            scene.world.light,
+            Light::new(point(-10.0, 10.0, -10.0), Color::white())
+        );
+        assert_eq!(
+            scene.world.background.color(vector(0.0, 1.0, 0.0)),
+            Color::new(0.5, 0.7, 1.0)
+        );
+        assert_eq!(scene.world.objects().len(), 2);
+        // Objects are numbered in order, the same every time the scene is parsed
+        let ids = |scene: &Scene| {
+            scene
+                .world
+                .objects()
+                .iter()
+                .map(Sphere::id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&scene), vec![1, 2]);
+        assert_eq!(ids(&Scene::parse(source).unwrap()), ids(&scene));
+        assert_eq!(
+            scene.world.fog,
+            Some(Fog::Height {
+                medium: gray_medium(0.0, 0.1),
+                height: 0.0,
+                falloff: 2.0
+            })
+        );
+        assert_eq!(
+            scene.world.volumes,
+            vec![Volume::new(
+                Matrix::get_scaling_matrix(2.0, 2.0, 2.0),
+                gray_medium(0.5, 0.2)
+            )]
+        );
+        let sphere = scene.world.objects()[0];
+        assert_eq!(
+            sphere.transform(),
+            Matrix::get_translation_matrix(1.5, 0.5, -0.5)
+                * Matrix::get_scaling_matrix(0.5, 0.5, 0.5)
+        );
+        assert_eq!(sphere.material.color, Color::new(0.5, 1.0, 0.1));
+        assert_eq!(sphere.material.diffuse, 0.7);
+        assert_eq!(
+            sphere.motion,
+            Some(Motion::linear(0.0, 1.0, vector(0.0, 1.0, 0.0)))
+        );
+        assert_eq!(scene.world.objects()[1].motion, None);
+        assert_eq!(
+            (scene.camera.shutter_open, scene.camera.shutter_close),
+            (0.0, 0.5)
+        );
+        assert_eq!(sphere.material.specular, 0.9);
+        assert_eq!(sphere.material.pbr, None);
+        assert!(sphere.material.casts_shadow);
+        assert_eq!(sphere.material.transparency, 0.25);
+        assert!(!scene.world.objects()[1].material.casts_shadow);
+        assert!(scene.world.objects()[1].material.receives_shadow);
+        assert_eq!(
+            scene.world.objects()[1].material.pbr,
+            Some(Pbr::new(1.0, 0.5))
+        );
+        assert_eq!(
+            scene.world.objects()[1].material.emission,
+            Color::new(1.0, 0.5, 0.0)
+        );
+        let mut marble = Pattern::marble(Color::white(), Color::new(0.1, 0.1, 0.1), 7);
+        marble.set_jitter(0.5, 0);
+        assert_eq!(scene.world.objects()[1].material.pattern, Some(marble));
+        let mut bumps = Pattern::clouds(Color::black(), Color::white(), 3);
+        bumps.set_transform(Matrix::get_scaling_matrix(0.5, 0.5, 0.5));
+        assert_eq!(
+            scene.world.objects()[1].material.bump,
+            Some(Bump::height(bumps, 0.1))
+        );
+    }
+
+    #[test]
+    fn parsing_errors_report_line() {
+        let source = "camera 10 10 1\nlight 0 0 0 1 1 1\ntranslate 1 2 3";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.starts_with("line 3:"));
+
+        let source = "camera 10 10\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.starts_with("line 1:"));
+
+        let source = "camera 10 10 1\nsphere\nglow 1\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.contains("unknown statement `glow`"));
+
+        let source = "camera 10 10 1\nsphere\ntexture earth.png spherical wrap\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.contains("unknown texture option `wrap`"));
+
+        let source = "camera 10 10 1\nsphere\ntexture earth.png 2\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.contains("unknown texture option `2`"));
+
+        let source = "camera 10 10 1\nsphere\njitter 1\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.contains("`jitter` used before any pattern"));
+
+        let source = "camera 10 10 1\nenvironment does/not/exist.hdr\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert!(err.starts_with("line 2: Unable to read hdr image"));
+
+        let source = "camera 10 10 1\nsphere\nscale 1 2 1\nscale 0 1 1\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 4: singular transform");
+
+        let source = "camera 10 10 1\nsphere\nvolume 1 0.5\nsphere\nvolume 1 0.5\nvolume 1 0.5\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 6: sphere is already a `volume`");
+
+        let source = "camera 10 10 1\nsphere\nmotion 0 1 0 0 0 0 0 0 1\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 3: rotation axis must not be zero");
+
+        for camera in ["camera -1 10 1", "camera 10 70000 1", "camera 10.5 10 1"] {
+            let err = Scene::parse(camera).err().unwrap();
+            assert_eq!(
+                err,
+                "line 1: camera size must be a whole number from 1 to 65535"
+            );
+        }
+        for camera in ["camera 10 10 0", "camera 10 10 -1", "camera 10 10 pi"] {
+            let err = Scene::parse(camera).err().unwrap();
+            assert_eq!(err, "line 1: field of view must be between 0 and pi");
+        }
+        let err = Scene::parse("camera 10 10 nan").err().unwrap();
+        assert_eq!(err, "line 1: `nan` is not a number");
+
+        let source = "camera 10 10 1\nsphere\nbump 0.1 0\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 3: bump size must be positive");
+
+        let source = "camera 10 10 1\nsphere\nrotate 0 0 0 1\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 3: rotation axis must not be zero");
+
+        // Looking at the eye itself, or along the up vector
+        let source = "camera 10 10 1\nview 0 1 -5 0 1 -5 0 1 0\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 2: singular transform");
+        let source = "camera 10 10 1\nview 0 1 -5 0 3 -5 0 1 0\n";
+        let err = Scene::parse(source).err().unwrap();
+        assert_eq!(err, "line 2: singular transform");
+    }
+
+    #[test]
+    fn scene_requires_camera_and_light() {
+        assert!(Scene::parse("light 0 0 0 1 1 1").is_err());
+        assert!(Scene::parse("camera 10 10 1").is_err());
+    }
+}
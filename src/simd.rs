@@ -0,0 +1,309 @@
+// Vectorised versions of the hot math operations (dot, cross, matrix * tuple, matrix * matrix)
+//
+// `Tuple` and the rows of `Matrix` are laid out as 4 consecutive aligned floats, so they can be
+// loaded directly into a single SIMD register-
+// * f32 uses SSE (`__m128`), which every x86_64 cpu has
+// * f64 uses AVX2 (`__m256d`), build with `RUSTFLAGS="-C target-cpu=native"` to enable it
+// Everything else falls back to the `scalar` versions, which are also used by the benchmark
+use crate::Float;
+use crate::Matrix;
+use crate::Tuple;
+use std::mem::{align_of, size_of};
+
+// The loads and stores below read and write whole `Tuple`s and `Matrix` rows, check that the
+// layout they rely on still holds
+const _: () = assert!(size_of::<Tuple>() == 4 * size_of::<Float>());
+const _: () = assert!(align_of::<Tuple>() == size_of::<Tuple>());
+const _: () = assert!(size_of::<Matrix>() == 4 * size_of::<Tuple>());
+const _: () = assert!(align_of::<Matrix>() == size_of::<Tuple>());
+
+// Plain loops, used when no SIMD path is available for the target and precision
+pub mod scalar {
+    use super::*;
+
+    #[inline]
+    pub fn dot(a: &Tuple, b: &Tuple) -> Float {
+        a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w
+    }
+
+    #[inline]
+    pub fn cross(a: &Tuple, b: &Tuple) -> Tuple {
+        Tuple::new(
+            a.y * b.z - a.z * b.y,
+            a.z * b.x - a.x * b.z,
+            a.x * b.y - a.y * b.x,
+            0.0, // It still is a vector
+        )
+    }
+
+    #[inline]
+    pub fn mul_matrix_tuple(m: &Matrix, t: &Tuple) -> Tuple {
+        let row = |i: usize| m[i][0] * t.x + m[i][1] * t.y + m[i][2] * t.z + m[i][3] * t.w;
+        Tuple::new(row(0), row(1), row(2), row(3))
+    }
+
+    #[inline]
+    pub fn mul_matrix_matrix(a: &Matrix, b: &Matrix) -> Matrix {
+        let mut result: Matrix = Default::default();
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..4 {
+                    result[i][j] += a[i][k] * b[k][j];
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+mod sse {
+    use super::*;
+    use std::arch::x86_64::*;
+    use std::ptr;
+
+    // SAFETY of all the functions here: SSE is part of the x86_64 baseline and `Tuple` and
+    // the rows of `Matrix` are `repr(C)` arrays of 4 f32 aligned to 16 bytes. The pointers are
+    // taken from the whole `Tuple`, a pointer to one of its fields may only access that field
+
+    #[inline]
+    unsafe fn load(t: &Tuple) -> __m128 {
+        _mm_load_ps(t as *const Tuple as *const Float)
+    }
+
+    #[inline]
+    unsafe fn load_row(m: &Matrix, row: usize) -> __m128 {
+        _mm_load_ps(m[row].as_ptr())
+    }
+
+    #[inline]
+    unsafe fn to_tuple(v: __m128) -> Tuple {
+        let mut t = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        _mm_store_ps(ptr::addr_of_mut!(t) as *mut Float, v);
+        t
+    }
+
+    #[inline]
+    pub fn dot(a: &Tuple, b: &Tuple) -> Float {
+        unsafe {
+            let m = _mm_mul_ps(load(a), load(b));
+            let swapped = _mm_shuffle_ps::<0b10_11_00_01>(m, m); // y x w z
+            let sums = _mm_add_ps(m, swapped); // x+y x+y z+w z+w
+            let high = _mm_movehl_ps(swapped, sums); // z+w
+            _mm_cvtss_f32(_mm_add_ss(sums, high))
+        }
+    }
+
+    #[inline]
+    pub fn cross(a: &Tuple, b: &Tuple) -> Tuple {
+        unsafe {
+            let (a, b) = (load(a), load(b));
+            let a_yzx = _mm_shuffle_ps::<0b11_00_10_01>(a, a);
+            let b_yzx = _mm_shuffle_ps::<0b11_00_10_01>(b, b);
+            let a_zxy = _mm_shuffle_ps::<0b11_01_00_10>(a, a);
+            let b_zxy = _mm_shuffle_ps::<0b11_01_00_10>(b, b);
+            let mut result = to_tuple(_mm_sub_ps(
+                _mm_mul_ps(a_yzx, b_zxy),
+                _mm_mul_ps(a_zxy, b_yzx),
+            ));
+            result.w = 0.0; // It still is a vector
+            result
+        }
+    }
+
+    #[inline]
+    pub fn mul_matrix_tuple(m: &Matrix, t: &Tuple) -> Tuple {
+        unsafe {
+            let t = load(t);
+            let r0 = _mm_mul_ps(load_row(m, 0), t);
+            let r1 = _mm_mul_ps(load_row(m, 1), t);
+            let r2 = _mm_mul_ps(load_row(m, 2), t);
+            let r3 = _mm_mul_ps(load_row(m, 3), t);
+            // Transpose so that each register holds one column of the products, then sum them up
+            let t0 = _mm_unpacklo_ps(r0, r1);
+            let t1 = _mm_unpackhi_ps(r0, r1);
+            let t2 = _mm_unpacklo_ps(r2, r3);
+            let t3 = _mm_unpackhi_ps(r2, r3);
+            let c0 = _mm_movelh_ps(t0, t2);
+            let c1 = _mm_movehl_ps(t2, t0);
+            let c2 = _mm_movelh_ps(t1, t3);
+            let c3 = _mm_movehl_ps(t3, t1);
+            to_tuple(_mm_add_ps(_mm_add_ps(c0, c1), _mm_add_ps(c2, c3)))
+        }
+    }
+
+    #[inline]
+    pub fn mul_matrix_matrix(a: &Matrix, b: &Matrix) -> Matrix {
+        let mut result: Matrix = Default::default();
+        unsafe {
+            let rows = [
+                load_row(b, 0),
+                load_row(b, 1),
+                load_row(b, 2),
+                load_row(b, 3),
+            ];
+            for i in 0..4 {
+                // Row i of the result is the sum of the rows of `b` weighted by row i of `a`
+                let mut sum = _mm_setzero_ps();
+                for (k, row) in rows.iter().enumerate() {
+                    sum = _mm_add_ps(sum, _mm_mul_ps(_mm_set1_ps(a[i][k]), *row));
+                }
+                _mm_store_ps(result[i].as_mut_ptr(), sum);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2", feature = "f64"))]
+mod avx {
+    use super::*;
+    use std::arch::x86_64::*;
+    use std::ptr;
+
+    // SAFETY of all the functions here: they are only compiled when AVX2 is enabled and `Tuple`
+    // and the rows of `Matrix` are `repr(C)` arrays of 4 f64 aligned to 32 bytes. The pointers
+    // are taken from the whole `Tuple`, a pointer to one of its fields may only access that field
+
+    #[inline]
+    unsafe fn load(t: &Tuple) -> __m256d {
+        _mm256_load_pd(t as *const Tuple as *const Float)
+    }
+
+    #[inline]
+    unsafe fn load_row(m: &Matrix, row: usize) -> __m256d {
+        _mm256_load_pd(m[row].as_ptr())
+    }
+
+    #[inline]
+    unsafe fn to_tuple(v: __m256d) -> Tuple {
+        let mut t = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        _mm256_store_pd(ptr::addr_of_mut!(t) as *mut Float, v);
+        t
+    }
+
+    #[inline]
+    pub fn dot(a: &Tuple, b: &Tuple) -> Float {
+        unsafe {
+            let m = _mm256_mul_pd(load(a), load(b));
+            let sums = _mm_add_pd(_mm256_castpd256_pd128(m), _mm256_extractf128_pd::<1>(m)); // x+z y+w
+            _mm_cvtsd_f64(_mm_add_sd(sums, _mm_unpackhi_pd(sums, sums)))
+        }
+    }
+
+    #[inline]
+    pub fn cross(a: &Tuple, b: &Tuple) -> Tuple {
+        unsafe {
+            let (a, b) = (load(a), load(b));
+            let a_yzx = _mm256_permute4x64_pd::<0b11_00_10_01>(a);
+            let b_yzx = _mm256_permute4x64_pd::<0b11_00_10_01>(b);
+            let a_zxy = _mm256_permute4x64_pd::<0b11_01_00_10>(a);
+            let b_zxy = _mm256_permute4x64_pd::<0b11_01_00_10>(b);
+            let mut result = to_tuple(_mm256_sub_pd(
+                _mm256_mul_pd(a_yzx, b_zxy),
+                _mm256_mul_pd(a_zxy, b_yzx),
+            ));
+            result.w = 0.0; // It still is a vector
+            result
+        }
+    }
+
+    #[inline]
+    pub fn mul_matrix_tuple(m: &Matrix, t: &Tuple) -> Tuple {
+        unsafe {
+            let t = load(t);
+            let r0 = _mm256_mul_pd(load_row(m, 0), t);
+            let r1 = _mm256_mul_pd(load_row(m, 1), t);
+            let r2 = _mm256_mul_pd(load_row(m, 2), t);
+            let r3 = _mm256_mul_pd(load_row(m, 3), t);
+            // Pairwise sums of every row, then add the two halves of each row together
+            let h01 = _mm256_hadd_pd(r0, r1); // r0xy r1xy r0zw r1zw
+            let h23 = _mm256_hadd_pd(r2, r3); // r2xy r3xy r2zw r3zw
+            let crossed = _mm256_permute2f128_pd::<0x21>(h01, h23); // r0zw r1zw r2xy r3xy
+            let blended = _mm256_blend_pd::<0b1100>(h01, h23); // r0xy r1xy r2zw r3zw
+            to_tuple(_mm256_add_pd(crossed, blended))
+        }
+    }
+
+    #[inline]
+    pub fn mul_matrix_matrix(a: &Matrix, b: &Matrix) -> Matrix {
+        let mut result: Matrix = Default::default();
+        unsafe {
+            let rows = [
+                load_row(b, 0),
+                load_row(b, 1),
+                load_row(b, 2),
+                load_row(b, 3),
+            ];
+            for i in 0..4 {
+                // Row i of the result is the sum of the rows of `b` weighted by row i of `a`
+                let mut sum = _mm256_setzero_pd();
+                for (k, row) in rows.iter().enumerate() {
+                    sum = _mm256_add_pd(sum, _mm256_mul_pd(_mm256_set1_pd(a[i][k]), *row));
+                }
+                _mm256_store_pd(result[i].as_mut_ptr(), sum);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+pub use sse::{cross, dot, mul_matrix_matrix, mul_matrix_tuple};
+
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2", feature = "f64"))]
+pub use avx::{cross, dot, mul_matrix_matrix, mul_matrix_tuple};
+
+#[cfg(not(any(
+    all(target_arch = "x86_64", not(feature = "f64")),
+    all(target_arch = "x86_64", target_feature = "avx2", feature = "f64")
+)))]
+pub use scalar::{cross, dot, mul_matrix_matrix, mul_matrix_tuple};
+
+#[cfg(test)]
+mod simd_tests {
+    use super::*;
+    use crate::{point, vector, Compare};
+
+    fn sample_matrix() -> Matrix {
+        let mut m = Matrix::new();
+        let values = [
+            1.0, 2.0, 3.0, 4.0, 5.5, -6.5, 7.5, 8.5, 9.0, 10.0, -11.0, 12.0, 13.5, 14.5, 15.5, 16.5,
+        ];
+        for (ind, value) in values.iter().enumerate() {
+            m[ind / 4][ind % 4] = *value;
+        }
+        m
+    }
+
+    #[test]
+    fn dot_matches_scalar() {
+        let a = Tuple::new(1.5, -2.0, 3.25, 1.0);
+        let b = Tuple::new(-4.0, 0.5, 2.0, 0.0);
+        assert!(dot(&a, &b).eq(scalar::dot(&a, &b)));
+        assert!(dot(&a, &b).eq(-0.5));
+    }
+
+    #[test]
+    fn cross_matches_scalar() {
+        let a = Tuple::from(vector(1.0, 2.0, 3.0));
+        let b = Tuple::from(vector(2.0, 3.0, 4.0));
+        assert_eq!(cross(&a, &b), scalar::cross(&a, &b));
+        assert_eq!(cross(&a, &b), Tuple::from(vector(-1.0, 2.0, -1.0)));
+        assert_eq!(cross(&a, &b).w, 0.0);
+    }
+
+    #[test]
+    fn matrix_tuple_multiplication_matches_scalar() {
+        let m = sample_matrix();
+        let p = Tuple::from(point(1.0, -2.0, 3.0));
+        assert_eq!(mul_matrix_tuple(&m, &p), scalar::mul_matrix_tuple(&m, &p));
+    }
+
+    #[test]
+    fn matrix_matrix_multiplication_matches_scalar() {
+        let a = sample_matrix();
+        let b = sample_matrix().transpose();
+        assert_eq!(mul_matrix_matrix(&a, &b), scalar::mul_matrix_matrix(&a, &b));
+    }
+}
@@ -1,64 +1,183 @@
+use crate::consts::PI;
+use crate::Float;
 use crate::Material;
+use crate::Motion;
 use crate::Ray;
-use crate::{point, Matrix, Tuple};
+use crate::{point, vector, Matrix, Point3, Vector3};
 use crate::{Intersection, Intersections};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug)]
 pub struct Sphere {
-    // TODO: add `id` to it as described in the book
-    center: Tuple,
-    radius: f32,
-    transform: Matrix,  // Transformation matrix
-    pub material: Material, // Material of the sphere
+    id: u64, // Given by the world when the sphere is added to it (`World::add_object`), 0 before
+    center: Point3,
+    radius: Float,
+    transform: Matrix,          // Transformation matrix
+    inverse_transform: Matrix,  // Cached inverse of `transform`, used for every ray
+    inverse_transpose: Matrix, // Cached transpose of `inverse_transform`, used for transforming normals
+    pub material: Material,    // Material of the sphere
+    pub motion: Option<Motion>, // Movement on top of `transform` while the shutter is open
 }
 
 impl Sphere {
-    pub fn new(center: Tuple, radius: f32, transform: Matrix, material: Material) -> Self {
+    pub fn new(center: Point3, radius: Float, transform: Matrix, material: Material) -> Self {
+        let inverse_transform = transform.inverse();
         Self {
+            id: 0,
             center,
             radius,
             transform,
+            inverse_transform,
+            inverse_transpose: inverse_transform.transpose(),
             material,
+            motion: None,
+        }
+    }
+
+    // Identifies the sphere in its world, so that two spheres with the same shape and material
+    // can be told apart. Copies of the sphere, and the sphere seen at another time, keep it
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    // Whether `other` is this object of the world, maybe at another time
+    // Spheres without an id are compared by what they are made of
+    pub fn is(&self, other: &Sphere) -> bool {
+        if self.id == 0 || other.id == 0 {
+            self == other
+        } else {
+            self.id == other.id
+        }
+    }
+
+    // The sphere as it is at `time`, standing still with its motion baked into the transformation
+    pub fn at_time(&self, time: Float) -> Self {
+        match self.motion {
+            Some(motion) => {
+                let mut sphere = *self;
+                sphere.motion = None;
+                sphere.set_transform(motion.transform_at(time) * self.transform);
+                sphere
+            }
+            None => *self,
         }
     }
 
     // Returns the time at which the `ray` intersects the sphere
+    // A moving sphere is intersected where it is at the time of the ray, and the intersections
+    // hold that still copy so the normal and the texture are found at the same place
     pub fn intersect(&self, ray: Ray) -> Intersections {
+        if self.motion.is_some() {
+            return self.at_time(ray.time).intersect(ray);
+        }
+        let mut intersections: Intersections = Default::default();
+        if let Some((t1, t2)) = self.roots(ray) {
+            intersections.push(Intersection::new(t1, *self));
+            intersections.push(Intersection::new(t2, *self));
+        }
+        intersections
+    }
+
+    // Whether `ray` hits the sphere in front of its origin and before `distance`
+    // Same as looking for a hit in `intersect`, without building the intersections
+    pub fn hits_before(&self, ray: Ray, distance: Float) -> bool {
+        self.crossings_before(ray, distance) > 0
+    }
+
+    // How many times (0, 1 or 2) `ray` goes through the surface between its origin and `distance`
+    pub fn crossings_before(&self, ray: Ray, distance: Float) -> usize {
+        if self.motion.is_some() {
+            return self.at_time(ray.time).crossings_before(ray, distance);
+        }
+        match self.roots(ray) {
+            Some((t1, t2)) => [t1, t2]
+                .into_iter()
+                .filter(|&t| t > 0.0 && t < distance)
+                .count(),
+            None => 0,
+        }
+    }
+
+    // Times at which the `ray` enters and leaves the sphere, `None` when it misses
+    fn roots(&self, ray: Ray) -> Option<(Float, Float)> {
         // Transform the ray to the object space coordinates of the sphere
         // This means applying inverse transformation of the sphere to the ray
-        let ray = ray.transform(self.transform.inverse());
+        let ray = ray.transform(self.inverse_transform);
 
         let sphere_to_ray = ray.origin - self.center;
         let a = ray.direction.dot(&ray.direction);
         let b = 2.0 * ray.direction.dot(&sphere_to_ray);
         let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
         let discriminant = b * b - 4.0 * a * c;
-        let mut intersections: Intersections = Default::default();
 
-        if discriminant >= 0.0 {
-            let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
-            let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
-            intersections.push(Intersection::new(t1, *self));
-            intersections.push(Intersection::new(t2, *self));
+        if discriminant < 0.0 {
+            return None;
         }
-        intersections
+        let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
+        let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
+        Some((t1, t2))
     }
 
+    // Inverse and its transpose are computed here once instead of for every ray
     pub fn set_transform(&mut self, t: Matrix) {
         self.transform = t;
+        self.inverse_transform = t.inverse();
+        self.inverse_transpose = self.inverse_transform.transpose();
+    }
+
+    pub fn transform(&self) -> Matrix {
+        self.transform
+    }
+
+    // Transform the world point `p` to the object space of the sphere
+    pub fn world_to_object(&self, p: Point3) -> Point3 {
+        self.inverse_transform * p
     }
 
     // Find normal of the sphere at world point `p`
     // Basically transform the point to the object space, find normal at that point
     // and then, transform it back to the world space
     // Derivation is given in the chapter 6 README
-    pub fn normal_at(&self, p: Tuple) -> Tuple {
-        let object_point = self.transform.inverse() * p;
+    // Multiplying a `Vector3` ignores the bottom row of the inverse transpose, so no need to reset w
+    pub fn normal_at(&self, p: Point3) -> Vector3 {
+        let object_point = self.inverse_transform * p;
         let object_normal = object_point - point(0.0, 0.0, 0.0);
-        let mut world_normal = self.transform.inverse().transpose() * object_normal;
-        world_normal.w = 0.0;
+        let world_normal = self.inverse_transpose * object_normal;
         world_normal.normalize()
     }
+
+    // Point on the surface picked from `u` and `v` (both between 0 and 1) uniformly over the
+    // untransformed sphere, together with the normal there and the probability density of picking
+    // it per unit of world area. Used to sample emissive spheres as area lights
+    pub fn sample_surface(&self, u: Float, v: Float) -> (Point3, Vector3, Float) {
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let object_normal = vector(r * phi.cos(), r * phi.sin(), z);
+        let world_point = self.transform * (point(0.0, 0.0, 0.0) + object_normal);
+        let world_normal = self.inverse_transpose * object_normal;
+        // A small patch of the surface grows by |det| * |M^-T n| when transformed (Nanson's formula)
+        let area_scale = self.transform.determinant().abs() * world_normal.magnitude();
+        (
+            world_point,
+            world_normal.normalize(),
+            1.0 / (4.0 * PI * area_scale),
+        )
+    }
+}
+
+// Same shape, place and material, whatever the ids
+impl PartialEq for Sphere {
+    fn eq(&self, other: &Self) -> bool {
+        self.center == other.center
+            && self.radius == other.radius
+            && self.transform == other.transform
+            && self.material == other.material
+            && self.motion == other.motion
+    }
 }
 
 impl Default for Sphere {
@@ -77,8 +196,8 @@ impl Default for Sphere {
 #[cfg(test)]
 mod sphere_tests {
     use super::*;
-    use crate::vector;
-    use std::f32::consts::{FRAC_1_SQRT_2, PI};
+    use crate::consts::FRAC_1_SQRT_2;
+    use crate::Compare;
 
     #[test]
     fn sphere_ray_intersection1() {
@@ -138,6 +257,37 @@ mod sphere_tests {
         assert_eq!(xs[1].object, Sphere::default());
     }
 
+    #[test]
+    fn hits_before_a_distance() {
+        let s = Sphere::default();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(s.hits_before(r, 4.5));
+        assert!(!s.hits_before(r, 3.5));
+        // From inside only the exit counts, and nothing behind the origin
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        assert!(s.hits_before(r, 2.0));
+        let r = Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
+        assert!(!s.hits_before(r, 100.0));
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(s.crossings_before(r, 5.0), 1);
+        assert_eq!(s.crossings_before(r, 7.0), 2);
+    }
+
+    #[test]
+    fn sphere_identity() {
+        let (mut a, mut b) = (Sphere::default(), Sphere::default());
+        assert_eq!(a.id(), 0);
+        // Without ids the same spheres are the same object
+        assert!(a.is(&b));
+        a.set_id(1);
+        b.set_id(2);
+        assert!(!a.is(&b));
+        // The sphere seen at another time is still the same object
+        a.motion = Some(Motion::linear(0.0, 1.0, vector(1.0, 0.0, 0.0)));
+        assert!(a.at_time(0.5).is(&a));
+        assert_eq!(a.at_time(0.5).id(), 1);
+    }
+
     #[test]
     fn check_sphere_default_transformation() {
         let s: Sphere = Default::default();
@@ -152,6 +302,16 @@ mod sphere_tests {
         assert_eq!(s.transform, t);
     }
 
+    #[test]
+    fn set_transform_caches_inverse() {
+        let mut s: Sphere = Default::default();
+        let t = Matrix::get_translation_matrix(2.0, 3.0, 4.0)
+            * Matrix::get_scaling_matrix(1.0, 2.0, 3.0);
+        s.set_transform(t);
+        assert_eq!(s.inverse_transform, t.inverse());
+        assert_eq!(s.inverse_transpose, t.inverse().transpose());
+    }
+
     #[test]
     fn ray_intersect_scaled_sphere() {
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
@@ -196,7 +356,7 @@ mod sphere_tests {
     #[test]
     fn sphere_normal_at_non_axial_point() {
         let s: Sphere = Default::default();
-        let val: f32 = f32::sqrt(3.0) / 3.0;
+        let val: Float = Float::sqrt(3.0) / 3.0;
         let n = s.normal_at(point(val, val, val));
         assert_eq!(n, vector(val, val, val));
     }
@@ -204,7 +364,7 @@ mod sphere_tests {
     #[test]
     fn sphere_normal_is_normalized() {
         let s: Sphere = Default::default();
-        let val: f32 = f32::sqrt(3.0) / 3.0;
+        let val: Float = Float::sqrt(3.0) / 3.0;
         let n = s.normal_at(point(val, val, val));
         assert_eq!(n, n.normalize());
     }
@@ -227,6 +387,46 @@ mod sphere_tests {
         assert_eq!(n, vector(0.0, 0.97014, -0.24254));
     }
 
+    #[test]
+    fn surface_samples_are_on_the_sphere() {
+        let mut s = Sphere::default();
+        s.set_transform(Matrix::get_scaling_matrix(2.0, 1.0, 0.5).translate(1.0, 2.0, 3.0));
+        for (u, v) in [(0.0, 0.0), (0.3, 0.7), (0.5, 0.25), (1.0, 0.9)] {
+            let (p, n, pdf) = s.sample_surface(u, v);
+            assert_eq!(n, s.normal_at(p));
+            assert!(pdf > 0.0);
+            // Back in object space the point is on the unit sphere
+            assert!((s.inverse_transform * p - point(0.0, 0.0, 0.0))
+                .magnitude()
+                .eq(1.0));
+        }
+    }
+
+    #[test]
+    fn surface_sample_density_matches_area() {
+        // Expected value of 1 / pdf is the surface area, 16 pi for a sphere of radius 2
+        let mut s = Sphere::default();
+        s.set_transform(Matrix::get_scaling_matrix(2.0, 2.0, 2.0));
+        let (_, _, pdf) = s.sample_surface(0.4, 0.1);
+        assert!((1.0 / pdf).eq(16.0 * PI));
+
+        // Ellipsoid- average over a grid of samples
+        s.set_transform(Matrix::get_scaling_matrix(1.0, 1.0, 3.0));
+        let steps = 200;
+        let mut area = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let u = (i as Float + 0.5) / steps as Float;
+                let v = (j as Float + 0.5) / steps as Float;
+                area += 1.0 / s.sample_surface(u, v).2 / (steps * steps) as Float;
+            }
+        }
+        // Surface area of the prolate spheroid with a = 1, c = 3
+        let e = (1.0 - 1.0 / 9.0 as Float).sqrt();
+        let expected = 2.0 * PI * (1.0 + 3.0 * e.asin() / e);
+        assert!((area - expected).abs() < 0.01 * expected);
+    }
+
     #[test]
     fn sphere_default_material() {
         let s: Sphere = Default::default();
@@ -244,4 +444,25 @@ mod sphere_tests {
         s.material = m;
         assert_eq!(s.material, m);
     }
+
+    #[test]
+    fn moving_sphere_is_intersected_at_the_time_of_the_ray() {
+        let mut s = Sphere::default();
+        s.set_transform(Matrix::get_scaling_matrix(0.5, 0.5, 0.5));
+        s.motion = Some(Motion::linear(0.0, 1.0, vector(2.0, 0.0, 0.0)));
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(s.intersect(r).len(), 2);
+        assert_eq!(s.intersect(r.at_time(1.0)).len(), 0);
+        // At time 1 the sphere of radius 0.5 is centered at (2, 0, 0)
+        let r = Ray::new(point(2.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)).at_time(1.0);
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert!(xs[0].t.eq(4.5));
+        // and the intersection holds the sphere as it is then
+        assert_eq!(
+            xs[0].object.normal_at(point(2.0, 0.0, -0.5)),
+            vector(0.0, 0.0, -1.0)
+        );
+        assert_eq!(xs[0].object.motion, None);
+    }
 }
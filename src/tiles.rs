@@ -0,0 +1,184 @@
+// Rectangle of pixels of the image, whose top left pixel is (x,y)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tile {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+// Order the tiles of an image are rendered in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    #[default]
+    Scanline, // Rows of tiles from the top, each from left to right
+    Spiral, // Outwards from the tile in the middle, where the subject usually is
+    // Along a Hilbert curve, so the tiles stay close to the ones before them. On a square grid
+    // of tiles with a power of two side every tile touches the one before it, on other grids
+    // the curve of the next larger such grid is cut to the image and jumps where it leaves it
+    Hilbert,
+}
+
+impl Tile {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // Splits the tile into tiles of `size` x `size` pixels, smaller on the right and bottom
+    // borders when `size` does not divide the width or height
+    pub fn split(&self, size: u16, order: TileOrder) -> Vec<Tile> {
+        assert!(size > 0, "Tile size must be positive");
+        let cols = self.width.div_ceil(size) as u32;
+        let rows = self.height.div_ceil(size) as u32;
+        let cells: Vec<(u32, u32)> = match order {
+            TileOrder::Scanline => (0..rows)
+                .flat_map(|row| (0..cols).map(move |col| (col, row)))
+                .collect(),
+            TileOrder::Spiral => spiral(cols, rows),
+            TileOrder::Hilbert => {
+                let n = cols.max(rows).next_power_of_two();
+                let mut cells: Vec<(u32, u32)> = (0..rows)
+                    .flat_map(|row| (0..cols).map(move |col| (col, row)))
+                    .collect();
+                cells.sort_by_key(|&(col, row)| hilbert_index(n, col, row));
+                cells
+            }
+        };
+        cells
+            .into_iter()
+            .map(|(col, row)| {
+                let x = col as u16 * size;
+                let y = row as u16 * size;
+                Tile::new(
+                    self.x + x,
+                    self.y + y,
+                    size.min(self.width - x),
+                    size.min(self.height - y),
+                )
+            })
+            .collect()
+    }
+}
+
+// Cells of a `cols` x `rows` grid, walking a square spiral out of the middle cell and skipping the
+// steps outside of the grid
+fn spiral(cols: u32, rows: u32) -> Vec<(u32, u32)> {
+    let total = (cols * rows) as usize;
+    let mut cells = Vec::with_capacity(total);
+    if total == 0 {
+        return cells;
+    }
+    let (mut col, mut row) = ((cols as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut len = 1;
+    let mut dir = 0;
+    cells.push((col as u32, row as u32));
+    while cells.len() < total {
+        // Every length is walked twice, once in each of the next two directions
+        for _ in 0..2 {
+            let (dc, dr) = directions[dir % 4];
+            for _ in 0..len {
+                col += dc;
+                row += dr;
+                if (0..cols as i64).contains(&col) && (0..rows as i64).contains(&row) {
+                    cells.push((col as u32, row as u32));
+                }
+            }
+            dir += 1;
+        }
+        len += 1;
+    }
+    cells
+}
+
+// Distance along the Hilbert curve filling an `n` x `n` grid (`n` a power of two) to (x,y)
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += (s as u64) * (s as u64) * ((3 * rx) ^ ry) as u64;
+        // Rotate the quadrant so the curve inside it starts and ends at the right corners
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+#[cfg(test)]
+mod tiles_tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_region_once() {
+        let region = Tile::new(3, 2, 23, 17);
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let tiles = region.split(5, order);
+            assert_eq!(tiles.len(), 5 * 4);
+            let mut covered = vec![0; 23 * 17];
+            for tile in tiles {
+                for row in tile.y..tile.y + tile.height {
+                    for col in tile.x..tile.x + tile.width {
+                        covered[(row - 2) as usize * 23 + (col - 3) as usize] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&count| count == 1));
+        }
+        let tiles = region.split(5, TileOrder::Scanline);
+        assert_eq!(tiles[0], Tile::new(3, 2, 5, 5));
+        assert_eq!(tiles[4], Tile::new(23, 2, 3, 5));
+        assert_eq!(tiles[19], Tile::new(23, 17, 3, 2));
+    }
+
+    #[test]
+    fn spiral_starts_in_the_middle() {
+        let tiles = Tile::new(0, 0, 30, 30).split(10, TileOrder::Spiral);
+        let corners: Vec<(u16, u16)> = tiles.iter().map(|tile| (tile.x, tile.y)).collect();
+        assert_eq!(
+            corners,
+            vec![
+                (10, 10),
+                (20, 10),
+                (20, 20),
+                (10, 20),
+                (0, 20),
+                (0, 10),
+                (0, 0),
+                (10, 0),
+                (20, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn consecutive_hilbert_tiles_touch() {
+        let tiles = Tile::new(0, 0, 64, 64).split(8, TileOrder::Hilbert);
+        assert_eq!(tiles[0], Tile::new(0, 0, 8, 8));
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].x as i32 - pair[1].x as i32).abs();
+            let dy = (pair[0].y as i32 - pair[1].y as i32).abs();
+            assert_eq!(dx + dy, 8);
+        }
+        // 3 x 2 tiles, the curve of the 4 x 4 grid leaves the image between some of them
+        let tiles = Tile::new(0, 0, 24, 16).split(8, TileOrder::Hilbert);
+        assert_eq!(tiles.len(), 6);
+        let jumps = tiles
+            .windows(2)
+            .filter(|pair| pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y) > 8)
+            .count();
+        assert!(jumps > 0);
+    }
+}
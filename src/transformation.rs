@@ -1,8 +1,9 @@
+use crate::Float;
 use crate::Matrix;
-use crate::Tuple;
+use crate::{Point3, Vector3};
 
 impl Matrix {
-    pub fn get_translation_matrix(x: f32, y: f32, z: f32) -> Self {
+    pub fn get_translation_matrix(x: Float, y: Float, z: Float) -> Self {
         let mut transformation_matrix = Matrix::I();
         transformation_matrix[0][3] = x;
         transformation_matrix[1][3] = y;
@@ -10,7 +11,7 @@ impl Matrix {
         transformation_matrix
     }
 
-    pub fn get_scaling_matrix(x: f32, y: f32, z: f32) -> Self {
+    pub fn get_scaling_matrix(x: Float, y: Float, z: Float) -> Self {
         let mut transformation_matrix: Matrix = Default::default();
         transformation_matrix[0][0] = x;
         transformation_matrix[1][1] = y;
@@ -20,7 +21,7 @@ impl Matrix {
     }
 
     // Get rotation matrix around +x direction, r is in radians
-    pub fn get_rotation_x_matrix(r: f32) -> Self {
+    pub fn get_rotation_x_matrix(r: Float) -> Self {
         let mut transformation_matrix = Matrix::I();
         transformation_matrix[1][1] = r.cos();
         transformation_matrix[1][2] = -r.sin();
@@ -30,7 +31,7 @@ impl Matrix {
     }
 
     // Get rotation matrix around +y direction, r is in radians
-    pub fn get_rotation_y_matrix(r: f32) -> Self {
+    pub fn get_rotation_y_matrix(r: Float) -> Self {
         let mut transformation_matrix = Matrix::I();
         transformation_matrix[0][0] = r.cos();
         transformation_matrix[0][2] = r.sin();
@@ -40,7 +41,7 @@ impl Matrix {
     }
 
     // Get rotation matrix around +z direction, r is in radians
-    pub fn get_rotation_z_matrix(r: f32) -> Self {
+    pub fn get_rotation_z_matrix(r: Float) -> Self {
         let mut transformation_matrix = Matrix::I();
         transformation_matrix[0][0] = r.cos();
         transformation_matrix[0][1] = -r.sin();
@@ -49,7 +50,36 @@ impl Matrix {
         transformation_matrix
     }
 
-    pub fn get_shearing_matrix(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+    // Get rotation matrix around an arbitrary `axis` (need not be normalized), r is in radians
+    // Rodrigues' rotation formula, rotation_x/y/z are its special cases
+    // A zero axis has no direction to turn around, it gives the identity
+    pub fn get_rotation_matrix(axis: Vector3, r: Float) -> Self {
+        let Some(a) = axis.try_normalize() else {
+            return Matrix::I();
+        };
+        let (sin, cos) = r.sin_cos();
+        let t = 1.0 - cos;
+        let mut transformation_matrix = Matrix::I();
+        transformation_matrix[0][0] = t * a.x * a.x + cos;
+        transformation_matrix[0][1] = t * a.x * a.y - sin * a.z;
+        transformation_matrix[0][2] = t * a.x * a.z + sin * a.y;
+        transformation_matrix[1][0] = t * a.x * a.y + sin * a.z;
+        transformation_matrix[1][1] = t * a.y * a.y + cos;
+        transformation_matrix[1][2] = t * a.y * a.z - sin * a.x;
+        transformation_matrix[2][0] = t * a.x * a.z - sin * a.y;
+        transformation_matrix[2][1] = t * a.y * a.z + sin * a.x;
+        transformation_matrix[2][2] = t * a.z * a.z + cos;
+        transformation_matrix
+    }
+
+    pub fn get_shearing_matrix(
+        xy: Float,
+        xz: Float,
+        yx: Float,
+        yz: Float,
+        zx: Float,
+        zy: Float,
+    ) -> Self {
         let mut transformation_matrix = Matrix::I();
         transformation_matrix[0][1] = xy;
         transformation_matrix[0][2] = xz;
@@ -60,12 +90,43 @@ impl Matrix {
         transformation_matrix
     }
 
+    // Fluent API to chain transformations in reading order
+    // `Matrix::I().rotate_x(r).scale(x, y, z).translate(x, y, z)` first rotates, then scales and then translates,
+    // it is the same as `translation * scaling * rotation`
+    pub fn translate(self, x: Float, y: Float, z: Float) -> Self {
+        Matrix::get_translation_matrix(x, y, z) * self
+    }
+
+    pub fn scale(self, x: Float, y: Float, z: Float) -> Self {
+        Matrix::get_scaling_matrix(x, y, z) * self
+    }
+
+    pub fn rotate_x(self, r: Float) -> Self {
+        Matrix::get_rotation_x_matrix(r) * self
+    }
+
+    pub fn rotate_y(self, r: Float) -> Self {
+        Matrix::get_rotation_y_matrix(r) * self
+    }
+
+    pub fn rotate_z(self, r: Float) -> Self {
+        Matrix::get_rotation_z_matrix(r) * self
+    }
+
+    pub fn rotate(self, axis: Vector3, r: Float) -> Self {
+        Matrix::get_rotation_matrix(axis, r) * self
+    }
+
+    pub fn shear(self, xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Self {
+        Matrix::get_shearing_matrix(xy, xz, yx, yz, zx, zy) * self
+    }
+
     /// Compute the view transform matrix to move the eye
     ///
     /// * `from`: position where the eye is
     /// * `to`: the point to look at
     /// * `up`: vector indicating the up direction
-    pub fn get_view_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix {
+    pub fn get_view_transform(from: Point3, to: Point3, up: Vector3) -> Matrix {
         let forward = (to - from).normalize();
         let left = forward.cross(&up.normalize());
         let true_up = left.cross(&forward);
@@ -86,8 +147,8 @@ impl Matrix {
 #[cfg(test)]
 mod transformation_tests {
     use super::*;
+    use crate::consts::PI;
     use crate::{point, vector};
-    use std::f32::consts::PI;
 
     #[test]
     fn point_translation1() {
@@ -158,7 +219,11 @@ mod transformation_tests {
         let p = point(0.0, 1.0, 0.0);
         let half_quarter = Matrix::get_rotation_x_matrix(PI / 4.0);
         let full_quarter = Matrix::get_rotation_x_matrix(PI / 2.0);
-        let _p1 = point(0.0, 2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0);
+        let _p1 = point(
+            0.0,
+            (2.0 as Float).sqrt() / 2.0,
+            (2.0 as Float).sqrt() / 2.0,
+        );
         let _p2 = point(0.0, 0.0, 1.0);
 
         assert_eq!(half_quarter * p, _p1);
@@ -170,7 +235,11 @@ mod transformation_tests {
         let p = point(0.0, 1.0, 0.0);
         let half_quarter = Matrix::get_rotation_x_matrix(PI / 4.0);
         let inv = half_quarter.inverse();
-        let _p = point(0.0, 2.0_f32.sqrt() / 2.0, -(2.0_f32.sqrt()) / 2.0);
+        let _p = point(
+            0.0,
+            (2.0 as Float).sqrt() / 2.0,
+            -((2.0 as Float).sqrt()) / 2.0,
+        );
         assert_eq!(inv * p, _p);
     }
 
@@ -179,7 +248,11 @@ mod transformation_tests {
         let p = point(0.0, 0.0, 1.0);
         let half_quarter = Matrix::get_rotation_y_matrix(PI / 4.0);
         let full_quarter = Matrix::get_rotation_y_matrix(PI / 2.0);
-        let _p1 = point(2.0_f32.sqrt() / 2.0, 0.0, 2.0_f32.sqrt() / 2.0);
+        let _p1 = point(
+            (2.0 as Float).sqrt() / 2.0,
+            0.0,
+            (2.0 as Float).sqrt() / 2.0,
+        );
         let _p2 = point(1.0, 0.0, 0.0);
 
         assert_eq!(half_quarter * p, _p1);
@@ -191,13 +264,42 @@ mod transformation_tests {
         let p = point(0.0, 1.0, 0.0);
         let half_quarter = Matrix::get_rotation_z_matrix(PI / 4.0);
         let full_quarter = Matrix::get_rotation_z_matrix(PI / 2.0);
-        let _p1 = point(-(2.0_f32.sqrt()) / 2.0, 2.0_f32.sqrt() / 2.0, 0.0);
+        let _p1 = point(
+            -((2.0 as Float).sqrt()) / 2.0,
+            (2.0 as Float).sqrt() / 2.0,
+            0.0,
+        );
         let _p2 = point(-1.0, 0.0, 0.0);
 
         assert_eq!(half_quarter * p, _p1);
         assert_eq!(full_quarter * p, _p2);
     }
 
+    #[test]
+    fn rotation_around_coordinate_axes() {
+        let r = 0.8;
+        let x = Matrix::get_rotation_matrix(vector(2.0, 0.0, 0.0), r);
+        let y = Matrix::get_rotation_matrix(vector(0.0, 1.0, 0.0), r);
+        let z = Matrix::get_rotation_matrix(vector(0.0, 0.0, 1.0), r);
+        assert_eq!(x, Matrix::get_rotation_x_matrix(r));
+        assert_eq!(y, Matrix::get_rotation_y_matrix(r));
+        assert_eq!(z, Matrix::get_rotation_z_matrix(r));
+    }
+
+    #[test]
+    fn rotation_around_zero_axis_is_identity() {
+        let t = Matrix::get_rotation_matrix(vector(0.0, 0.0, 0.0), 0.8);
+        assert_eq!(t, Matrix::I());
+    }
+
+    #[test]
+    fn rotation_around_arbitrary_axis() {
+        // A third of a full turn around the diagonal cycles the coordinate axes
+        let t = Matrix::I().rotate(vector(1.0, 1.0, 1.0), 2.0 * PI / 3.0);
+        assert_eq!(t * point(1.0, 0.0, 0.0), point(0.0, 1.0, 0.0));
+        assert_eq!(t * vector(0.0, 1.0, 0.0), vector(0.0, 0.0, 1.0));
+    }
+
     #[test]
     fn shear_x_according_y() {
         let transform = Matrix::get_shearing_matrix(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
@@ -276,6 +378,37 @@ mod transformation_tests {
         assert_eq!(T * p, point(15.0, 0.0, 7.0));
     }
 
+    #[test]
+    fn fluent_transformations_apply_in_reading_order() {
+        let p = point(1.0, 0.0, 1.0);
+        let t = Matrix::I()
+            .rotate_x(PI / 2.0)
+            .scale(5.0, 5.0, 5.0)
+            .translate(10.0, 5.0, 7.0);
+
+        assert_eq!(t * p, point(15.0, 0.0, 7.0));
+        assert_eq!(
+            t,
+            Matrix::get_translation_matrix(10.0, 5.0, 7.0)
+                * Matrix::get_scaling_matrix(5.0, 5.0, 5.0)
+                * Matrix::get_rotation_x_matrix(PI / 2.0)
+        );
+    }
+
+    #[test]
+    fn fluent_rotation_and_shearing() {
+        let t = Matrix::I()
+            .shear(1.0, 0.0, 0.0, 0.0, 0.0, 0.0)
+            .rotate_y(PI / 2.0)
+            .rotate_z(PI / 2.0);
+        assert_eq!(
+            t,
+            Matrix::get_rotation_z_matrix(PI / 2.0)
+                * Matrix::get_rotation_y_matrix(PI / 2.0)
+                * Matrix::get_shearing_matrix(1.0, 0.0, 0.0, 0.0, 0.0, 0.0)
+        );
+    }
+
     #[test]
     fn default_view_transform() {
         let from = point(0.0, 0.0, 0.0);
@@ -1,24 +1,20 @@
+use crate::simd;
 use crate::Compare;
+use crate::Float;
 
+// Laid out like an aligned array of 4 floats so that it can be loaded in a single SIMD register
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(feature = "f64"), repr(C, align(16)))]
+#[cfg_attr(feature = "f64", repr(C, align(32)))]
 pub struct Tuple {
-    pub x: f32, // x coordinate of the Tuple
-    pub y: f32, // y coordinate of the Tuple
-    pub z: f32, // z coordinate of the Tuple
-    pub w: f32, // indicator whether the Tuple is a vector(w=0.0) or a point(w=1.0). It is float as we need it for computation rather than an actual indicator
-}
-
-// Factory methods for creating vector and point
-pub fn vector(x: f32, y: f32, z: f32) -> Tuple {
-    Tuple::new(x, y, z, 0.0)
-}
-
-pub fn point(x: f32, y: f32, z: f32) -> Tuple {
-    Tuple::new(x, y, z, 1.0)
+    pub x: Float, // x coordinate of the Tuple
+    pub y: Float, // y coordinate of the Tuple
+    pub z: Float, // z coordinate of the Tuple
+    pub w: Float, // indicator whether the Tuple is a vector(w=0.0) or a point(w=1.0). It is float as we need it for computation rather than an actual indicator
 }
 
 impl Tuple {
-    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+    pub fn new(x: Float, y: Float, z: Float, w: Float) -> Self {
         Self { x, y, z, w }
     }
 
@@ -31,7 +27,7 @@ impl Tuple {
     }
 
     // Pythagoras theorem
-    pub fn magnitude(&self) -> f32 {
+    pub fn magnitude(&self) -> Float {
         (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
     }
 
@@ -41,18 +37,13 @@ impl Tuple {
     }
 
     // Dot product of 2 vectors
-    pub fn dot(&self, other: &Self) -> f32 {
-        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    pub fn dot(&self, other: &Self) -> Float {
+        simd::dot(self, other)
     }
 
     // Cross product of 2 vectors
     pub fn cross(&self, other: &Self) -> Self {
-        Self::new(
-            self.y * other.z - self.z * other.y,
-            self.z * other.x - self.x * other.z,
-            self.x * other.y - self.y * other.x,
-            0.0, // It still is a vector
-        )
+        simd::cross(self, other)
     }
 
     // Reflection vector of a vector with respect to a normal
@@ -105,16 +96,16 @@ impl std::ops::Neg for Tuple {
 }
 
 // For tuple * scalar
-impl std::ops::Mul<f32> for Tuple {
+impl std::ops::Mul<Float> for Tuple {
     type Output = Self;
 
-    fn mul(self, a: f32) -> Self {
+    fn mul(self, a: Float) -> Self {
         Self::new(self.x * a, self.y * a, self.z * a, self.w * a)
     }
 }
 
 // For scalar * tuple
-impl std::ops::Mul<Tuple> for f32 {
+impl std::ops::Mul<Tuple> for Float {
     type Output = Tuple;
 
     fn mul(self, a: Tuple) -> Tuple {
@@ -123,10 +114,10 @@ impl std::ops::Mul<Tuple> for f32 {
 }
 
 // For tuple / scalar
-impl std::ops::Div<f32> for Tuple {
+impl std::ops::Div<Float> for Tuple {
     type Output = Self;
 
-    fn div(self, a: f32) -> Self {
+    fn div(self, a: Float) -> Self {
         Self::new(self.x / a, self.y / a, self.z / a, self.w / a)
     }
 }
@@ -134,7 +125,8 @@ impl std::ops::Div<f32> for Tuple {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::f32::consts::FRAC_1_SQRT_2;
+    use crate::consts::FRAC_1_SQRT_2;
+    use crate::{point, vector};
 
     #[test]
     fn point_validity() {
@@ -160,14 +152,14 @@ mod tests {
 
     #[test]
     fn point_factory() {
-        let p = point(4.0, -4.0, 3.0);
+        let p = Tuple::from(point(4.0, -4.0, 3.0));
         let _p = Tuple::new(4.0, -4.0, 3.0, 1.0);
         assert_eq!(p, _p);
     }
 
     #[test]
     fn vector_factory() {
-        let p = vector(4.0, -4.0, 3.0);
+        let p = Tuple::from(vector(4.0, -4.0, 3.0));
         let _p = Tuple::new(4.0, -4.0, 3.0, 0.0);
         assert_eq!(p, _p);
     }
@@ -261,13 +253,13 @@ mod tests {
     #[test]
     fn vector_magnitude4() {
         let v = vector(1.0, 2.0, 3.0);
-        assert_eq!(v.magnitude(), f32::sqrt(14.0));
+        assert_eq!(v.magnitude(), Float::sqrt(14.0));
     }
 
     #[test]
     fn vector_magnitude5() {
         let v = vector(-1.0, -2.0, -3.0);
-        assert_eq!(v.magnitude(), f32::sqrt(14.0));
+        assert_eq!(v.magnitude(), Float::sqrt(14.0));
     }
 
     #[test]
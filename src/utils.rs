@@ -1,51 +1,155 @@
 // Utility functions or structs or traits
 
-pub const EPSILON: f32 = 1.0e-5;
+// Scalar type of all the math (tuples, matrices, colors etc.)
+// It is `f32` by default and `f64` when built with the `f64` feature
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+// Mathematical constants (PI etc.) in the precision of `Float`
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+pub const EPSILON: Float = 1.0e-5;
+
+// How far `over_point` and `under_point` are moved off a surface, so the rays leaving it do not
+// hit it again because of rounding errors (*acne*). f32 rounds about a thousand times more
+#[cfg(not(feature = "f64"))]
+pub const SURFACE_OFFSET: Float = 1.0e-3;
+#[cfg(feature = "f64")]
+pub const SURFACE_OFFSET: Float = EPSILON;
 
 pub trait Compare {
-    fn eq(self, other: f32) -> bool;
-    fn neq(self, other: f32) -> bool;
+    fn eq(self, other: Float) -> bool;
+    fn neq(self, other: Float) -> bool;
 }
 
-impl Compare for f32 {
-    fn eq(self, other: f32) -> bool {
+impl Compare for Float {
+    fn eq(self, other: Float) -> bool {
         (self - other).abs() < EPSILON
     }
 
-    fn neq(self, other: f32) -> bool {
+    fn neq(self, other: Float) -> bool {
         !self.eq(other)
     }
 }
 
+// `index`th element of the Halton low discrepancy sequence in `base`, lies between 0 and 1
+// Samples taken from it are spread more evenly than random ones, so fewer of them are needed
+pub fn halton(index: u32, base: u32) -> Float {
+    let mut result: Float = 0.0;
+    let mut fraction: Float = 1.0;
+    let mut i = index;
+    while i > 0 {
+        fraction /= base as Float;
+        result += fraction * (i % base) as Float;
+        i /= base;
+    }
+    result
+}
+
+// Scrambles the bits of `z` (splitmix64), close inputs give unrelated outputs
+pub(crate) fn splitmix64(z: u64) -> u64 {
+    let mut z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Small seedable random number generator (xorshift64*) for Monte Carlo sampling
+// Not cryptographically secure, but fast and the same seed always gives the same sequence,
+// which keeps renders reproducible and independent of the number of threads
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed so that close seeds give unrelated sequences
+        let z = splitmix64(seed);
+        // xorshift gets stuck at 0
+        Self {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniformly distributed in [0, 1)
+    // 24 bits fit exactly in both f32 and f64, so it never rounds up to 1
+    pub fn next_float(&mut self) -> Float {
+        (self.next_u64() >> 40) as Float / (1u32 << 24) as Float
+    }
+}
+
 #[cfg(test)]
 mod utility_test {
     use super::*;
 
     #[test]
     fn float_compare_equal1() {
-        let a: f32 = 3.3;
-        let b: f32 = 3.3;
+        let a: Float = 3.3;
+        let b: Float = 3.3;
         assert!(a.eq(b));
     }
 
     #[test]
     fn float_compare_equal2() {
-        let a: f32 = 0.0;
-        let b: f32 = 0.000005;
+        let a: Float = 0.0;
+        let b: Float = 0.000005;
         assert!(a.eq(b));
     }
 
     #[test]
     fn float_compare_not_equal1() {
-        let a: f32 = 3.3;
-        let b: f32 = 3.2;
+        let a: Float = 3.3;
+        let b: Float = 3.2;
         assert!(a.neq(b));
     }
 
     #[test]
     fn float_compare_not_equal2() {
-        let a: f32 = 3.3;
-        let b: f32 = 3.2;
+        let a: Float = 3.3;
+        let b: Float = 3.2;
         assert!(a.neq(b));
     }
+
+    #[test]
+    fn halton_sequence_base2() {
+        assert!(halton(1, 2).eq(0.5));
+        assert!(halton(2, 2).eq(0.25));
+        assert!(halton(3, 2).eq(0.75));
+        assert!(halton(4, 2).eq(0.125));
+    }
+
+    #[test]
+    fn halton_sequence_base3() {
+        assert!(halton(1, 3).eq(1.0 / 3.0));
+        assert!(halton(2, 3).eq(2.0 / 3.0));
+        assert!(halton(3, 3).eq(1.0 / 9.0));
+    }
+
+    #[test]
+    fn rng_is_deterministic_and_in_range() {
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        let mut sum = 0.0;
+        for _ in 0..1000 {
+            let value = a.next_float();
+            assert_eq!(value, b.next_float());
+            assert!((0.0..1.0).contains(&value));
+            sum += value;
+        }
+        // Roughly uniform
+        assert!((sum / 1000.0 - 0.5).abs() < 0.05);
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
 }
@@ -0,0 +1,219 @@
+use crate::simd;
+use crate::Compare;
+use crate::Float;
+use crate::{Matrix, Tuple};
+
+// A direction with a length, like a Tuple with w=0.0 but checked by the compiler
+// vector + vector = vector, point - point = vector (see `Point3`)
+#[derive(Clone, Copy, Debug)]
+pub struct Vector3 {
+    pub x: Float, // x component of the vector
+    pub y: Float, // y component of the vector
+    pub z: Float, // z component of the vector
+}
+
+// Factory method for creating vector
+pub fn vector(x: Float, y: Float, z: Float) -> Vector3 {
+    Vector3::new(x, y, z)
+}
+
+impl Vector3 {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
+        Self { x, y, z }
+    }
+
+    // Pythagoras theorem
+    pub fn magnitude(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    // We get a unit vector after normalization
+    pub fn normalize(&self) -> Self {
+        *self / self.magnitude()
+    }
+
+    // Unit vector in the same direction, `None` for the zero vector (and for one too long or with
+    // NaN components), which `normalize` would turn into NaN
+    pub fn try_normalize(&self) -> Option<Self> {
+        let magnitude = self.magnitude();
+        (magnitude > 0.0 && magnitude.is_finite()).then(|| *self / magnitude)
+    }
+
+    // Dot product of 2 vectors, done on Tuples (w=0.0 adds nothing) to use the SIMD versions
+    pub fn dot(&self, other: &Self) -> Float {
+        simd::dot(&Tuple::from(*self), &Tuple::from(*other))
+    }
+
+    // Cross product of 2 vectors
+    pub fn cross(&self, other: &Self) -> Self {
+        let t = simd::cross(&Tuple::from(*self), &Tuple::from(*other));
+        Self::new(t.x, t.y, t.z)
+    }
+
+    // Reflection vector of a vector with respect to a normal
+    // For derivation, see the chapter 6 README.md
+    pub fn reflect(&self, normal: &Self) -> Self {
+        *self - *normal * 2.0 * self.dot(normal)
+    }
+
+    // Two unit vectors which, together with this (unit) vector, form an orthonormal basis
+    // Branchless construction of Duff et al. "Building an Orthonormal Basis, Revisited"
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        let sign = Float::copysign(1.0, self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Self::new(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Self::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+}
+
+impl PartialEq for Vector3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.x.eq(other.x) && self.y.eq(other.y) && self.z.eq(other.z)
+    }
+}
+
+// Vector is a Tuple with w=0.0
+impl From<Vector3> for Tuple {
+    fn from(v: Vector3) -> Self {
+        Tuple::new(v.x, v.y, v.z, 0.0)
+    }
+}
+
+// Only Tuples with w=0.0 are vectors
+impl TryFrom<Tuple> for Vector3 {
+    type Error = String;
+
+    fn try_from(t: Tuple) -> Result<Self, Self::Error> {
+        if t.is_vector() {
+            Ok(Vector3::new(t.x, t.y, t.z))
+        } else {
+            Err(format!("Tuple with w={} is not a vector", t.w))
+        }
+    }
+}
+
+// For vector + vector
+impl std::ops::Add for Vector3 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+// For vector - vector
+impl std::ops::Sub for Vector3 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl std::ops::Neg for Vector3 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+// For vector * scalar
+impl std::ops::Mul<Float> for Vector3 {
+    type Output = Self;
+
+    fn mul(self, a: Float) -> Self {
+        Self::new(self.x * a, self.y * a, self.z * a)
+    }
+}
+
+// For scalar * vector
+impl std::ops::Mul<Vector3> for Float {
+    type Output = Vector3;
+
+    fn mul(self, a: Vector3) -> Vector3 {
+        Vector3::new(a.x * self, a.y * self, a.z * self)
+    }
+}
+
+// For vector / scalar
+impl std::ops::Div<Float> for Vector3 {
+    type Output = Self;
+
+    fn div(self, a: Float) -> Self {
+        Self::new(self.x / a, self.y / a, self.z / a)
+    }
+}
+
+// For matrix * vector
+// Translation does not affect vectors, only the upper 3x3 part of the matrix is used
+impl std::ops::Mul<Vector3> for Matrix {
+    type Output = Vector3;
+
+    fn mul(self, other: Vector3) -> Vector3 {
+        let t = self * Tuple::from(other);
+        Vector3::new(t.x, t.y, t.z)
+    }
+}
+
+#[cfg(test)]
+mod vector_tests {
+    use super::*;
+    use crate::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn vector_from_tuple() {
+        let v = Vector3::try_from(Tuple::new(4.0, -4.0, 3.0, 0.0));
+        assert_eq!(v, Ok(vector(4.0, -4.0, 3.0)));
+        assert!(Vector3::try_from(Tuple::new(4.0, -4.0, 3.0, 1.0)).is_err());
+    }
+
+    #[test]
+    fn scalar_times_vector() {
+        let v = vector(1.0, -2.0, 3.0);
+        assert_eq!(0.5 * v, vector(0.5, -1.0, 1.5));
+    }
+
+    #[test]
+    fn checked_normalization() {
+        assert_eq!(
+            vector(1.0, 2.0, -3.0).try_normalize(),
+            Some(vector(1.0, 2.0, -3.0).normalize())
+        );
+        assert_eq!(vector(0.0, 0.0, 0.0).try_normalize(), None);
+        assert_eq!(vector(Float::NAN, 0.0, 1.0).try_normalize(), None);
+    }
+
+    #[test]
+    fn orthonormal_basis_of_vector() {
+        for n in [
+            vector(0.0, 0.0, 1.0),
+            vector(0.0, 0.0, -1.0),
+            vector(1.0, 2.0, -3.0).normalize(),
+        ] {
+            let (t, b) = n.orthonormal_basis();
+            assert!(t.magnitude().eq(1.0) && b.magnitude().eq(1.0));
+            assert!(t.dot(&n).eq(0.0) && b.dot(&n).eq(0.0) && t.dot(&b).eq(0.0));
+        }
+    }
+
+    #[test]
+    fn translation_does_not_affect_vector() {
+        let transform = Matrix::get_translation_matrix(5.0, -3.0, 2.0);
+        let v = vector(-3.0, 4.0, 5.0);
+        assert_eq!(transform * v, v);
+    }
+
+    #[test]
+    fn inverse_transpose_keeps_vector_w_zero() {
+        // Inverse transpose of a translation has a non zero bottom row, which used to leak into w
+        let t = Matrix::get_translation_matrix(0.0, 1.0, 0.0)
+            .inverse()
+            .transpose();
+        let n = t * vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
+        assert!(n.magnitude().eq(1.0));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::Float;
+use std::io::{self, Read};
+
+// Encoding of the messages sent between machines, see `distributed`
+// Numbers are little endian, floats are sent as f64 whatever `Float` is so nothing is lost
+pub(crate) type WireFloat = f64;
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn put_u16(message: &mut Vec<u8>, value: u16) {
+    message.extend(value.to_le_bytes());
+}
+
+pub(crate) fn put_u32(message: &mut Vec<u8>, value: u32) {
+    message.extend(value.to_le_bytes());
+}
+
+pub(crate) fn put_float(message: &mut Vec<u8>, value: Float) {
+    message.extend((value as WireFloat).to_le_bytes());
+}
+
+// Length, then the bytes
+pub(crate) fn put_bytes(message: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(message, bytes.len() as u32);
+    message.extend(bytes);
+}
+
+fn get_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn get_u8(reader: &mut impl Read) -> io::Result<u8> {
+    Ok(get_array::<1>(reader)?[0])
+}
+
+pub(crate) fn get_u16(reader: &mut impl Read) -> io::Result<u16> {
+    Ok(u16::from_le_bytes(get_array(reader)?))
+}
+
+pub(crate) fn get_u32(reader: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(get_array(reader)?))
+}
+
+pub(crate) fn get_float(reader: &mut impl Read) -> io::Result<Float> {
+    Ok(WireFloat::from_le_bytes(get_array(reader)?) as Float)
+}
+
+// The buffer grows as the bytes arrive, a bogus length can not make it allocate everything at once
+pub(crate) fn get_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = get_u32(reader)? as u64;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
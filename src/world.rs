@@ -1,20 +1,112 @@
+use crate::medium;
+use crate::utils::Rng;
+use crate::Background;
+use crate::Camera;
+use crate::Canvas;
+use crate::Float;
 use crate::Light;
 use crate::Matrix;
+use crate::Point3;
 use crate::Ray;
 use crate::Sphere;
-use crate::Tuple;
+use crate::Vector3;
 use crate::{point, Color};
-use crate::{Computation, Intersections};
+use crate::{Computation, Intersection, Intersections};
+use crate::{Fog, Volume};
 
+// Points sampled along a ray going through fog or volumes, and along the shadow rays from them
+const MARCH_STEPS: u16 = 64;
+const SHADOW_MARCH_STEPS: u16 = 8;
+// Fog is only marched this far along the rays which miss everything
+const FOG_DISTANCE: Float = 100.0;
+
+// Transparent surfaces seen through one after another along a ray by `color_at`
+const TRANSPARENT_DEPTH: u16 = 5;
+
+// What is seen through a pixel, see `World::pick`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pick {
+    pub id: u64,         // Id of the object
+    pub point: Point3,   // Point of the object seen
+    pub normal: Vector3, // Normal of the surface there, facing the camera
+    pub distance: Float, // Distance from the camera
+}
+
+#[derive(Clone)]
 pub struct World {
     pub light: Light,
-    pub objects: Vec<Sphere>,
+    objects: Vec<Sphere>, // Only added with `add_object`, which gives them their id
+    pub background: Background, // Seen by the rays which miss every object, also lights the scene
+    pub textures: Vec<Canvas>, // Images of the texture patterns, see `add_texture`
+    pub fog: Option<Fog>, // Medium filling the whole world
+    pub volumes: Vec<Volume>, // Media filling the inside of spheres
 }
 
 impl World {
+    // World without any object, lit by `light`
+    pub fn new(light: Light) -> Self {
+        Self {
+            light,
+            objects: Vec::new(),
+            background: Background::default(),
+            textures: Vec::new(),
+            fog: None,
+            volumes: Vec::new(),
+        }
+    }
+
+    // Add an image for the texture patterns, returns its id
+    pub fn add_texture(&mut self, image: Canvas) -> usize {
+        self.textures.push(image);
+        self.textures.len() - 1
+    }
+
+    // Add an object, returns the id it gets. Ids follow the order the objects are added in, so
+    // the same scene always gets the same ids
+    pub fn add_object(&mut self, mut object: Sphere) -> u64 {
+        let id = self.objects.iter().map(Sphere::id).max().unwrap_or(0) + 1;
+        object.set_id(id);
+        self.objects.push(object);
+        id
+    }
+
+    // Objects of the world, in the order they were added
+    pub fn objects(&self) -> &[Sphere] {
+        &self.objects
+    }
+
+    // Object with the id `id`
+    pub fn object(&self, id: u64) -> Option<&Sphere> {
+        self.objects.iter().find(|object| object.id() == id)
+    }
+
+    pub fn object_mut(&mut self, id: u64) -> Option<&mut Sphere> {
+        self.objects.iter_mut().find(|object| object.id() == id)
+    }
+
+    // Takes the object with the id `id` out of the world, the others keep their ids
+    pub fn remove_object(&mut self, id: u64) -> Option<Sphere> {
+        let ind = self.objects.iter().position(|object| object.id() == id)?;
+        Some(self.objects.remove(ind))
+    }
+
+    // Object seen through the center of the (x, y) pixel of `camera`, for click to select
+    // `None` when the pixel only sees the background
+    pub fn pick(&self, camera: &Camera, x: u16, y: u16) -> Option<Pick> {
+        let ray = camera.ray_for_pixel(x, y).at_time(camera.shutter_open);
+        let hit = self.intersect_world(ray).hit()?;
+        let comps = hit.prepare_computations(&ray);
+        Some(Pick {
+            id: hit.object.id(),
+            point: comps.point,
+            normal: comps.normalv,
+            distance: hit.t,
+        })
+    }
+
     // Returns the intersections of the ray with all objects in the world
     // sorted by the `t` value
-    fn intersect_world(&self, ray: Ray) -> Intersections {
+    pub(crate) fn intersect_world(&self, ray: Ray) -> Intersections {
         let mut xs: Intersections = Default::default();
         for obj in &self.objects {
             let obj_xs = obj.intersect(ray);
@@ -24,42 +116,293 @@ impl World {
         xs
     }
 
+    // Closest hit of `ray`, together with the index of the hit object in `objects`
+    pub(crate) fn hit_object(&self, ray: Ray) -> Option<(usize, Intersection)> {
+        let mut closest: Option<(usize, Intersection)> = None;
+        for (ind, obj) in self.objects.iter().enumerate() {
+            if let Some(hit) = obj.intersect(ray).hit() {
+                if closest.is_none_or(|(_, closest)| hit.t < closest.t) {
+                    closest = Some((ind, hit));
+                }
+            }
+        }
+        closest
+    }
+
+    // Computation object for the `hit` of `ray`, with the pattern and the bumps of the material
+    // evaluated at the hit
+    pub(crate) fn prepare_computations(&self, hit: &Intersection, ray: &Ray) -> Computation {
+        let mut comps = hit.prepare_computations(ray);
+        if let Some(pattern) = comps.material.pattern {
+            let object_point = comps.object.world_to_object(comps.point);
+            comps.material.color = pattern.color_at(object_point, &self.textures);
+        }
+        if let Some(bump) = comps.material.bump {
+            // `over_point` stays above the real surface, only the shading sees the new normal
+            comps.normalv = bump.shading_normal(&comps, &self.textures);
+        }
+        comps
+    }
+
     // Compute the color at the intersection point via computation object
+    // Glowing objects add their own emission, but only the point light lights the scene here-
+    // the light of the emitters and of the background is sampled by the path tracer
     fn shade_hit(&self, comps: &Computation) -> Color {
-        let in_shadow = self.is_shadowed(comps.over_point);
-        comps.object.material.lighting(
+        let surface = comps.material.lighting_filtered(
             self.light,
-            comps.over_point,
+            comps.point,
             comps.eyev,
             comps.normalv,
-            in_shadow,
-        )
+            self.hit_transmission(comps),
+        );
+        surface + comps.material.emission
     }
 
     // Get the color at the intersection point of the ray
-    pub fn color_at(&self, ray: &Ray) -> Color {
+    // `rng` offsets the steps marched through the media, it comes from the pixel being rendered
+    // Fog and volumes between the eye and the hit dim it and add the light they scatter
+    pub fn color_at(&self, ray: &Ray, rng: &mut Rng) -> Color {
+        self.color_through(ray, rng, TRANSPARENT_DEPTH)
+    }
+
+    // Color along `ray`, seeing through up to `depth` transparent surfaces
+    // Transparent surfaces blend their own shading with what is behind them, tinted by their color
+    fn color_through(&self, ray: &Ray, rng: &mut Rng, depth: u16) -> Color {
         let xs = self.intersect_world(*ray);
-        match xs.hit() {
+        let (color, distance) = match xs.hit() {
             Some(i) => {
-                let comp = i.prepare_computations(ray);
-                self.shade_hit(&comp)
+                let comp = self.prepare_computations(&i, ray);
+                let mut color = self.shade_hit(&comp);
+                let transparency = comp.material.transparency;
+                if transparency > 0.0 && depth > 0 {
+                    let through = Ray::new(comp.under_point, ray.direction).at_time(comp.time);
+                    let behind = self.color_through(&through, rng, depth - 1);
+                    color =
+                        color * (1.0 - transparency) + behind * comp.material.color * transparency;
+                }
+                (color, i.t)
+            }
+            None => (self.background.color(ray.direction), Float::INFINITY),
+        };
+        let (transmittance, scattered) = self.march_media(ray, distance, rng);
+        color * transmittance + scattered
+    }
+
+    // Ray march the fog and the volumes along `ray`, up to `distance` (the hit, or infinity)
+    // Returns the fraction of the light from `distance` which reaches the origin of the ray,
+    // and the light of the point light scattered towards the origin on the way
+    // Steps are shifted by a random offset, so the banding of the steps turns into noise
+    pub(crate) fn march_media(&self, ray: &Ray, distance: Float, rng: &mut Rng) -> (Color, Color) {
+        // Parts of the ray inside each medium
+        let mut spans: Vec<(Float, Float)> = Vec::new();
+        if self.fog.is_some() {
+            let end = if distance.is_finite() {
+                distance
+            } else {
+                FOG_DISTANCE
+            };
+            spans.push((0.0, end));
+        }
+        for volume in &self.volumes {
+            if let Some((t0, t1)) = volume.span(*ray) {
+                spans.push((t0.max(0.0), t1.min(distance)));
+            }
+        }
+        spans.retain(|&(start, end)| start < end);
+
+        // The same media fill the ray between two consecutive ends of the spans, each of these
+        // segments gets its own steps so a small volume is sampled as finely as the whole fog
+        let mut ends: Vec<Float> = spans
+            .iter()
+            .flat_map(|&(start, end)| [start, end])
+            .collect();
+        ends.sort_by(Float::total_cmp);
+        ends.dedup();
+        let mut transmittance = Color::white();
+        let mut scattered = Color::black();
+        for segment in ends.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+            let middle = (start + end) / 2.0;
+            if !spans.iter().any(|&(t0, t1)| t0 <= middle && middle <= t1) {
+                continue; // Gap between two volumes
+            }
+            let step = (end - start) / MARCH_STEPS as Float;
+            let offset = rng.next_float();
+            for i in 0..MARCH_STEPS {
+                let p = ray.position(start + (i as Float + offset) * step);
+                let (extinction, scattering) = self.media_at(p);
+                if extinction.max_component() <= 0.0 {
+                    continue;
+                }
+                if scattering.max_component() > 0.0 {
+                    scattered = scattered
+                        + transmittance * scattering * self.light_in_media(p, ray.time) * step;
+                }
+                transmittance = transmittance * medium::transmittance(extinction * step);
             }
-            None => Color::black(),
         }
+        (transmittance, scattered)
     }
 
-    // Compute whether the point is under a shadow
-    // See README for explanation
-    pub fn is_shadowed(&self, point: Tuple) -> bool {
+    // Extinction and scattering coefficients at `p`, summed over the fog and the volumes
+    fn media_at(&self, p: Point3) -> (Color, Color) {
+        let mut extinction = Color::black();
+        let mut scattering = Color::black();
+        if let Some(fog) = self.fog {
+            let density = fog.density(p);
+            extinction = extinction + fog.medium().extinction() * density;
+            scattering = scattering + fog.medium().scattering * density;
+        }
+        for volume in self.volumes.iter().filter(|volume| volume.contains(p)) {
+            extinction = extinction + volume.medium.extinction();
+            scattering = scattering + volume.medium.scattering;
+        }
+        (extinction, scattering)
+    }
+
+    // Light of the point light scattered towards any direction at `p` inside a medium
+    // Scattering is isotropic, 1 / 4pi of the light goes to each unit of solid angle, and the light
+    // brings I * pi like on the surfaces (see `Material::brdf`). On the way from the light the
+    // objects cast shadows (the god rays) and the media dim it
+    fn light_in_media(&self, p: Point3, time: Float) -> Color {
+        let shadow = self.shadow_transmission(p, time);
+        if shadow.max_component() <= 0.0 {
+            return Color::black();
+        }
+        let v = self.light.position - p;
+        let step = v / SHADOW_MARCH_STEPS as Float;
+        let mut optical_depth = Color::black();
+        for i in 0..SHADOW_MARCH_STEPS {
+            let (extinction, _) = self.media_at(p + step * (i as Float + 0.5));
+            optical_depth = optical_depth + extinction;
+        }
+        let transmittance = medium::transmittance(optical_depth * step.magnitude());
+        self.light.intensity * shadow * transmittance * 0.25
+    }
+
+    // Light reaching the hit from the emissive objects and reflected towards the eye
+    // Averages `samples` random points on the surface of every emitter (other than the hit object),
+    // each one weighted by how much of the hemisphere above the hit it covers
+    pub(crate) fn emitted_light(&self, comps: &Computation, samples: u16, rng: &mut Rng) -> Color {
+        let material = comps.material;
+        let mut total = Color::black();
+        let emitters = self
+            .objects
+            .iter()
+            .filter(|obj| obj.material.emission.max_component() > 0.0 && !obj.is(&comps.object));
+        for emitter in emitters {
+            for _ in 0..samples {
+                let (light_point, light_normal, pdf) = emitter
+                    .at_time(comps.time)
+                    .sample_surface(rng.next_float(), rng.next_float());
+                let v = light_point - comps.over_point;
+                let distance_squared = v.dot(&v);
+                let lightv = v / distance_squared.sqrt();
+                let cos_surface = lightv.dot(&comps.normalv);
+                let cos_light = -lightv.dot(&light_normal);
+                if cos_surface <= 0.0 || cos_light <= 0.0 {
+                    continue;
+                }
+                let transmission = if material.receives_shadow {
+                    self.transmission_between(comps.over_point, light_point, comps.time)
+                } else {
+                    Color::white()
+                };
+                if transmission.max_component() <= 0.0 {
+                    continue;
+                }
+                let brdf = material.brdf(comps.normalv, comps.eyev, lightv);
+                let factor = cos_surface * cos_light / (distance_squared * pdf);
+                total = total + emitter.material.emission * transmission * brdf * factor;
+            }
+        }
+        total * (1.0 / samples.max(1) as Float)
+    }
+
+    // Random direction from the background lighting the hit, with the light coming from it
+    // (dimmed by the transparent objects on the way) and its probability density
+    // `None` when the direction is below the surface or blocked
+    pub(crate) fn sample_background(
+        &self,
+        comps: &Computation,
+        rng: &mut Rng,
+    ) -> Option<(Vector3, Color, Float)> {
+        let (lightv, pdf) = self.background.sample(rng);
+        if pdf <= 0.0 || lightv.dot(&comps.normalv) <= 0.0 {
+            return None;
+        }
+        let mut radiance = self.background.color(lightv);
+        if comps.material.receives_shadow {
+            let ray = Ray::new(comps.over_point, lightv).at_time(comps.time);
+            radiance = radiance * self.transmission(ray, Float::INFINITY);
+            if radiance.max_component() <= 0.0 {
+                return None;
+            }
+        }
+        Some((lightv, radiance, pdf))
+    }
+
+    // Light getting from `from` to `to` at `time`, through the objects between them
+    // Hits in the last 0.1% of the way are ignored, as `to` is usually on the surface of an object
+    fn transmission_between(&self, from: Point3, to: Point3, time: Float) -> Color {
+        let v = to - from;
+        let distance = v.magnitude();
+        let r = Ray::new(from, v / distance).at_time(time);
+        self.transmission(r, distance * 0.999)
+    }
+
+    // Fraction of the light going along `ray` which gets through the objects casting shadows
+    // before `distance`. Every time the ray goes through the surface of a transparent object,
+    // `transparency` of the light gets through tinted by the color of the object, so glass casts
+    // a light colored shadow. Opaque objects block all of it, the search stops at the first one
+    // instead of collecting and sorting all the intersections
+    pub(crate) fn transmission(&self, ray: Ray, distance: Float) -> Color {
+        let mut transmission = Color::white();
+        for obj in self.objects.iter().filter(|obj| obj.material.casts_shadow) {
+            let crossings = obj.crossings_before(ray, distance);
+            if crossings == 0 {
+                continue;
+            }
+            let material = obj.material;
+            if material.transparency <= 0.0 {
+                return Color::black();
+            }
+            for _ in 0..crossings {
+                transmission = transmission * material.color * material.transparency;
+            }
+        }
+        transmission
+    }
+
+    // Fraction of the light of the point light reaching the hit of `comps`
+    // All of it for the materials which do not receive shadows
+    pub(crate) fn hit_transmission(&self, comps: &Computation) -> Color {
+        if !comps.material.receives_shadow {
+            return Color::white();
+        }
+        self.shadow_transmission(comps.over_point, comps.time)
+    }
+
+    // Fraction of the light of the point light reaching `point`, black in a full shadow
+    // and white when nothing is in the way. Moving objects are where they are at `time`
+    pub fn shadow_transmission(&self, point: Point3, time: Float) -> Color {
         let v = self.light.position - point;
         let distance = v.magnitude();
         let direction = v.normalize();
 
-        let r = Ray::new(point, direction);
-        let intersections = self.intersect_world(r);
-        let h = intersections.hit();
+        let r = Ray::new(point, direction).at_time(time);
+        self.transmission(r, distance)
+    }
+
+    // Compute whether the point is under a shadow, where no light at all gets to it
+    // See README for explanation
+    pub fn is_shadowed(&self, point: Point3) -> bool {
+        self.is_shadowed_at(point, 0.0)
+    }
 
-        h.is_some() && (h.unwrap().t < distance)
+    // Same as `is_shadowed` with the moving objects where they are at `time`
+    pub fn is_shadowed_at(&self, point: Point3, time: Float) -> bool {
+        self.shadow_transmission(point, time).max_component() <= 0.0
     }
 }
 
@@ -75,10 +418,10 @@ impl Default for World {
         let mut s2: Sphere = Default::default();
         s2.set_transform(Matrix::get_scaling_matrix(0.5, 0.5, 0.5));
 
-        Self {
-            light,
-            objects: vec![s1, s2],
-        }
+        let mut world = Self::new(light);
+        world.add_object(s1);
+        world.add_object(s2);
+        world
     }
 }
 
@@ -87,6 +430,38 @@ mod world_test {
     use super::*;
     use crate::vector;
     use crate::Intersection;
+    use crate::{Medium, Pattern, UvMapping, WrapMode};
+
+    #[test]
+    fn picking_an_object() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, crate::consts::FRAC_PI_2);
+        c.set_transform(Matrix::get_view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let pick = w.pick(&c, 5, 5).unwrap();
+        assert_eq!(pick.id, w.objects[0].id());
+        assert_eq!(pick.point, point(0.0, 0.0, -1.0));
+        assert_eq!(pick.normal, vector(0.0, 0.0, -1.0));
+        assert_eq!(pick.distance, 4.0);
+        assert_eq!(w.pick(&c, 0, 0), None);
+        // The id finds the object wherever it is in the list
+        let mut w = w;
+        w.objects.swap(0, 1);
+        assert_eq!(
+            w.object(pick.id).unwrap().material.color,
+            Color::new(0.8, 1.0, 0.6)
+        );
+        w.object_mut(pick.id).unwrap().material.diffuse = 0.2;
+        assert_eq!(w.objects[1].material.diffuse, 0.2);
+        assert_eq!(w.object(0), None);
+        // Every object added gets its own id, copies too
+        let copy = w.objects[0];
+        assert_eq!(w.add_object(copy), 3);
+        assert!(!w.objects[2].is(&w.objects[0]));
+    }
 
     #[test]
     fn check_default_world() {
@@ -147,7 +522,7 @@ mod world_test {
     fn color_at_when_ray_misses() {
         let w = World::default();
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
-        let c = w.color_at(&r);
+        let c = w.color_at(&r, &mut Rng::new(0));
         assert_eq!(c, Color::new(0.0, 0.0, 0.0));
     }
 
@@ -155,7 +530,7 @@ mod world_test {
     fn color_at_when_ray_hits() {
         let w = World::default();
         let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
-        let c = w.color_at(&r);
+        let c = w.color_at(&r, &mut Rng::new(0));
         assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
@@ -166,7 +541,7 @@ mod world_test {
         inner.material.ambient = 1.0;
         let inner_color = inner.material.color; // Needed due to Rust's borrow checker
         let r = Ray::new(point(0.0, 0.0, 0.75), vector(0.0, 0.0, -1.0));
-        let c = w.color_at(&r);
+        let c = w.color_at(&r, &mut Rng::new(0));
         assert_eq!(c, inner_color);
     }
 
@@ -206,6 +581,10 @@ mod world_test {
         let w = World {
             light: Light::new(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0)),
             objects: vec![s1, s2],
+            background: Background::default(),
+            textures: Vec::new(),
+            fog: None,
+            volumes: Vec::new(),
         };
 
         let r = Ray::new(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
@@ -214,4 +593,209 @@ mod world_test {
         let c = w.shade_hit(&comps);
         assert_eq!(c, Color::new(0.1, 0.1, 0.1));
     }
+
+    #[test]
+    fn transparent_sphere_casts_a_tinted_shadow() {
+        let mut glass = Sphere::default();
+        glass.material.color = Color::new(1.0, 0.5, 0.0);
+        glass.material.transparency = 0.5;
+        let w = World {
+            light: Light::new(point(0.0, 0.0, -10.0), Color::white()),
+            objects: vec![glass],
+            ..Default::default()
+        };
+        // Through both sides of the sphere
+        let shadow = w.shadow_transmission(point(0.0, 0.0, 10.0), 0.0);
+        assert_eq!(shadow, Color::new(0.25, 0.0625, 0.0));
+        assert!(!w.is_shadowed(point(0.0, 0.0, 10.0)));
+        // From inside only one side is in the way
+        let shadow = w.shadow_transmission(point(0.0, 0.0, 0.0), 0.0);
+        assert_eq!(shadow, Color::new(0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    fn color_at_sees_through_transparent_sphere() {
+        let mut glass = Sphere::default();
+        glass.material.transparency = 1.0;
+        let w = World {
+            objects: vec![glass],
+            background: Background::Solid(Color::new(0.2, 0.4, 0.6)),
+            ..Default::default()
+        };
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(&r, &mut Rng::new(0)), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn shadow_flags_of_the_materials() {
+        let mut w = World::default();
+        let p = point(10.0, -10.0, 10.0);
+        assert!(w.is_shadowed(p));
+        // Both spheres are in the way, the light goes through them when neither casts shadows
+        w.objects[0].material.casts_shadow = false;
+        assert!(w.is_shadowed(p));
+        w.objects[1].material.casts_shadow = false;
+        assert!(!w.is_shadowed(p));
+
+        // Point on the outer sphere facing away from the light, it shadows itself
+        let w = World {
+            light: Light::new(point(0.0, 0.0, 10.0), Color::white()),
+            ..Default::default()
+        };
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let comps = Intersection::new(4.0, w.objects[0]).prepare_computations(&r);
+        assert_eq!(w.hit_transmission(&comps), Color::black());
+        let mut comps = comps;
+        comps.material.receives_shadow = false;
+        assert_eq!(w.hit_transmission(&comps), Color::white());
+    }
+
+    #[test]
+    fn emissive_object_glows() {
+        let mut w = World::default();
+        w.objects[0].material.emission = Color::new(0.5, 0.25, 0.0);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let c = w.color_at(&r, &mut Rng::new(0));
+        assert_eq!(c, Color::new(0.88066, 0.72583, 0.2855));
+    }
+
+    #[test]
+    fn only_the_point_light_lights_the_scene() {
+        // The default world of the book, under a white sky and next to a lamp, still gives the
+        // colors of the book. The lamp and the sky only light the scene in the path tracer
+        let mut w = World {
+            background: Background::Solid(Color::white()),
+            ..Default::default()
+        };
+        let mut lamp = Sphere::default();
+        lamp.set_transform(Matrix::get_translation_matrix(-3.0, 0.0, -3.0));
+        lamp.material.emission = Color::new(2.0, 2.0, 2.0);
+        w.add_object(lamp);
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let c = w.color_at(&r, &mut Rng::new(0));
+        assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(c, w.color_at(&r, &mut Rng::new(1)));
+    }
+
+    #[test]
+    fn color_at_when_ray_misses_shows_background() {
+        let w = World {
+            background: Background::Solid(Color::new(0.2, 0.3, 0.4)),
+            ..Default::default()
+        };
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(&r, &mut Rng::new(0)), Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn color_at_uses_texture_pattern() {
+        // Left half red, right half blue
+        let mut image = Canvas::new(2, 1);
+        image.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        image.write_pixel(1, 0, Color::new(0.0, 0.0, 1.0));
+        let mut w = World::default();
+        let id = w.add_texture(image);
+        let mut s = Sphere::default();
+        s.material.ambient = 1.0;
+        s.material.diffuse = 0.0;
+        s.material.specular = 0.0;
+        s.material.pattern = Some(Pattern::texture(id, UvMapping::Spherical, WrapMode::Clamp));
+        w.objects = vec![s];
+
+        // Seen from the front, the middle of the image faces the camera and x goes to the right
+        let left = Ray::new(point(-0.5, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let c = w.color_at(&left, &mut Rng::new(0));
+        assert!(c.red > c.blue && c.green == 0.0);
+        let right = Ray::new(point(0.5, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let c = w.color_at(&right, &mut Rng::new(0));
+        assert!(c.blue > c.red && c.green == 0.0);
+    }
+
+    #[test]
+    fn absorbing_fog_dims_the_hit_and_the_background() {
+        let clear = World {
+            background: Background::Solid(Color::white()),
+            ..World::default()
+        };
+        let medium = Medium::new(Color::new(0.1, 0.1, 0.1), Color::black());
+        let w = World {
+            fog: Some(Fog::Homogeneous(medium)),
+            background: Background::Solid(Color::white()),
+            ..World::default()
+        };
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        // The hit is 4 units away
+        let expected = clear.color_at(&r, &mut Rng::new(0)) * (-0.4 as Float).exp();
+        assert_eq!(w.color_at(&r, &mut Rng::new(0)), expected);
+
+        let up = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+        let expected = Color::white() * (-0.1 * FOG_DISTANCE).exp();
+        assert_eq!(w.color_at(&up, &mut Rng::new(0)), expected);
+    }
+
+    #[test]
+    fn fog_reaches_hits_beyond_the_fog_distance() {
+        let medium = Medium::new(Color::new(0.01, 0.01, 0.01), Color::black());
+        let w = World {
+            fog: Some(Fog::Homogeneous(medium)),
+            ..World::default()
+        };
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let distance = 2.5 * FOG_DISTANCE;
+        let (transmittance, _) = w.march_media(&r, distance, &mut Rng::new(0));
+        let expected = (-0.01 * distance).exp();
+        assert!(
+            (transmittance.red - expected).abs() < 1e-4,
+            "{:?}",
+            transmittance
+        );
+    }
+
+    #[test]
+    fn small_volume_in_fog_is_marched_finely() {
+        // Clear fog all the way to FOG_DISTANCE and a volume 0.1 units across
+        let fog = Medium::new(Color::new(0.001, 0.001, 0.001), Color::black());
+        let smoke = Medium::new(Color::new(5.0, 5.0, 5.0), Color::black());
+        let w = World {
+            objects: Vec::new(),
+            fog: Some(Fog::Homogeneous(fog)),
+            volumes: vec![Volume::new(
+                Matrix::get_scaling_matrix(0.05, 0.05, 0.05),
+                smoke,
+            )],
+            ..World::default()
+        };
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let (transmittance, _) = w.march_media(&r, Float::INFINITY, &mut Rng::new(0));
+        let expected = (-0.001 * FOG_DISTANCE - 5.0 * 0.1 as Float).exp();
+        assert!(
+            (transmittance.red - expected).abs() < 1e-3,
+            "{:?}",
+            transmittance
+        );
+    }
+
+    #[test]
+    fn volume_scatters_the_light() {
+        // Thin volume, light gets through almost undimmed
+        let medium = Medium::new(Color::black(), Color::new(0.01, 0.01, 0.01));
+        let mut w = World {
+            light: Light::new(point(0.0, 10.0, 0.0), Color::white()),
+            objects: Vec::new(),
+            volumes: vec![Volume::new(Matrix::I(), medium)],
+            ..World::default()
+        };
+        // 2 units through the volume, each scattering 0.01 * 1/4 of the light towards the eye
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let c = w.color_at(&r, &mut Rng::new(0));
+        assert!((c.red - 0.005).abs() < 0.0002, "{:?}", c);
+        let miss = Ray::new(point(0.0, 5.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(&miss, &mut Rng::new(0)), Color::black());
+
+        // An object between the light and the volume casts a shadow through it
+        let mut blocker = Sphere::default();
+        blocker.set_transform(Matrix::get_scaling_matrix(3.0, 0.1, 3.0).translate(0.0, 5.0, 0.0));
+        w.objects.push(blocker);
+        assert_eq!(w.color_at(&r, &mut Rng::new(0)), Color::black());
+    }
 }